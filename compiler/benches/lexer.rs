@@ -0,0 +1,89 @@
+//! Measures lexer throughput over large generated `.ptn` files.
+//!
+//! Run with `cargo bench --bench lexer`. Only the current lexer is measured.
+//! The "before" numbers for the hand-written scanner came from copying this
+//! file into its parent commit, where `Lexer` still tried every rule in the
+//! regex table, and running the same command there. The files are generated
+//! the same way every time, so both runs lexed identical input.
+
+use std::{
+    fmt::Write,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use compiler::lexer::Lexer;
+
+const ROUNDS: usize = 5;
+
+/// A chunk of typical code, with `{n}` replaced by a number so that
+/// identifiers and literals vary across the file.
+const PROGRAM: &str = r#"
+// Shapes and their areas, number {n}.
+struct Point{n} { x: Float, y: Float }
+
+enum Shape{n} {
+    Circle(Float),
+    Rect { w: Int, h: Int },
+    Empty,
+}
+
+const scale_{n}: Float = 1.5e-3 * {n}.25
+const name_{n}: Str = "shape \"{n}\"\n"
+
+fn area_{n}(shape: Shape{n}): Float -> match shape {
+    Shape{n}::Circle(r) -> 3.14159 * r ** 2.,
+    Shape{n}::Rect { w, h: 1 } -> .5,
+    Shape{n}::Rect { w, h } -> to_float(w * h),
+    Shape{n}::Empty -> 0.0,
+}
+
+fn main_{n}() -> {
+    let mut total = {n};
+    let shapes = [Shape{n}::Circle(1.), Shape{n}::Rect { w: 4, h: {n} }, Shape{n}::Empty];
+    let add = |by| -> total = total + by;
+    if (total >= 10 && total != 12 || !false) add(1) else add(-1);
+    let sep = '\n';
+    shapes |> len >> add;
+    (total << 2, total >> 1, total ^ 3, sep, name_{n})
+}
+"#;
+
+/// The same code with runs of characters that can't start a token mixed in.
+const STRAY: &str = r#"
+fn broken_{n}() -> $$$$$$$$$$$$$$$$$$$$$$$$$$$$$$$$ + {n} @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+const weird_{n}: Int = ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~{n}
+"#;
+
+/// Repeats `template` until the result is at least `size` bytes long.
+fn generate(template: &str, size: usize) -> String {
+    let mut source = String::with_capacity(size + template.len());
+    let mut n = 0;
+    while source.len() < size {
+        write!(source, "{}", template.replace("{n}", &n.to_string())).unwrap();
+        n += 1;
+    }
+    source
+}
+
+fn bench(name: &str, source: &str) {
+    let mut best = Duration::MAX;
+    let mut tokens = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        tokens = black_box(Lexer::new(source).tokenize()).len();
+        best = best.min(start.elapsed());
+    }
+
+    let mib = source.len() as f64 / (1024. * 1024.);
+    println!(
+        "{name:<8} {mib:>6.2} MiB {tokens:>9} tokens {:>10.2?} {:>9.2} MiB/s",
+        best,
+        mib / best.as_secs_f64()
+    );
+}
+
+fn main() {
+    bench("program", &generate(PROGRAM, 4 << 20));
+    bench("stray", &generate(STRAY, 1 << 20));
+}
@@ -0,0 +1,230 @@
+pub const USAGE: &str = "\
+usage: patinac <command> [options] <file>
+       patinac repl [--error-format=<format>]
+
+commands:
+  lex      print the tokens in <file>, unless `--emit` says otherwise
+  parse    print the syntax tree of <file>, unless `--emit` says otherwise
+  check    check <file> for errors without running it
+  run      run <file>'s `main` function
+  build    compile <file> to a module for the runtime
+  repl     read items and expressions interactively, printing the value and
+           type of each expression
+
+options:
+  --emit=<kinds>          also print any of `tokens`, `ast` and `typed-ast`,
+                          separated by commas
+  --error-format=<format> print errors as `human` text (the default) or as
+                          one line of `json` each
+  -o, --output <path>     where `build` writes the module, by default next
+                          to <file> with the extension `ptnc`
+  -h, --help              print this message
+
+Pass `-` as <file> to read the source from stdin.
+
+exit status:
+  0  success, or for `run`, `main` didn't return an `Int`
+  1  the source has syntax errors
+  2  the command line is invalid
+  3  the source has other errors, like unknown names or mismatched types
+  4  the program failed while running
+  5  a file couldn't be read or written
+`run` otherwise exits with whatever `Int` `main` returns.
+";
+
+/// How far through the compiler a command goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Command {
+    Lex,
+    Parse,
+    Check,
+    Run,
+    Build,
+    /// Unlike the others, doesn't go through the compiler once on a file.
+    Repl,
+}
+
+/// Something printed along the way, once the stage that produces it is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    /// Every item's type, and the type of each expression in it.
+    TypedAst,
+}
+
+impl Emit {
+    /// The first command that gets far enough to produce this.
+    fn needs(self) -> Command {
+        match self {
+            Emit::Tokens => Command::Lex,
+            Emit::Ast => Command::Parse,
+            Emit::TypedAst => Command::Check,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::TypedAst => "typed-ast",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    /// A path, or `-` for stdin. Empty for `repl`, which doesn't take one.
+    pub input: String,
+    pub emit: Vec<Emit>,
+    pub error_format: ErrorFormat,
+    pub output: Option<String>,
+}
+
+impl Args {
+    /// Parses the arguments after the program name, returning `Ok(None)` if
+    /// help was asked for and a message explaining the problem otherwise.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+        let mut args = args.into_iter();
+        let mut command = None;
+        let mut input = None;
+        let mut emit = Vec::new();
+        let mut error_format = ErrorFormat::default();
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("`{flag}` needs a value"))
+            };
+
+            match flag {
+                "-h" | "--help" => return Ok(None),
+                "--emit" => {
+                    for kind in value()?.split(',') {
+                        let kind = match kind {
+                            "tokens" => Emit::Tokens,
+                            "ast" => Emit::Ast,
+                            "typed-ast" => Emit::TypedAst,
+                            _ => {
+                                return Err(format!(
+                                    "unknown `--emit` kind `{kind}`, expected `tokens`, `ast` or `typed-ast`"
+                                ));
+                            }
+                        };
+                        if !emit.contains(&kind) {
+                            emit.push(kind);
+                        }
+                    }
+                }
+                "--error-format" => {
+                    error_format = match value()?.as_str() {
+                        "human" => ErrorFormat::Human,
+                        "json" => ErrorFormat::Json,
+                        format => {
+                            return Err(format!(
+                                "unknown error format `{format}`, expected `human` or `json`"
+                            ));
+                        }
+                    }
+                }
+                "-o" | "--output" => output = Some(value()?),
+                _ if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown option `{flag}`"));
+                }
+                _ if command.is_none() => {
+                    command = Some(match flag {
+                        "lex" => Command::Lex,
+                        "parse" => Command::Parse,
+                        "check" => Command::Check,
+                        "run" => Command::Run,
+                        "build" => Command::Build,
+                        "repl" => Command::Repl,
+                        _ => return Err(format!("unknown command `{flag}`")),
+                    });
+                }
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+
+        let command = command.ok_or("missing a command")?;
+        if command == Command::Repl {
+            if let Some(input) = input {
+                return Err(format!("`repl` doesn't take a file, but got `{input}`"));
+            }
+            if !emit.is_empty() || output.is_some() {
+                return Err("`repl` only takes `--error-format`".into());
+            }
+            return Ok(Some(Args {
+                command,
+                input: String::new(),
+                emit,
+                error_format,
+                output,
+            }));
+        }
+        let input = input.ok_or("missing the file to compile")?;
+        if let Some(kind) = emit.iter().find(|kind| kind.needs() > command) {
+            return Err(format!(
+                "`--emit={}` isn't available for `{}`, which stops before it's produced",
+                kind.name(),
+                command.name(),
+            ));
+        }
+        // Printing what they produce is the point of `lex` and `parse`.
+        match command {
+            Command::Lex if emit.is_empty() => emit.push(Emit::Tokens),
+            Command::Parse if emit.is_empty() => emit.push(Emit::Ast),
+            _ => {}
+        }
+        if output.is_some() && command != Command::Build {
+            return Err(format!(
+                "`--output` only applies to `build`, not `{}`",
+                command.name()
+            ));
+        }
+        if input == "-" && command == Command::Build && output.is_none() {
+            return Err("`build` needs `--output` when reading from stdin".into());
+        }
+
+        Ok(Some(Args {
+            command,
+            input,
+            emit,
+            error_format,
+            output,
+        }))
+    }
+
+    pub fn emits(&self, kind: Emit) -> bool {
+        self.emit.contains(&kind)
+    }
+}
+
+impl Command {
+    fn name(self) -> &'static str {
+        match self {
+            Command::Lex => "lex",
+            Command::Parse => "parse",
+            Command::Check => "check",
+            Command::Run => "run",
+            Command::Build => "build",
+            Command::Repl => "repl",
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use compiler::{
+    lexer::Lexer,
+    parser::ast::{Ast, Expr, ExprKind, Field, ItemKind, Lit, VariantKind},
+    source_map::SourceMap,
+    span::FileId,
+    typeck::Types,
+};
+
+/// One token per line, after the range of source it covers.
+pub fn tokens(sources: &SourceMap, file: FileId) -> String {
+    let mut out = String::new();
+    for token in Lexer::with_file(sources.get(file).source(), file) {
+        let start = sources.line_col(file, token.span.start);
+        let end = sources.line_col(file, token.span.end);
+        let _ = writeln!(out, "{start}-{end} {:?}", token.node);
+    }
+    out
+}
+
+/// Each item's type, followed by the expressions in it as an indented tree
+/// with the type of each.
+///
+/// ```text
+/// fn twice: fn(fn('a): 'a, 'a): 'a
+///   call: 'a
+///     f: fn('a): 'a
+///     call: 'a
+/// ```
+pub fn typed_ast(ast: &Ast, types: &Types) -> String {
+    let mut out = String::new();
+    for item in ast {
+        let (keyword, name, body) = match &item.kind {
+            ItemKind::Const { ident, value, .. } => ("const", ident, value),
+            ItemKind::Function { name, body, .. } => ("fn", name, body),
+            ItemKind::Struct {
+                name,
+                generic_params,
+                fields,
+            } => {
+                let _ = writeln!(
+                    out,
+                    "struct {name}{} {{ {} }}",
+                    generics(generic_params),
+                    self::fields(fields),
+                );
+                continue;
+            }
+            ItemKind::Enum {
+                name,
+                generic_params,
+                variants,
+            } => {
+                let variants: Vec<_> = variants
+                    .iter()
+                    .map(|variant| match &variant.kind {
+                        VariantKind::Unit(name) => name.clone(),
+                        VariantKind::Tuple(name, types) => {
+                            let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+                            format!("{name}({})", types.join(", "))
+                        }
+                        VariantKind::Struct(name, fields) => {
+                            format!("{name} {{ {} }}", self::fields(fields))
+                        }
+                    })
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    "enum {name}{} {{ {} }}",
+                    generics(generic_params),
+                    variants.join(", "),
+                );
+                continue;
+            }
+            ItemKind::Error => continue,
+        };
+
+        let _ = match types.items.get(name) {
+            Some(scheme) => writeln!(out, "{keyword} {name}: {scheme}"),
+            None => writeln!(out, "{keyword} {name}"),
+        };
+        expr(&mut out, types, body, 1);
+    }
+    out
+}
+
+fn expr(out: &mut String, types: &Types, expr: &Expr, depth: usize) {
+    let description = match &expr.kind {
+        ExprKind::Literal(Lit::Int(int)) => int.to_string(),
+        ExprKind::Literal(Lit::Float(float)) => format!("{float:?}"),
+        ExprKind::Literal(Lit::Str(string)) => format!("{string:?}"),
+        ExprKind::Literal(Lit::Char(char)) => format!("{char:?}"),
+        ExprKind::Literal(Lit::Bool(bool)) => bool.to_string(),
+        ExprKind::Literal(Lit::Array(_)) => "array".into(),
+        ExprKind::Literal(Lit::Tuple(_)) => "tuple".into(),
+        ExprKind::Literal(Lit::Struct {
+            name,
+            variant: None,
+            ..
+        }) => format!("{name} {{ .. }}"),
+        ExprKind::Literal(Lit::Struct {
+            name,
+            variant: Some(variant),
+            ..
+        }) => format!("{name}::{variant} {{ .. }}"),
+        ExprKind::Ident(name) => name.clone(),
+        ExprKind::Path { enum_name, variant } => format!("{enum_name}::{variant}"),
+        ExprKind::FnCall { .. } => "call".into(),
+        ExprKind::BinaryOp { op, .. } => op.to_string(),
+        ExprKind::UnaryOp { op, .. } => op.to_string(),
+        ExprKind::Index { .. } => "index".into(),
+        ExprKind::FieldAccess { field, .. } => format!(".{field}"),
+        ExprKind::If { .. } => "if".into(),
+        ExprKind::Let { binding, .. } => {
+            let mutable = if binding.mutable { "mut " } else { "" };
+            format!("let {mutable}{}", binding.name)
+        }
+        ExprKind::Lambda { params, .. } => {
+            let params: Vec<_> = params.iter().map(|param| param.name.as_str()).collect();
+            format!("|{}|", params.join(", "))
+        }
+        ExprKind::Block { .. } => "block".into(),
+        ExprKind::Match { .. } => "match".into(),
+        ExprKind::Interpolated(_) => "interpolated string".into(),
+        ExprKind::Placeholder => "_".into(),
+        ExprKind::Error => "<error>".into(),
+    };
+
+    // The binding's type says more than the let's, which is always `()`.
+    let span = match &expr.kind {
+        ExprKind::Let { binding, .. } => binding.span,
+        _ => expr.span,
+    };
+    let _ = write!(out, "{}{description}", "  ".repeat(depth));
+    if let Some(ty) = types.node(span) {
+        let _ = write!(out, ": {ty}");
+    }
+    out.push('\n');
+    for child in expr.children() {
+        self::expr(out, types, child, depth + 1);
+    }
+}
+
+fn generics(params: &[String]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", params.join(", "))
+    }
+}
+
+fn fields(fields: &[Field]) -> String {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| format!("{}: {}", field.name, field.ty))
+        .collect();
+    fields.join(", ")
+}
@@ -0,0 +1,235 @@
+mod args;
+mod emit;
+mod repl;
+#[cfg(test)]
+mod test;
+
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Read},
+    path::Path,
+    process, thread,
+};
+
+use args::{Args, Command, Emit, ErrorFormat, USAGE};
+use compiler::{
+    codegen,
+    desugar::desugar,
+    diagnostics::{Diagnostic, Renderer, Severity, message_to_json},
+    interpreter::{Interpreter, Value},
+    lexer::{Lexer, Token},
+    mutability,
+    natives::Natives,
+    parser::{ParseError, Parser, ast::Ast},
+    resolve::resolve,
+    source_map::SourceMap,
+    span::FileId,
+    typeck,
+};
+use repl::Repl;
+
+/// The interpreter recurses on the host stack, so give it plenty of room.
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The ways `patinac` can fail, each with its own exit status.
+#[derive(Debug, Clone, Copy)]
+enum Failure {
+    /// The lexer or parser rejected the source.
+    Syntax = 1,
+    Usage = 2,
+    /// The source parsed, but a later stage rejected it.
+    Semantic = 3,
+    Runtime = 4,
+    Io = 5,
+}
+
+/// Everything needed to report problems once the command line is parsed.
+struct Session {
+    sources: SourceMap,
+    error_format: ErrorFormat,
+    renderer: Renderer,
+}
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            process::exit(Failure::Usage as i32);
+        }
+    };
+
+    let mut session = Session {
+        sources: SourceMap::new(),
+        error_format: args.error_format,
+        renderer: renderer(),
+    };
+    if args.command == Command::Repl {
+        repl(session);
+    }
+    let file = session.load(&args.input);
+    let source = session.sources.get(file).source();
+
+    if args.emits(Emit::Tokens) {
+        print!("{}", emit::tokens(&session.sources, file));
+    }
+    if args.command == Command::Lex {
+        let errors = Lexer::with_file(source, file).filter_map(|token| match token.node {
+            Token::Error(err) => Some(Diagnostic::from(&ParseError::Lex(err, token.span))),
+            _ => None,
+        });
+        session.report(errors.collect(), Failure::Syntax);
+        return;
+    }
+
+    let (mut ast, errors) = Parser::with_file(source, file).file();
+    if args.emits(Emit::Ast) {
+        println!("{ast:#?}");
+    }
+    session.report(
+        errors.iter().map(Diagnostic::from).collect(),
+        Failure::Syntax,
+    );
+    if args.command == Command::Parse {
+        return;
+    }
+
+    // The interpreter only has the builtins, so that's all programs get.
+    let natives = Natives::builtins();
+    session.report(desugar(&mut ast), Failure::Semantic);
+    let (resolution, diagnostics) = resolve(&ast, &natives);
+    session.report(diagnostics, Failure::Semantic);
+    session.report(mutability::check(&ast, &resolution), Failure::Semantic);
+    let (types, diagnostics) = typeck::check(&ast, &natives);
+    if args.emits(Emit::TypedAst) {
+        print!("{}", emit::typed_ast(&ast, &types));
+    }
+    session.report(diagnostics, Failure::Semantic);
+
+    match args.command {
+        Command::Lex | Command::Parse | Command::Check | Command::Repl => {}
+        Command::Build => session.build(&ast, file, &args),
+        Command::Run => session.run(ast),
+    }
+}
+
+impl Session {
+    /// Adds the source to compile, reading it from stdin if `input` is `-`.
+    fn load(&mut self, input: &str) -> FileId {
+        let loaded = if input == "-" {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map(|_| self.sources.add("<stdin>", source))
+        } else {
+            self.sources.load(input)
+        };
+        loaded
+            .unwrap_or_else(|err| self.fail(Failure::Io, &format!("cannot read `{input}`: {err}")))
+    }
+
+    fn build(&self, ast: &Ast, file: FileId, args: &Args) {
+        let (module, diagnostics) = codegen::compile(ast, self.sources.get(file))
+            .unwrap_or_else(|message| self.fail(Failure::Semantic, &message));
+        self.report(diagnostics, Failure::Semantic);
+
+        let output = args.output.clone().unwrap_or_else(|| {
+            Path::new(&args.input)
+                .with_extension("ptnc")
+                .display()
+                .to_string()
+        });
+        if let Err(err) = fs::write(&output, module.encode()) {
+            self.fail(Failure::Io, &format!("cannot write `{output}`: {err}"));
+        }
+    }
+
+    /// Runs `main`, exiting with its result if that's an `Int`.
+    fn run(&self, ast: Ast) -> ! {
+        let result = thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn(move || {
+                Interpreter::run(&ast).map(|value| match value {
+                    Value::Int(code) => code as i32,
+                    _ => 0,
+                })
+            })
+            .expect("failed to spawn the interpreter thread")
+            .join()
+            .expect("interpreter thread panicked");
+        match result {
+            Ok(code) => process::exit(code),
+            Err(err) => {
+                let Some(diagnostic) = err.diagnostic() else {
+                    self.fail(Failure::Runtime, &err.message)
+                };
+                self.report(vec![diagnostic], Failure::Runtime);
+                unreachable!("runtime errors are always reported as errors")
+            }
+        }
+    }
+
+    /// Prints `diagnostics`, exiting with `failure`'s status if any of them
+    /// are errors.
+    fn report(&self, diagnostics: Vec<Diagnostic>, failure: Failure) {
+        for diagnostic in &diagnostics {
+            eprintln!("{}", self.format(diagnostic));
+        }
+
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            process::exit(failure as i32);
+        }
+    }
+
+    fn format(&self, diagnostic: &Diagnostic) -> String {
+        match self.error_format {
+            ErrorFormat::Human => self.renderer.render(&self.sources, diagnostic),
+            ErrorFormat::Json => diagnostic.to_json(&self.sources),
+        }
+    }
+
+    /// Prints an error that isn't about the source, and exits.
+    fn fail(&self, failure: Failure, message: &str) -> ! {
+        match self.error_format {
+            ErrorFormat::Human => eprintln!("error: {message}"),
+            ErrorFormat::Json => eprintln!("{}", message_to_json(Severity::Error, message)),
+        }
+        process::exit(failure as i32);
+    }
+}
+
+/// Runs the REPL on stdin until it runs out, prompting only if someone's
+/// typing.
+fn repl(session: Session) -> ! {
+    let result = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let prompt = io::stdin().is_terminal();
+            if prompt {
+                println!("patinac {}, `:help` for help", env!("CARGO_PKG_VERSION"));
+            }
+            Repl::new(session, io::stdout()).run(io::stdin().lock(), prompt)
+        })
+        .expect("failed to spawn the REPL thread")
+        .join()
+        .expect("REPL thread panicked");
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        process::exit(Failure::Io as i32);
+    }
+    process::exit(0);
+}
+
+/// Colors diagnostics only when they're going straight to a terminal, and
+/// honours the `NO_COLOR` convention.
+fn renderer() -> Renderer {
+    if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+        Renderer::colored()
+    } else {
+        Renderer::plain()
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use compiler::{
+    desugar::desugar,
+    diagnostics::Diagnostic,
+    interpreter::{Interpreter, Value},
+    lexer::{Lexer, Token},
+    mutability,
+    natives::Natives,
+    parser::{
+        Parser,
+        ast::{Ast, Expr, ExprKind, Item, ItemKind},
+    },
+    resolve::resolve,
+    span::FileId,
+    typeck::{self, Scheme, Ty, Types},
+};
+
+use crate::{Session, emit};
+
+pub const HELP: &str = "\
+Enter an item to define it, or an expression to see its value and type.
+Input carries on over more lines while brackets are left open.
+
+commands:
+  :type <expr>     print the type of <expr> without evaluating it
+  :ast <input>     print the syntax tree of an item or expression
+  :tokens <input>  print the tokens in <input>
+  :help            print this message
+  :quit            leave, as does the end of the input
+";
+
+/// What an expression is wrapped in to check and evaluate it, which no item
+/// can be called since it isn't an identifier.
+const WRAPPER: &str = "<repl>";
+
+/// An interactive session, which keeps each item that's entered for later
+/// inputs to use. Everything, errors included, goes to `out`.
+pub struct Repl<W> {
+    session: Session,
+    /// The items entered so far, each from its own file in the session.
+    items: Vec<Item>,
+    /// The values of the constants evaluated so far, so that each is only
+    /// evaluated once.
+    consts: HashMap<String, Value>,
+    out: W,
+}
+
+enum Input {
+    Item(Item),
+    Expr(Expr),
+}
+
+impl<W: Write> Repl<W> {
+    pub fn new(session: Session, out: W) -> Self {
+        Repl {
+            session,
+            items: Vec::new(),
+            consts: HashMap::new(),
+            out,
+        }
+    }
+
+    /// Handles inputs until `input` runs out or one is `:quit`, prompting
+    /// for each if `prompt` is set.
+    pub fn run(&mut self, input: impl BufRead, prompt: bool) -> io::Result<()> {
+        let mut lines = input.lines();
+        while let Some(text) = self.read(&mut lines, prompt)? {
+            if !self.handle(&text)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads lines until every bracket opened in them is closed.
+    fn read(
+        &mut self,
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+        prompt: bool,
+    ) -> io::Result<Option<String>> {
+        let mut text = String::new();
+        loop {
+            if prompt {
+                write!(self.out, "{}", if text.is_empty() { "> " } else { ". " })?;
+                self.out.flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else {
+                // Whatever was left unfinished still gets its errors.
+                return Ok((!text.trim().is_empty()).then_some(text));
+            };
+            text.push_str(&line);
+            text.push('\n');
+            if open_brackets(&text) <= 0 {
+                return Ok(Some(text));
+            }
+        }
+    }
+
+    /// Handles one input, returning whether to carry on.
+    fn handle(&mut self, text: &str) -> io::Result<bool> {
+        let Some(command) = text.trim_start().strip_prefix(':') else {
+            if !text.trim().is_empty() {
+                self.eval(text)?;
+            }
+            return Ok(true);
+        };
+
+        let (name, rest) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command.trim_end(), ""));
+        match name {
+            "q" | "quit" => return Ok(false),
+            "h" | "help" => write!(self.out, "{HELP}")?,
+            "t" | "type" => self.type_of(rest)?,
+            "ast" => {
+                let file = self.add(rest);
+                match self.parse(file)? {
+                    Some(Input::Item(item)) => writeln!(self.out, "{item:#?}")?,
+                    Some(Input::Expr(expr)) => writeln!(self.out, "{expr:#?}")?,
+                    None => {}
+                }
+            }
+            "tokens" => {
+                let file = self.add(rest);
+                write!(self.out, "{}", emit::tokens(&self.session.sources, file))?;
+            }
+            _ => writeln!(self.out, "error: unknown command `:{name}`, try `:help`")?,
+        }
+        Ok(true)
+    }
+
+    /// Defines an item, replacing any of the same name, or evaluates an
+    /// expression.
+    fn eval(&mut self, text: &str) -> io::Result<()> {
+        let file = self.add(text);
+        match self.parse(file)? {
+            Some(Input::Item(item)) => {
+                let name = name(&item).to_string();
+                let mut items: Vec<_> = self
+                    .items
+                    .iter()
+                    .filter(|existing| self::name(existing) != name)
+                    .cloned()
+                    .collect();
+                let replaces = items.len() < self.items.len();
+                items.push(item);
+                let Some((_, types)) = self.check(items.clone(), file)? else {
+                    return Ok(());
+                };
+                self.items = items;
+                // Any constant could depend on the item that was replaced.
+                if replaces {
+                    self.consts.clear();
+                }
+
+                let item = self.items.last().expect("the item was just added");
+                match &item.kind {
+                    ItemKind::Function { .. } => {
+                        writeln!(self.out, "fn {name}: {}", types.items[&name])?
+                    }
+                    ItemKind::Const { .. } => {
+                        writeln!(self.out, "const {name}: {}", types.items[&name])?
+                    }
+                    ItemKind::Struct { .. } => writeln!(self.out, "struct {name}")?,
+                    ItemKind::Enum { .. } => writeln!(self.out, "enum {name}")?,
+                    ItemKind::Error => {}
+                }
+            }
+            Some(Input::Expr(expr)) => {
+                if let ExprKind::Let { binding, .. } = &expr.kind {
+                    let diagnostic = Diagnostic::error(
+                        format!("`{}` would be gone by the next input", binding.name),
+                        expr.span,
+                    )
+                    .with_label("`let` only lasts until the end of its input")
+                    .with_note(format!(
+                        "to keep the value, define it with `const {}: <type> = ...`",
+                        binding.name
+                    ));
+                    self.report(vec![diagnostic], file)?;
+                    return Ok(());
+                }
+                let Some((ast, types)) = self.check(self.with_wrapper(expr), file)? else {
+                    return Ok(());
+                };
+                let result = Interpreter::new(&ast, &mut self.out).and_then(|mut interpreter| {
+                    for (name, value) in &self.consts {
+                        interpreter.set_const(name, value.clone());
+                    }
+                    let result = interpreter.call_function(WRAPPER, Vec::new());
+                    self.consts.extend(
+                        interpreter
+                            .evaluated_consts()
+                            .map(|(name, value)| (name.to_string(), value.clone())),
+                    );
+                    result
+                });
+                match result {
+                    Ok(value) => {
+                        // Quoted, so that `"1"` isn't mistaken for `1`.
+                        let value = match value {
+                            Value::Str(string) => format!("{string:?}"),
+                            Value::Char(char) => format!("{char:?}"),
+                            value => value.to_string(),
+                        };
+                        writeln!(self.out, "{value}: {}", result_type(&types))?;
+                    }
+                    Err(err) => match err.diagnostic() {
+                        Some(diagnostic) => {
+                            self.report(vec![diagnostic], file)?;
+                        }
+                        None => writeln!(self.out, "error: {}", err.message)?,
+                    },
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn type_of(&mut self, text: &str) -> io::Result<()> {
+        let file = self.add(text);
+        match self.parse(file)? {
+            Some(Input::Expr(expr)) => {
+                if let Some((_, types)) = self.check(self.with_wrapper(expr), file)? {
+                    writeln!(self.out, "{}", result_type(&types))?;
+                }
+            }
+            Some(Input::Item(_)) => {
+                writeln!(self.out, "error: `:type` takes an expression, not an item")?
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, text: &str) -> FileId {
+        self.session.sources.add("<repl>", text)
+    }
+
+    /// Parses the file as an item if it starts with an item's keyword, and
+    /// as an expression otherwise, reporting any errors.
+    fn parse(&mut self, file: FileId) -> io::Result<Option<Input>> {
+        // Owned, so that errors can be reported while the parser borrows it.
+        let text = self.session.sources.get(file).source().to_string();
+        let is_item = matches!(
+            Lexer::with_file(&text, file)
+                .map(|token| token.node)
+                .find(|token| !matches!(token, Token::DocComment(_))),
+            Some(Token::Fn | Token::Const | Token::Struct | Token::Enum)
+        );
+
+        let mut parser = Parser::with_file(&text, file);
+        let input = if is_item {
+            parser.item().map(Input::Item)
+        } else {
+            parser.expression().map(Input::Expr)
+        };
+        let input = input.and_then(|input| parser.end().map(|()| input));
+        let mut errors = parser.take_errors();
+        let input = input.map_err(|err| errors.push(err)).ok();
+
+        if errors.is_empty() {
+            Ok(input)
+        } else {
+            self.report(errors.iter().map(Diagnostic::from).collect(), file)?;
+            Ok(None)
+        }
+    }
+
+    /// The items so far, followed by a function that evaluates `expr`.
+    fn with_wrapper(&self, expr: Expr) -> Ast {
+        let mut items = self.items.clone();
+        items.push(Item {
+            span: expr.span,
+            docs: Vec::new(),
+            kind: ItemKind::Function {
+                name: WRAPPER.into(),
+                params: Vec::new(),
+                return_type: None,
+                body: expr,
+            },
+        });
+        items
+    }
+
+    /// Runs every check the compiler would on `ast`, stopping at the first
+    /// stage with errors.
+    fn check(&mut self, mut ast: Ast, file: FileId) -> io::Result<Option<(Ast, Types)>> {
+        let natives = Natives::builtins();
+        if self.report(desugar(&mut ast), file)? {
+            return Ok(None);
+        }
+        let (resolution, diagnostics) = resolve(&ast, &natives);
+        if self.report(diagnostics, file)?
+            || self.report(mutability::check(&ast, &resolution), file)?
+        {
+            return Ok(None);
+        }
+        let (types, diagnostics) = typeck::check(&ast, &natives);
+        if self.report(diagnostics, file)? {
+            return Ok(None);
+        }
+        Ok(Some((ast, types)))
+    }
+
+    /// Prints the errors, and any warnings about the input in `file` rather
+    /// than earlier ones, returning whether there were errors.
+    fn report(&mut self, diagnostics: Vec<Diagnostic>, file: FileId) -> io::Result<bool> {
+        for diagnostic in &diagnostics {
+            if diagnostic.is_error() || diagnostic.primary.span.file == file {
+                writeln!(self.out, "{}", self.session.format(diagnostic))?;
+            }
+        }
+        Ok(diagnostics.iter().any(Diagnostic::is_error))
+    }
+}
+
+/// How many more brackets the input opens than it closes.
+fn open_brackets(text: &str) -> isize {
+    Lexer::new(text)
+        .map(|token| match token.node {
+            Token::LParen | Token::LBrace | Token::LBracket => 1,
+            Token::RParen | Token::RBrace | Token::RBracket => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn name(item: &Item) -> &str {
+    match &item.kind {
+        ItemKind::Function { name, .. }
+        | ItemKind::Const { ident: name, .. }
+        | ItemKind::Struct { name, .. }
+        | ItemKind::Enum { name, .. } => name,
+        ItemKind::Error => "",
+    }
+}
+
+/// The type of the wrapped expression, generalized like an item's.
+fn result_type(types: &Types) -> Scheme {
+    let Scheme { vars, ty } = types.items[WRAPPER].clone();
+    let Ty::Fn(_, result) = ty else {
+        unreachable!("the wrapper is a function")
+    };
+    Scheme { vars, ty: *result }
+}
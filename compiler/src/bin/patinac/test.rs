@@ -0,0 +1,262 @@
+use compiler::{
+    desugar::desugar, diagnostics::Renderer, natives::Natives, parser::Parser,
+    source_map::SourceMap, typeck,
+};
+
+use crate::{
+    Session,
+    args::{Args, Command, Emit, ErrorFormat},
+    emit,
+    repl::Repl,
+};
+
+fn parse(args: &str) -> Result<Option<Args>, String> {
+    Args::parse(args.split_whitespace().map(String::from))
+}
+
+#[test]
+fn parse_arguments() {
+    assert_eq!(
+        parse("check --emit=typed-ast,tokens --error-format json main.ptn"),
+        Ok(Some(Args {
+            command: Command::Check,
+            input: "main.ptn".into(),
+            emit: vec![Emit::TypedAst, Emit::Tokens],
+            error_format: ErrorFormat::Json,
+            output: None,
+        }))
+    );
+    assert_eq!(
+        parse("build - -o out.ptnc"),
+        Ok(Some(Args {
+            command: Command::Build,
+            input: "-".into(),
+            emit: Vec::new(),
+            error_format: ErrorFormat::Human,
+            output: Some("out.ptnc".into()),
+        }))
+    );
+    assert_eq!(parse("run --help"), Ok(None));
+    assert_eq!(
+        parse("repl --error-format=json"),
+        Ok(Some(Args {
+            command: Command::Repl,
+            input: String::new(),
+            emit: Vec::new(),
+            error_format: ErrorFormat::Json,
+            output: None,
+        }))
+    );
+
+    // `lex` and `parse` print what they produce unless told otherwise.
+    assert_eq!(parse("lex a.ptn").unwrap().unwrap().emit, [Emit::Tokens]);
+    assert_eq!(parse("parse a.ptn").unwrap().unwrap().emit, [Emit::Ast]);
+    assert_eq!(
+        parse("parse --emit tokens a.ptn").unwrap().unwrap().emit,
+        [Emit::Tokens]
+    );
+}
+
+#[test]
+fn reject_invalid_arguments() {
+    let error = |args| parse(args).unwrap_err();
+    assert_eq!(error("a.ptn"), "unknown command `a.ptn`");
+    assert_eq!(error("run"), "missing the file to compile");
+    assert_eq!(error(""), "missing a command");
+    assert_eq!(error("run a.ptn b.ptn"), "unexpected argument `b.ptn`");
+    assert_eq!(error("run --fast a.ptn"), "unknown option `--fast`");
+    assert_eq!(error("run a.ptn --emit"), "`--emit` needs a value");
+    assert_eq!(
+        error("check --emit=ir a.ptn"),
+        "unknown `--emit` kind `ir`, expected `tokens`, `ast` or `typed-ast`"
+    );
+    assert_eq!(
+        error("check --error-format=xml a.ptn"),
+        "unknown error format `xml`, expected `human` or `json`"
+    );
+    assert_eq!(
+        error("parse --emit=typed-ast a.ptn"),
+        "`--emit=typed-ast` isn't available for `parse`, which stops before it's produced"
+    );
+    assert_eq!(
+        error("run -o out a.ptn"),
+        "`--output` only applies to `build`, not `run`"
+    );
+    assert_eq!(
+        error("build -"),
+        "`build` needs `--output` when reading from stdin"
+    );
+    assert_eq!(
+        error("repl a.ptn"),
+        "`repl` doesn't take a file, but got `a.ptn`"
+    );
+    assert_eq!(
+        error("repl --emit=ast"),
+        "`repl` only takes `--error-format`"
+    );
+}
+
+#[test]
+fn emit_typed_ast() {
+    let (mut ast, errors) = Parser::new(
+        "
+        struct Pair<T> { a: T, b: T }
+        enum Op { Neg, Add(Int, Int), Mul { by: Int } }
+        fn first(pair) -> pair.a
+        const two: Int = { let mut n = first(Pair { a: 2, b: 3 }); n }
+    ",
+    )
+    .file();
+    assert!(errors.is_empty());
+    desugar(&mut ast);
+    let (types, diagnostics) = typeck::check(&ast, &Natives::builtins());
+    assert!(diagnostics.is_empty());
+
+    assert_eq!(
+        emit::typed_ast(&ast, &types),
+        "\
+struct Pair<T> { a: T, b: T }
+enum Op { Neg, Add(Int, Int), Mul { by: Int } }
+fn first: fn(Pair<'a>): 'a
+  .a: 'a
+    pair: Pair<'a>
+const two: Int
+  block: Int
+    let mut n: Int
+      call: Int
+        first: fn(Pair<Int>): Int
+        Pair { .. }: Pair<Int>
+          2: Int
+          3: Int
+    n: Int
+"
+    );
+}
+
+/// Feeds `input` to a REPL, returning everything it printed.
+fn repl(input: &str) -> String {
+    let session = Session {
+        sources: SourceMap::new(),
+        error_format: ErrorFormat::Human,
+        renderer: Renderer::plain(),
+    };
+    let mut out = Vec::new();
+    Repl::new(session, &mut out)
+        .run(input.as_bytes(), false)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn evaluate_in_the_repl() {
+    assert_eq!(
+        repl(
+            r#"
+fn add(a, b) -> a + b
+add(1, 2)
+add("con", "cat")
+struct Point { x: Int, y: Int }
+Point {
+    x: 1,
+    y: add(1, 1),
+}.y
+{ println("hi"); [Point { x: 3, y: 4 }.y] }
+fn add(a) -> a * 2
+add(4)
+:type |x| -> (x, add(x))
+"#
+        ),
+        r#"fn add: fn('a, 'a): 'a where 'a: Int | Float | Str
+3: Int
+"concat": Str
+struct Point
+2: Int
+hi
+[4]: [Int]
+fn add: fn(Int): Int
+8: Int
+fn(Int): (Int, Int)
+"#
+    );
+}
+
+#[test]
+fn report_errors_in_the_repl() {
+    assert_eq!(
+        repl(
+            r#"fn half(n) -> n / 2
+fn half(n) -> n / "2"
+half(1, 2)
+1 / 0
+:ast 1 )
+:tokens 'a' +
+:quit
+half(4)
+"#
+        ),
+        r#"fn half: fn(Int): Int
+error: mismatched types
+ --> <repl>:1:15
+  |
+1 | fn half(n) -> n / "2"
+  |               ^ expected `Int` or `Float`, found `Str`
+
+error: this function takes 1 argument, but 2 were supplied
+ --> <repl>:1:1
+  |
+1 | half(1, 2)
+  | ^^^^^^^^^^ wrong number of arguments
+
+error: division by zero
+ --> <repl>:1:1
+  |
+1 | 1 / 0
+  | ^^^^^
+
+error: unexpected token `)` at end of input
+ --> <repl>:1:3
+  |
+1 | 1 )
+  |   ^ unexpected token
+
+1:1-1:4 CharLit('a')
+1:5-1:6 Plus
+2:1-2:1 Eof
+"#
+    );
+}
+
+#[test]
+fn keep_values_between_inputs() {
+    assert_eq!(
+        repl(
+            r#"const k: Int = { println("init"); 3 }
+k + 1
+k + 2
+fn double() -> k * 2
+double()
+const k: Int = { println("again"); 4 }
+double()
+let y = 2
+"#
+        ),
+        r#"const k: Int
+init
+4: Int
+5: Int
+fn double: fn(): Int
+6: Int
+const k: Int
+again
+8: Int
+error: `y` would be gone by the next input
+ --> <repl>:1:1
+  |
+1 | let y = 2
+  | ^^^^^^^^^ `let` only lasts until the end of its input
+  |
+  = note: to keep the value, define it with `const y: <type> = ...`
+
+"#
+    );
+}
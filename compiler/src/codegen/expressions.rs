@@ -0,0 +1,451 @@
+use runtime::bytecode::{Constant, Fields, Function, Op};
+
+use super::Codegen;
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{Bop, Expr, ExprKind, FieldInit, Lit, MatchArm, StringPart, Unop},
+    span::Span,
+};
+
+/// The steps from a variable down to the part of it being assigned to.
+enum Projection<'a> {
+    Index(&'a Expr),
+    Field(&'a str),
+}
+
+impl<'a> Codegen<'a> {
+    /// Compiles `expr` to code that leaves its value on the stack.
+    pub(super) fn expr(&mut self, expr: &'a Expr) {
+        let line = self.line;
+        self.line = self.line(expr.span);
+
+        match &expr.kind {
+            ExprKind::Literal(lit) => self.literal(lit),
+            ExprKind::Ident(name) => self.load(name),
+            ExprKind::Path { enum_name, variant } => {
+                let shape = self.shapes[&format!("{enum_name}::{variant}")];
+                match self.module.shapes[shape as usize].fields {
+                    Fields::Unit => {
+                        self.emit(Op::Record(shape));
+                    }
+                    Fields::Tuple(len) => {
+                        let constructor = self.constructor(shape, len);
+                        self.emit(Op::Closure(constructor));
+                    }
+                    Fields::Named(_) => unreachable!("rejected by type checking"),
+                }
+            }
+            ExprKind::FnCall { fun, args } => self.call(fun, args, expr.span),
+            ExprKind::BinaryOp { op, lhs, rhs } => self.binary_op(*op, lhs, rhs),
+            ExprKind::UnaryOp { op, expr } => {
+                self.expr(expr);
+                self.emit(match op {
+                    Unop::Neg => Op::Neg,
+                    Unop::Not => Op::Not,
+                });
+            }
+            ExprKind::Index { arr, index } => {
+                self.expr(arr);
+                self.expr(index);
+                self.emit(Op::Index);
+            }
+            ExprKind::FieldAccess { base, field } => {
+                self.expr(base);
+                let field = self.constant(Constant::Str(field.clone()));
+                self.emit(Op::GetField(field));
+            }
+            ExprKind::If { cond, th, el } => {
+                let height = self.height();
+                self.expr(cond);
+                let else_jump = self.jump(Op::JumpIfFalse);
+                self.expr(th);
+                let end_jump = self.jump(Op::Jump);
+                self.patch(else_jump);
+                self.set_height(height);
+                match el {
+                    Some(el) => self.expr(el),
+                    None => {
+                        self.emit(Op::Unit);
+                    }
+                }
+                self.patch(end_jump);
+            }
+            // Outside of a block, the new variable immediately goes out of scope.
+            ExprKind::Let { value, .. } => {
+                self.expr(value);
+                self.emit(Op::Pop);
+                self.emit(Op::Unit);
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                let function = self.function("<closure>", params, body, expr.span);
+                self.emit(Op::Closure(function));
+            }
+            ExprKind::Block { exprs, trailing } => self.block(exprs, *trailing),
+            ExprKind::Match { scrutinee, arms } => self.match_(scrutinee, arms),
+            ExprKind::Interpolated(parts) => self.interpolated(parts),
+            ExprKind::Placeholder | ExprKind::Error => {
+                unreachable!("removed by desugaring or rejected by the parser")
+            }
+        }
+
+        self.line = line;
+    }
+
+    pub(super) fn literal(&mut self, lit: &'a Lit) {
+        let constant = match lit {
+            Lit::Int(int) => Constant::Int(*int),
+            Lit::Float(float) => Constant::Float(*float),
+            Lit::Str(string) => Constant::Str(string.clone()),
+            Lit::Char(char) => Constant::Char(*char),
+            Lit::Bool(true) => {
+                self.emit(Op::True);
+                return;
+            }
+            Lit::Bool(false) => {
+                self.emit(Op::False);
+                return;
+            }
+            Lit::Array(exprs) | Lit::Tuple(exprs) => {
+                for expr in exprs {
+                    self.expr(expr);
+                }
+                let len = exprs.len() as u32;
+                self.emit(match lit {
+                    Lit::Array(_) => Op::Array(len),
+                    _ => Op::Tuple(len),
+                });
+                return;
+            }
+            Lit::Struct {
+                name,
+                variant,
+                fields,
+            } => {
+                let shape = match variant {
+                    Some(variant) => format!("{name}::{variant}"),
+                    None => name.clone(),
+                };
+                self.struct_literal(self.shapes[&shape], fields);
+                return;
+            }
+        };
+        let constant = self.constant(constant);
+        self.emit(Op::Const(constant));
+    }
+
+    /// Builds a struct or struct variant. The fields are evaluated in the
+    /// order they're written, then put in the order they're declared.
+    fn struct_literal(&mut self, shape: u32, inits: &'a [FieldInit]) {
+        let Fields::Named(names) = &self.module.shapes[shape as usize].fields else {
+            unreachable!("rejected by type checking")
+        };
+        let order: Vec<_> = names
+            .iter()
+            .map(|name| {
+                inits
+                    .iter()
+                    .position(|init| init.name == *name)
+                    .expect("missing fields are rejected by type checking")
+            })
+            .collect();
+        // Every value is consumed by the record, so none is left on the stack.
+        assert_eq!(
+            inits.len(),
+            names.len(),
+            "unknown and duplicate fields are rejected by type checking"
+        );
+
+        let start = self.height();
+        for init in inits {
+            self.expr(&init.value);
+        }
+        if order.iter().enumerate().any(|(i, &init)| i != init) {
+            for init in order {
+                self.emit(Op::GetLocal((start + init) as u16));
+            }
+            self.emit(Op::Record(shape));
+            self.emit(Op::EndScope(inits.len() as u16));
+        } else {
+            self.emit(Op::Record(shape));
+        }
+    }
+
+    /// The function that builds the tuple variant `shape` from its `len`
+    /// fields, compiling it the first time it's needed.
+    fn constructor(&mut self, shape: u32, len: u32) -> u32 {
+        if let Some(&constructor) = self.constructors.get(&shape) {
+            return constructor;
+        }
+
+        let name = self.module.shapes[shape as usize].name.clone();
+        let mut code: Vec<_> = (0..len as u16).map(Op::GetLocal).collect();
+        code.extend([Op::Record(shape), Op::Return]);
+        self.module.functions.push(Function {
+            name,
+            arity: len as u8,
+            captures: Vec::new(),
+            code,
+            // It doesn't come from any line in particular.
+            lines: Vec::new(),
+        });
+
+        let constructor = self.module.functions.len() as u32 - 1;
+        self.constructors.insert(shape, constructor);
+        constructor
+    }
+
+    fn call(&mut self, fun: &'a Expr, args: &'a [Expr], span: Span) {
+        let Ok(argc) = u8::try_from(args.len()) else {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "this call passes {} arguments, but at most 255 are supported",
+                        args.len()
+                    ),
+                    span,
+                )
+                .with_label("too many arguments"),
+            );
+            self.emit(Op::Unit);
+            return;
+        };
+
+        // Tuple variants are built directly, rather than through a call.
+        if let ExprKind::Path { enum_name, variant } = &fun.kind {
+            let shape = self.shapes[&format!("{enum_name}::{variant}")];
+            if self.module.shapes[shape as usize].fields == Fields::Tuple(argc.into()) {
+                for arg in args {
+                    self.expr(arg);
+                }
+                self.emit(Op::Record(shape));
+                return;
+            }
+        }
+
+        self.expr(fun);
+        for arg in args {
+            self.expr(arg);
+        }
+        self.emit(Op::Call(argc));
+    }
+
+    fn binary_op(&mut self, op: Bop, lhs: &'a Expr, rhs: &'a Expr) {
+        let op = match op {
+            Bop::Assign => return self.assign(lhs, rhs),
+            Bop::And | Bop::Or => return self.logical(op, lhs, rhs),
+            Bop::Add => Op::Add,
+            Bop::Sub => Op::Sub,
+            Bop::Mul => Op::Mul,
+            Bop::Div => Op::Div,
+            Bop::Exp => Op::Exp,
+            Bop::Xor => Op::BitXor,
+            Bop::BOr => Op::BitOr,
+            Bop::BAnd => Op::BitAnd,
+            Bop::Gt => Op::Gt,
+            Bop::Lt => Op::Lt,
+            Bop::Eqq => Op::Eq,
+            Bop::Neq => Op::Neq,
+            Bop::Geq => Op::Geq,
+            Bop::Leq => Op::Leq,
+            Bop::Pipeline | Bop::ComposeRight | Bop::ComposeLeft => {
+                unreachable!("removed by desugaring")
+            }
+        };
+        self.expr(lhs);
+        self.expr(rhs);
+        self.emit(op);
+    }
+
+    /// `&&` and `||`, which only evaluate their right side if the left
+    /// doesn't decide the result.
+    fn logical(&mut self, op: Bop, lhs: &'a Expr, rhs: &'a Expr) {
+        let height = self.height();
+        self.expr(lhs);
+        let short_circuit = self.jump(Op::JumpIfFalse);
+        match op {
+            Bop::And => self.expr(rhs),
+            _ => {
+                self.emit(Op::True);
+            }
+        }
+        let end = self.jump(Op::Jump);
+        self.patch(short_circuit);
+        self.set_height(height);
+        match op {
+            Bop::And => {
+                self.emit(Op::False);
+            }
+            _ => self.expr(rhs),
+        }
+        self.patch(end);
+    }
+
+    /// Stores the value of `value` in the variable, element or field that
+    /// `place` refers to.
+    ///
+    /// Aggregates are values, so changing part of one rebuilds every
+    /// aggregate on the way down from the variable, then stores the new root
+    /// back in the variable.
+    fn assign(&mut self, place: &'a Expr, value: &'a Expr) {
+        let mut projections = Vec::new();
+        let mut root = place;
+        loop {
+            match &root.kind {
+                ExprKind::Index { arr, index } => {
+                    projections.push(Projection::Index(index));
+                    root = arr;
+                }
+                ExprKind::FieldAccess { base, field } => {
+                    projections.push(Projection::Field(field));
+                    root = base;
+                }
+                _ => break,
+            }
+        }
+        projections.reverse();
+
+        let ExprKind::Ident(name) = &root.kind else {
+            unreachable!("rejected by the mutability check")
+        };
+        let variable = self
+            .variable(name)
+            .expect("only variables pass the mutability check");
+
+        self.expr(value);
+        let value = self.height() - 1;
+
+        // Push each aggregate from the variable down to the one being
+        // changed, each after the index it's at in the one before.
+        if !projections.is_empty() {
+            self.get(variable);
+        }
+        for (i, projection) in projections.iter().enumerate() {
+            let container = self.height() - 1;
+            if let Projection::Index(index) = projection {
+                self.expr(index);
+            }
+            if i + 1 == projections.len() {
+                break;
+            }
+            self.emit(Op::GetLocal(container as u16));
+            match projection {
+                Projection::Index(_) => {
+                    self.emit(Op::GetLocal(container as u16 + 1));
+                    self.emit(Op::Index);
+                }
+                Projection::Field(field) => {
+                    let field = self.constant(Constant::Str(field.to_string()));
+                    self.emit(Op::GetField(field));
+                }
+            }
+        }
+
+        // Then put them back together around the new value.
+        self.emit(Op::GetLocal(value as u16));
+        for projection in projections.iter().rev() {
+            match projection {
+                Projection::Index(_) => {
+                    self.emit(Op::SetIndex);
+                }
+                Projection::Field(field) => {
+                    let field = self.constant(Constant::Str(field.to_string()));
+                    self.emit(Op::SetField(field));
+                }
+            }
+        }
+        self.store(variable);
+
+        // The value was only kept so it could be copied in.
+        self.emit(Op::Pop);
+        self.emit(Op::Unit);
+    }
+
+    /// Concatenates the parts of an interpolated string, converting each
+    /// value with the `to_string` native. It's loaded directly, so that an
+    /// item of the same name doesn't take its place.
+    fn interpolated(&mut self, parts: &'a [StringPart]) {
+        for (i, part) in parts.iter().enumerate() {
+            match part {
+                StringPart::Text(text) => {
+                    let text = self.constant(Constant::Str(text.clone()));
+                    self.emit(Op::Const(text));
+                }
+                StringPart::Expr(expr) => {
+                    let to_string = self.native("to_string");
+                    self.emit(Op::Native(to_string));
+                    self.expr(expr);
+                    self.emit(Op::Call(1));
+                }
+            }
+            if i > 0 {
+                self.emit(Op::Add);
+            }
+        }
+    }
+
+    fn block(&mut self, exprs: &'a [Expr], trailing: bool) {
+        let height = self.height();
+        let locals = self.scope().locals.len();
+
+        for (i, expr) in exprs.iter().enumerate() {
+            let last = i + 1 == exprs.len();
+            match &expr.kind {
+                ExprKind::Let { binding, value } => {
+                    self.expr(value);
+                    self.declare(&binding.name);
+                    if last {
+                        self.emit(Op::Unit);
+                    }
+                }
+                _ => {
+                    self.expr(expr);
+                    if !last || !trailing {
+                        self.emit(Op::Pop);
+                    }
+                    if last && !trailing {
+                        self.emit(Op::Unit);
+                    }
+                }
+            }
+        }
+        if exprs.is_empty() {
+            self.emit(Op::Unit);
+        }
+
+        self.end_scope(height, locals);
+    }
+
+    /// Tries each arm in turn, with the scrutinee kept in a slot of its own
+    /// for the patterns to look into.
+    fn match_(&mut self, scrutinee: &'a Expr, arms: &'a [MatchArm]) {
+        self.expr(scrutinee);
+        let height = self.height();
+        let slot = (height - 1) as u16;
+
+        let mut ends = Vec::new();
+        for arm in arms {
+            let locals = self.scope().locals.len();
+            let mut fails = Vec::new();
+            self.pattern_tests(&arm.pattern, slot, &mut Vec::new(), &mut fails);
+            self.pattern_bindings(&arm.pattern, slot, &mut Vec::new());
+            self.expr(&arm.body);
+            self.end_scope(height, locals);
+            ends.push(self.jump(Op::Jump));
+
+            for fail in fails {
+                self.patch(fail);
+            }
+            self.set_height(height);
+        }
+
+        self.line = self.line(scrutinee.span);
+        self.emit(Op::GetLocal(slot));
+        self.emit(Op::NoMatch);
+
+        self.set_height(height + 1);
+        for end in ends {
+            self.patch(end);
+        }
+        self.emit(Op::EndScope(1));
+    }
+}
@@ -0,0 +1,564 @@
+//! Lowers a checked AST to a bytecode module for the runtime.
+//!
+//! Every function and const item becomes a global. The module's init
+//! function fills those in, evaluating consts in dependency order, and its
+//! entry point calls that and then `main`.
+
+mod expressions;
+mod patterns;
+#[cfg(test)]
+mod test;
+
+use std::collections::HashMap;
+
+use runtime::bytecode::{Capture, Constant, Fields, Function, Module, Op, Shape};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{Ast, Binding, Expr, Item, ItemKind, VariantKind},
+    source_map::SourceFile,
+    span::Span,
+};
+
+/// Compiles a desugared `ast` that has passed every check, from the file
+/// `source`.
+///
+/// Fails with just a message if there's no `main` to start the program from,
+/// since that isn't about any place in the source.
+pub fn compile(ast: &Ast, source: &SourceFile) -> Result<(Module, Vec<Diagnostic>), String> {
+    if main(ast).is_none() {
+        return Err("no `main` function to run".into());
+    }
+    Ok(module(ast, source, true))
+}
+
+/// Like [`compile`], but for a module that a Rust program calls into, which
+/// doesn't need a `main`. Without one, the entry point is just the init
+/// function.
+pub fn compile_library(ast: &Ast, source: &SourceFile) -> (Module, Vec<Diagnostic>) {
+    module(ast, source, false)
+}
+
+/// The `main` function item, if there is one.
+fn main(ast: &Ast) -> Option<&Item> {
+    ast.iter().find(|item| match &item.kind {
+        ItemKind::Function { name, .. } => name == "main",
+        _ => false,
+    })
+}
+
+fn module(ast: &Ast, source: &SourceFile, needs_main: bool) -> (Module, Vec<Diagnostic>) {
+    let mut codegen = Codegen::new(source);
+    codegen.module.source = source.name().into();
+
+    let mut items = Vec::new();
+    for item in ast {
+        match &item.kind {
+            ItemKind::Function { name, .. } | ItemKind::Const { ident: name, .. } => {
+                let global = codegen.module.globals.len() as u32;
+                codegen.module.globals.push(name.clone());
+                codegen.globals.insert(name, global);
+                items.push(item);
+            }
+            ItemKind::Struct { name, fields, .. } => {
+                let names = fields.iter().map(|field| field.name.clone()).collect();
+                codegen.shape(name.clone(), Fields::Named(names));
+            }
+            ItemKind::Enum { name, variants, .. } => {
+                for variant in variants {
+                    let (variant, fields) = match &variant.kind {
+                        VariantKind::Unit(variant) => (variant, Fields::Unit),
+                        VariantKind::Tuple(variant, types) => {
+                            (variant, Fields::Tuple(types.len() as u32))
+                        }
+                        VariantKind::Struct(variant, fields) => (
+                            variant,
+                            Fields::Named(fields.iter().map(|field| field.name.clone()).collect()),
+                        ),
+                    };
+                    codegen.shape(format!("{name}::{variant}"), fields);
+                }
+            }
+            ItemKind::Error => {}
+        }
+    }
+
+    match main(ast).map(|main| (&main.kind, main.span)) {
+        Some((ItemKind::Function { params, .. }, _)) if params.is_empty() => {}
+        Some((_, span)) => codegen.diagnostics.push(
+            Diagnostic::error("`main` must be a function without parameters", span)
+                .with_label("defined here"),
+        ),
+        None => assert!(!needs_main, "`compile` checks there's a `main`"),
+    }
+
+    // Compile every item to a function, noting which globals each one uses.
+    let mut compiled = Vec::new();
+    for item in &items {
+        codegen.line = codegen.line(item.span);
+        let function = match &item.kind {
+            ItemKind::Function {
+                name, params, body, ..
+            } => codegen.function(name, params, body, item.span),
+            ItemKind::Const { ident, value, .. } => codegen.function(ident, &[], value, item.span),
+            _ => unreachable!("only functions and consts are globals"),
+        };
+        compiled.push((function, std::mem::take(&mut codegen.references)));
+    }
+
+    let order = codegen.const_order(&items, &compiled);
+
+    codegen.scopes.push(Scope::new("<init>", 0));
+    codegen.line = 0;
+    for (global, item) in items.iter().enumerate() {
+        if let ItemKind::Function { .. } = item.kind {
+            codegen.emit(Op::Closure(compiled[global].0));
+            codegen.emit(Op::SetGlobal(global as u32));
+        }
+    }
+    for global in order {
+        codegen.line = codegen.line(items[global].span);
+        codegen.emit(Op::Closure(compiled[global].0));
+        codegen.emit(Op::Call(0));
+        codegen.emit(Op::SetGlobal(global as u32));
+    }
+    codegen.line = 0;
+    codegen.emit(Op::Unit);
+    codegen.emit(Op::Return);
+    let init = codegen.finish_function();
+    codegen.module.init = init;
+
+    codegen.module.entry = match codegen.globals.get("main") {
+        Some(&main) => {
+            codegen.scopes.push(Scope::new("<entry>", 0));
+            codegen.emit(Op::Closure(init));
+            codegen.emit(Op::Call(0));
+            codegen.emit(Op::Pop);
+            codegen.emit(Op::GetGlobal(main));
+            codegen.emit(Op::Call(0));
+            codegen.emit(Op::Return);
+            codegen.finish_function()
+        }
+        None => init,
+    };
+    (codegen.module, codegen.diagnostics)
+}
+
+struct Codegen<'a> {
+    source: &'a SourceFile,
+    module: Module,
+    /// The global holding each function and const item.
+    globals: HashMap<&'a str, u32>,
+    /// Struct and variant layouts, by `Struct` or `Enum::Variant`.
+    shapes: HashMap<String, u32>,
+    /// Functions that build tuple variants, for when they're used as values.
+    constructors: HashMap<u32, u32>,
+    constants: HashMap<ConstantKey, u32>,
+    natives: HashMap<String, u32>,
+    /// The functions being compiled, innermost last.
+    scopes: Vec<Scope<'a>>,
+    /// The globals used by the item being compiled.
+    references: Vec<u32>,
+    /// The line of the expression being compiled.
+    line: u32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A function being compiled, along with its variables.
+struct Scope<'a> {
+    function: Function,
+    /// The variables in scope, innermost last.
+    locals: Vec<Local<'a>>,
+    /// How many values the code compiled so far leaves on the stack, counting
+    /// the parameters.
+    height: usize,
+}
+
+struct Local<'a> {
+    name: &'a str,
+    slot: u16,
+}
+
+/// Where a variable lives, relative to the function being compiled.
+#[derive(Clone, Copy)]
+enum Variable {
+    Local(u16),
+    Upvalue(u16),
+}
+
+/// `Constant`, but hashable so constants can be shared.
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Int(i64),
+    Float(u64),
+    Str(String),
+    Char(char),
+}
+
+impl<'a> Codegen<'a> {
+    fn new(source: &'a SourceFile) -> Self {
+        Codegen {
+            source,
+            module: Module::default(),
+            globals: HashMap::new(),
+            shapes: HashMap::new(),
+            constructors: HashMap::new(),
+            constants: HashMap::new(),
+            natives: HashMap::new(),
+            scopes: Vec::new(),
+            references: Vec::new(),
+            line: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn line(&self, span: Span) -> u32 {
+        self.source.line_col(span.start).line as u32
+    }
+
+    fn shape(&mut self, name: String, fields: Fields) {
+        let shape = self.module.shapes.len() as u32;
+        self.shapes.insert(name.clone(), shape);
+        self.module.shapes.push(Shape { name, fields });
+    }
+
+    /// Compiles a function item, const initializer or lambda, returning its
+    /// index in the module.
+    fn function(&mut self, name: &str, params: &'a [Binding], body: &'a Expr, span: Span) -> u32 {
+        let arity = u8::try_from(params.len()).unwrap_or_else(|_| {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "`{name}` has {} parameters, but at most 255 are supported",
+                        params.len()
+                    ),
+                    span,
+                )
+                .with_label("too many parameters"),
+            );
+            u8::MAX
+        });
+
+        let mut scope = Scope::new(name, arity);
+        scope.locals = params
+            .iter()
+            .enumerate()
+            .map(|(slot, param)| Local {
+                name: &param.name,
+                slot: slot as u16,
+            })
+            .collect();
+
+        self.scopes.push(scope);
+        self.expr(body);
+        self.emit(Op::Return);
+        self.finish_function()
+    }
+
+    /// Adds the innermost function being compiled to the module, returning
+    /// its index.
+    fn finish_function(&mut self) -> u32 {
+        let scope = self
+            .scopes
+            .pop()
+            .expect("the function's scope is still there");
+        self.module.functions.push(scope.function);
+        self.module.functions.len() as u32 - 1
+    }
+
+    /// Orders the consts so that each one comes after everything it uses,
+    /// reporting any that depend on themselves.
+    ///
+    /// Consts are evaluated before `main` runs, so a use anywhere in the code
+    /// a const can reach counts, even inside a function or lambda that might
+    /// never be called.
+    fn const_order(&mut self, items: &[&Item], compiled: &[(u32, Vec<u32>)]) -> Vec<usize> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            global: usize,
+            items: &[&Item],
+            compiled: &[(u32, Vec<u32>)],
+            states: &mut [State],
+            order: &mut Vec<usize>,
+            diagnostics: &mut Vec<Diagnostic>,
+        ) {
+            states[global] = State::Visiting;
+            for &used in &compiled[global].1 {
+                let used = used as usize;
+                match states[used] {
+                    State::New => visit(used, items, compiled, states, order, diagnostics),
+                    State::Visiting => {
+                        if let ItemKind::Const { ident, .. } = &items[used].kind {
+                            diagnostics.push(
+                                Diagnostic::error(
+                                    format!("`{ident}` depends on its own value"),
+                                    items[used].span,
+                                )
+                                .with_label("defined in terms of itself")
+                                .with_note(
+                                    "consts are evaluated before `main` runs, so they can't \
+                                     refer to themselves, even from inside a function",
+                                ),
+                            );
+                            // Only report each cycle once.
+                            states[used] = State::Done;
+                        }
+                    }
+                    State::Done => {}
+                }
+            }
+            if states[global] == State::Visiting {
+                states[global] = State::Done;
+                if let ItemKind::Const { .. } = items[global].kind {
+                    order.push(global);
+                }
+            }
+        }
+
+        let mut states = vec![State::New; items.len()];
+        let mut order = Vec::new();
+        for (global, item) in items.iter().enumerate() {
+            if let ItemKind::Const { .. } = item.kind
+                && states[global] == State::New
+            {
+                visit(
+                    global,
+                    items,
+                    compiled,
+                    &mut states,
+                    &mut order,
+                    &mut self.diagnostics,
+                );
+            }
+        }
+        order
+    }
+
+    fn scope(&mut self) -> &mut Scope<'a> {
+        self.scopes
+            .last_mut()
+            .expect("code is always compiled inside a function")
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let effect = match op {
+            Op::Record(shape) => 1 - self.module.shapes[shape as usize].fields.len() as isize,
+            op => effect(op),
+        };
+        let line = self.line;
+        let scope = self.scope();
+        scope.height = scope
+            .height
+            .checked_add_signed(effect)
+            .expect("instructions only pop values that are on the stack");
+        scope.function.code.push(op);
+        scope.function.lines.push(line);
+        scope.function.code.len() - 1
+    }
+
+    /// Emits a jump whose target is filled in later by `patch`.
+    fn jump(&mut self, op: fn(u32) -> Op) -> usize {
+        self.emit(op(0))
+    }
+
+    /// Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let code = &mut self.scope().function.code;
+        let target = code.len() as u32;
+        code[at] = match code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            op => unreachable!("`{op:?}` isn't a jump"),
+        };
+    }
+
+    fn height(&mut self) -> usize {
+        self.scope().height
+    }
+
+    /// Sets the stack height at a point that's jumped to, where it isn't
+    /// simply the height after the previous instruction.
+    fn set_height(&mut self, height: usize) {
+        self.scope().height = height;
+    }
+
+    /// Makes the value on top of the stack a variable called `name`.
+    fn declare(&mut self, name: &'a str) {
+        let scope = self.scope();
+        let slot = u16::try_from(scope.height - 1).expect("more than 65535 values on the stack");
+        scope.locals.push(Local { name, slot });
+    }
+
+    /// Drops the variables declared since the stack was `height` high, and
+    /// `locals` were in scope, keeping the value on top.
+    fn end_scope(&mut self, height: usize, locals: usize) {
+        let scope = self.scope();
+        scope.locals.truncate(locals);
+        let len = scope.height - height - 1;
+        if len > 0 {
+            self.emit(Op::EndScope(len as u16));
+        }
+    }
+
+    fn constant(&mut self, constant: Constant) -> u32 {
+        let key = match &constant {
+            Constant::Int(int) => ConstantKey::Int(*int),
+            Constant::Float(float) => ConstantKey::Float(float.to_bits()),
+            Constant::Str(string) => ConstantKey::Str(string.clone()),
+            Constant::Char(char) => ConstantKey::Char(*char),
+        };
+        *self.constants.entry(key).or_insert_with(|| {
+            self.module.constants.push(constant);
+            self.module.constants.len() as u32 - 1
+        })
+    }
+
+    fn native(&mut self, name: &str) -> u32 {
+        *self.natives.entry(name.into()).or_insert_with(|| {
+            self.module.natives.push(name.into());
+            self.module.natives.len() as u32 - 1
+        })
+    }
+
+    /// Finds the local variable `name` in the function being compiled or any
+    /// function around it, capturing it if it's from further out.
+    fn variable(&mut self, name: &str) -> Option<Variable> {
+        let depth = self.scopes.len() - 1;
+        match self.scopes[depth].local(name) {
+            Some(slot) => Some(Variable::Local(slot)),
+            None => self.upvalue(depth, name).map(Variable::Upvalue),
+        }
+    }
+
+    /// The upvalue of the function at `depth` that captures `name`.
+    fn upvalue(&mut self, depth: usize, name: &str) -> Option<u16> {
+        let outer = depth.checked_sub(1)?;
+        let capture = match self.scopes[outer].local(name) {
+            Some(slot) => Capture::Local(slot),
+            None => Capture::Upvalue(self.upvalue(outer, name)?),
+        };
+
+        let captures = &mut self.scopes[depth].function.captures;
+        let index = captures
+            .iter()
+            .position(|&existing| existing == capture)
+            .unwrap_or_else(|| {
+                captures.push(capture);
+                captures.len() - 1
+            });
+        Some(index as u16)
+    }
+
+    /// Pushes the value of the variable, item or native called `name`.
+    fn load(&mut self, name: &str) {
+        if let Some(variable) = self.variable(name) {
+            self.get(variable);
+        } else if let Some(&global) = self.globals.get(name) {
+            self.references.push(global);
+            self.emit(Op::GetGlobal(global));
+        } else {
+            // Name resolution only lets through names that are defined, so
+            // this must be a native. The runtime looks it up on loading.
+            let native = self.native(name);
+            self.emit(Op::Native(native));
+        }
+    }
+
+    fn get(&mut self, variable: Variable) {
+        self.emit(match variable {
+            Variable::Local(slot) => Op::GetLocal(slot),
+            Variable::Upvalue(index) => Op::GetUpvalue(index),
+        });
+    }
+
+    /// Pops a value into `variable`.
+    fn store(&mut self, variable: Variable) {
+        self.emit(match variable {
+            Variable::Local(slot) => Op::SetLocal(slot),
+            Variable::Upvalue(index) => Op::SetUpvalue(index),
+        });
+    }
+}
+
+impl Scope<'_> {
+    /// The scope of a function with `arity` parameters, which start out as
+    /// its only values on the stack.
+    fn new(name: &str, arity: u8) -> Self {
+        Scope {
+            function: Function {
+                name: name.into(),
+                arity,
+                captures: Vec::new(),
+                code: Vec::new(),
+                lines: Vec::new(),
+            },
+            locals: Vec::new(),
+            height: arity.into(),
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<u16> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|local| local.name == name)
+            .map(|local| local.slot)
+    }
+}
+
+/// How many values `op` adds to the stack, or takes off it if negative.
+fn effect(op: Op) -> isize {
+    match op {
+        Op::Const(_)
+        | Op::Unit
+        | Op::True
+        | Op::False
+        | Op::Dup
+        | Op::GetLocal(_)
+        | Op::GetUpvalue(_)
+        | Op::GetGlobal(_)
+        | Op::Native(_)
+        | Op::Closure(_) => 1,
+        Op::Pop
+        | Op::SetLocal(_)
+        | Op::SetUpvalue(_)
+        | Op::SetGlobal(_)
+        | Op::JumpIfFalse(_)
+        | Op::Return
+        | Op::NoMatch
+        | Op::Add
+        | Op::Sub
+        | Op::Mul
+        | Op::Div
+        | Op::Exp
+        | Op::BitAnd
+        | Op::BitOr
+        | Op::BitXor
+        | Op::Eq
+        | Op::Neq
+        | Op::Lt
+        | Op::Gt
+        | Op::Leq
+        | Op::Geq
+        | Op::Index
+        | Op::SetField(_) => -1,
+        Op::SetIndex => -2,
+        Op::EndScope(len) => -(len as isize),
+        Op::Call(argc) => -(argc as isize),
+        Op::Array(len) | Op::Tuple(len) => 1 - len as isize,
+        Op::Jump(_)
+        | Op::Neg
+        | Op::Not
+        | Op::GetField(_)
+        | Op::Element(_)
+        | Op::Len
+        | Op::IsShape(_) => 0,
+        Op::Record(_) => unreachable!("depends on the shape, so `Codegen::emit` handles it"),
+    }
+}
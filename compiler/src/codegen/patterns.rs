@@ -0,0 +1,169 @@
+//! Patterns are matched in two passes over the scrutinee: one that checks
+//! every part of it the pattern constrains, then, once they've all passed,
+//! one that pushes the parts the pattern binds as new variables.
+
+use runtime::bytecode::{Constant, Fields, Op};
+
+use super::Codegen;
+use crate::parser::ast::{FieldPattern, Pattern, PatternKind, VariantPattern};
+
+impl<'a> Codegen<'a> {
+    /// Emits checks that the part of the scrutinee in `slot` that `path`
+    /// leads to matches `pattern`. Each check jumps away if it fails, and the
+    /// jumps are added to `fails` to be pointed at the next arm.
+    pub(super) fn pattern_tests(
+        &mut self,
+        pattern: &'a Pattern,
+        slot: u16,
+        path: &mut Vec<u32>,
+        fails: &mut Vec<usize>,
+    ) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding { .. } => {}
+            PatternKind::Literal(lit) => {
+                self.load_path(slot, path);
+                self.literal(lit);
+                self.emit(Op::Eq);
+                fails.push(self.jump(Op::JumpIfFalse));
+            }
+            PatternKind::Tuple(patterns) => self.element_tests(patterns, slot, path, fails),
+            PatternKind::Array(patterns) => {
+                self.load_path(slot, path);
+                self.emit(Op::Len);
+                let len = self.constant(Constant::Int(patterns.len() as i64));
+                self.emit(Op::Const(len));
+                self.emit(Op::Eq);
+                fails.push(self.jump(Op::JumpIfFalse));
+                self.element_tests(patterns, slot, path, fails);
+            }
+            PatternKind::Variant { enum_name, variant } => {
+                let (VariantPattern::Unit(name)
+                | VariantPattern::Tuple(name, _)
+                | VariantPattern::Struct(name, _)) = variant;
+                let shape = self.shape_test(&format!("{enum_name}::{name}"), slot, path, fails);
+                match variant {
+                    VariantPattern::Unit(_) => {}
+                    VariantPattern::Tuple(_, patterns) => {
+                        self.element_tests(patterns, slot, path, fails);
+                    }
+                    VariantPattern::Struct(_, fields) => {
+                        for (field, pattern) in self.fields(shape, fields) {
+                            path.push(field);
+                            self.pattern_tests(pattern, slot, path, fails);
+                            path.pop();
+                        }
+                    }
+                }
+            }
+            PatternKind::Struct { name, fields } => {
+                let shape = self.shape_test(name, slot, path, fails);
+                for (field, pattern) in self.fields(shape, fields) {
+                    path.push(field);
+                    self.pattern_tests(pattern, slot, path, fails);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    /// Pushes every variable `pattern` binds, in the order they're written.
+    pub(super) fn pattern_bindings(
+        &mut self,
+        pattern: &'a Pattern,
+        slot: u16,
+        path: &mut Vec<u32>,
+    ) {
+        let mut each = |this: &mut Self, fields: Vec<(u32, &'a Pattern)>| {
+            for (field, pattern) in fields {
+                path.push(field);
+                this.pattern_bindings(pattern, slot, path);
+                path.pop();
+            }
+        };
+
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Literal(_) => {}
+            PatternKind::Binding { name, .. } => {
+                self.load_path(slot, path);
+                self.declare(name);
+            }
+            PatternKind::Tuple(patterns)
+            | PatternKind::Array(patterns)
+            | PatternKind::Variant {
+                variant: VariantPattern::Tuple(_, patterns),
+                ..
+            } => {
+                let elements = patterns.iter().enumerate();
+                each(
+                    self,
+                    elements.map(|(i, pattern)| (i as u32, pattern)).collect(),
+                );
+            }
+            PatternKind::Variant {
+                variant: VariantPattern::Unit(_),
+                ..
+            } => {}
+            PatternKind::Variant {
+                enum_name,
+                variant: VariantPattern::Struct(name, fields),
+            } => {
+                let shape = self.shapes[&format!("{enum_name}::{name}")];
+                let fields = self.fields(shape, fields);
+                each(self, fields);
+            }
+            PatternKind::Struct { name, fields } => {
+                let fields = self.fields(self.shapes[name], fields);
+                each(self, fields);
+            }
+        }
+    }
+
+    fn element_tests(
+        &mut self,
+        patterns: &'a [Pattern],
+        slot: u16,
+        path: &mut Vec<u32>,
+        fails: &mut Vec<usize>,
+    ) {
+        for (i, pattern) in patterns.iter().enumerate() {
+            path.push(i as u32);
+            self.pattern_tests(pattern, slot, path, fails);
+            path.pop();
+        }
+    }
+
+    /// Checks that the value at `path` is a record of the shape called
+    /// `name`, returning the shape.
+    fn shape_test(&mut self, name: &str, slot: u16, path: &[u32], fails: &mut Vec<usize>) -> u32 {
+        let shape = self.shapes[name];
+        self.load_path(slot, path);
+        self.emit(Op::IsShape(shape));
+        fails.push(self.jump(Op::JumpIfFalse));
+        shape
+    }
+
+    /// Pairs each field pattern with the position of its field in `shape`.
+    fn fields(&self, shape: u32, patterns: &'a [FieldPattern]) -> Vec<(u32, &'a Pattern)> {
+        let Fields::Named(names) = &self.module.shapes[shape as usize].fields else {
+            unreachable!("rejected by type checking")
+        };
+        patterns
+            .iter()
+            .map(|pattern| {
+                let field = names
+                    .iter()
+                    .position(|name| *name == pattern.name)
+                    .expect("unknown fields are rejected by type checking");
+                (field as u32, &pattern.pattern)
+            })
+            .collect()
+    }
+
+    /// Pushes the part of the scrutinee in `slot` that `path` leads to.
+    fn load_path(&mut self, slot: u16, path: &[u32]) {
+        self.emit(Op::GetLocal(slot));
+        for &element in path {
+            self.emit(Op::Element(element));
+        }
+    }
+}
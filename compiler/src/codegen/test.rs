@@ -0,0 +1,291 @@
+use runtime::{GcConfig, RuntimeError, TraceFrame, Vm, bytecode::Module};
+
+use super::compile;
+use crate::{desugar::desugar, interpreter::Interpreter, parser::Parser, source_map::SourceMap};
+
+/// Compiles `input`, returning the module and the messages of any errors.
+fn build(input: &str) -> (Module, Vec<String>) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("test.ptn", input);
+    let (mut ast, errors) = Parser::with_file(input, file).file();
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(desugar(&mut ast).is_empty());
+
+    let (module, diagnostics) = match compile(&ast, sources.get(file)) {
+        Ok(compiled) => compiled,
+        Err(message) => return (Module::default(), vec![message]),
+    };
+    let errors = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+    (module, errors)
+}
+
+/// Compiles and runs `input` on the VM, going through the binary format on
+/// the way, and returns the displayed result along with everything it
+/// printed. Collecting garbage on every allocation mustn't change either.
+fn run(input: &str) -> (Result<String, RuntimeError>, String) {
+    let (module, errors) = build(input);
+    assert!(errors.is_empty(), "{errors:?}");
+    let module = Module::decode(&module.encode()).unwrap();
+
+    let run = |config| {
+        let mut out = Vec::new();
+        let result = Vm::new(module.clone(), &mut out).and_then(|vm| {
+            let mut vm = vm.with_gc(config);
+            let value = vm.run()?;
+            Ok(vm.display(value))
+        });
+        (result, String::from_utf8(out).unwrap())
+    };
+    let result = run(GcConfig::default());
+    let stressed = GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    };
+    assert_eq!(run(stressed), result);
+    result
+}
+
+/// Checks that the VM and the interpreter agree on everything `input` does.
+fn same_as_interpreter(input: &str) {
+    let (mut ast, _) = Parser::new(input).file();
+    desugar(&mut ast);
+    let mut out = Vec::new();
+    let expected = Interpreter::new(&ast, &mut out)
+        .and_then(|mut interpreter| interpreter.run_main())
+        .map(|value| value.to_string())
+        .map_err(|err| err.message);
+    let expected = (expected, String::from_utf8(out).unwrap());
+
+    let (result, out) = run(input);
+    assert_eq!((result.map_err(|err| err.message), out), expected);
+}
+
+#[test]
+fn compile_expressions() {
+    same_as_interpreter("fn main() -> 1 + 2 * 3 - 8 / 2");
+    same_as_interpreter("fn main() -> (2 ** 3 ** 2, 1.5 * 2., \"con\" + \"cat\")");
+    same_as_interpreter("fn main() -> (6 & 3, 6 | 3, 6 ^ 3, -5, !true, !5)");
+    same_as_interpreter("fn main() -> 1 < 2 && 'a' >= 'b' || \"a\" < \"b\"");
+    same_as_interpreter("fn main() -> (false && 1 / 0 == 0, true || 1 / 0 == 0)");
+    same_as_interpreter("fn main() -> ([(1, 'x')] == [(1, 'x')], [1][0], (1,))");
+    same_as_interpreter("fn main() -> { let x = if (false) 1; let y = { 2; }; (x, y) }");
+    same_as_interpreter(
+        "
+        fn main() -> {
+            let a = 1;
+            let b = { let a = a + 1; let c = a * 10; c + a };
+            (a, b, { let z = 3 })
+        }
+    ",
+    );
+}
+
+#[test]
+fn compile_functions_and_closures() {
+    same_as_interpreter(
+        "
+        fn fib(n) -> if (n < 2) n else fib(n - 1) + fib(n - 2)
+        fn main() -> fib(15)
+    ",
+    );
+    same_as_interpreter(
+        "
+        fn main() -> {
+            let mut n = 1;
+            let get = |_unused| -> n;
+            let add = |by| -> n = n + by;
+            add(10);
+            let n = 100;
+            (get(0), n)
+        }
+    ",
+    );
+    same_as_interpreter(
+        "
+        fn twice(f) -> |x| -> f(f(x))
+        const inc: fn(Int): Int = add(1, _)
+        fn add(a, b) -> a + b
+        fn main() -> (twice(inc)(0), 1 |> inc >> twice(inc), twice, |x| -> x, len)
+    ",
+    );
+    // A lambda nested in a lambda captures through the one around it.
+    same_as_interpreter(
+        "
+        fn counter() -> {
+            let mut count = 0;
+            |by| -> |_unused| -> { count = count + by; count }
+        }
+        fn main() -> {
+            let step = counter();
+            step(1)(0);
+            step(2)(0);
+            step(3)(0)
+        }
+    ",
+    );
+    same_as_interpreter(
+        r#"
+        fn id(x) -> x
+        fn counter() -> { let mut n = 0; |by| -> { n = n + by; n } }
+        fn noisy(f) -> { print("noisy "); f }
+        fn sum(a, b) -> a + b
+        fn main() -> {
+            let up = counter() >> id;
+            let down = id << noisy(counter());
+            let add = sum(noisy(1), _);
+            (up(1), up(1), down(2), down(2), add(1), add(2), noisy(1) |> id)
+        }
+    "#,
+    );
+}
+
+#[test]
+fn compile_structs_enums_and_match() {
+    same_as_interpreter(
+        r#"
+        struct Point { x: Int, y: Int }
+        enum Shape {
+            Circle(Float),
+            Rect { w: Int, h: Int },
+            Empty,
+        }
+
+        fn describe(shape) -> match shape {
+            Shape::Circle(r) -> "circle",
+            Shape::Rect { w, h: 1 } -> "line",
+            Shape::Rect { w, h } -> to_string(w * h),
+            Shape::Empty -> "empty",
+        }
+
+        fn main() -> {
+            let mut p = Point { y: 2, x: 1 };
+            p.x = 10;
+            let shapes = [Shape::Circle(1.), Shape::Rect { w: 4, h: 1 }, Shape::Rect { w: 2, h: 3 }];
+            let circle = Shape::Circle;
+            (p, describe(shapes[0]), describe(shapes[1]), describe(shapes[2]), describe(Shape::Empty), circle(2.))
+        }
+    "#,
+    );
+    same_as_interpreter("fn main() -> match (1, [2, 3]) { (a, [_, b]) -> a + b, _ -> 0 }");
+    same_as_interpreter(
+        r#"fn main() -> match [1] { [] -> "none", [x, y] -> "two", _ -> "other" }"#,
+    );
+    same_as_interpreter(r#"fn main() -> match "b" { "a" -> 1, "b" -> 2, _ -> 3 }"#);
+}
+
+#[test]
+fn compile_assignments() {
+    same_as_interpreter(
+        "
+        struct Grid { rows: [[Int]] }
+        fn main() -> {
+            let mut grid = Grid { rows: [[0, 0], [0, 0]] };
+            let before = grid;
+            grid.rows[1][0] = 5;
+            let mut pair = (1, 2);
+            pair._1 = 3;
+            (grid.rows, pair, before.rows)
+        }
+    ",
+    );
+}
+
+#[test]
+fn compile_consts_and_output() {
+    same_as_interpreter(
+        r#"
+        const greeting: Str = "hello " + name
+        const name: Str = "world"
+        const lengths: [Int] = [len(greeting), count()]
+        fn count() -> len(name)
+        fn main() -> {
+            println(greeting);
+            print(lengths);
+        }
+    "#,
+    );
+}
+
+#[test]
+fn compile_interpolated_strings() {
+    same_as_interpreter(
+        r#"
+        struct Point { x: Int, y: Float }
+        fn main() -> {
+            let p = Point { x: 1, y: 2.5 };
+            println("at {p.x}, {p.y}: {[p.x]} {(p.x, "s", 'c')} {p.x > 0}");
+            "{"nested {p.x + 1}"} \{literal\}"
+        }
+    "#,
+    );
+    // Values are shown by the native, whatever else is called `to_string`.
+    let (result, _) = run(r#"fn to_string(x) -> "no" fn main() -> "{1}""#);
+    assert_eq!(result.unwrap(), "1");
+}
+
+#[test]
+fn collect_garbage_while_running() {
+    // Every step builds new aggregates and closures and drops the old ones,
+    // while the accumulator and captured variables have to survive.
+    same_as_interpreter(
+        r#"
+        struct Acc { total: Int, log: [Str] }
+        fn step(acc, n) -> {
+            let add = |x| -> x + n;
+            let mut next = Acc { total: add(acc.total), log: [to_string(n), "!"] };
+            next.log[1] = acc.log[0];
+            next
+        }
+        fn run(acc, n) -> if (n == 0) acc else run(step(acc, n), n - 1)
+        fn main() -> run(Acc { total: 0, log: ["start", ""] }, 100)
+    "#,
+    );
+}
+
+#[test]
+fn report_runtime_errors_with_lines() {
+    same_as_interpreter("fn main() -> [1, 2][2]");
+    same_as_interpreter("fn main() -> 9223372036854775807 + 1");
+    same_as_interpreter("fn f(a) -> a fn main() -> f(1, 2)");
+    same_as_interpreter("fn main() -> match 3 { 1 -> 1 }");
+
+    let (result, _) = run("fn divide(n) ->\n    n / 0\n\nfn main() ->\n    divide(1)");
+    let trace = result.unwrap_err().trace;
+    assert_eq!(
+        trace,
+        [
+            TraceFrame {
+                function: "divide".into(),
+                line: Some(2),
+            },
+            TraceFrame {
+                function: "main".into(),
+                line: Some(5),
+            },
+            TraceFrame {
+                function: "<entry>".into(),
+                line: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn report_codegen_errors() {
+    assert_eq!(build("fn f() -> 1").1, ["no `main` function to run"]);
+    assert_eq!(
+        build("fn main(x) -> 1").1,
+        ["`main` must be a function without parameters"]
+    );
+    assert_eq!(
+        build("const a: Int = b const b: Int = a fn main() -> a").1,
+        ["`a` depends on its own value"]
+    );
+    assert_eq!(
+        build("const a: Int = f() fn f() -> a + 1 fn main() -> a").1,
+        ["`a` depends on its own value"]
+    );
+}
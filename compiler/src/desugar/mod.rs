@@ -0,0 +1,253 @@
+//! Rewrites surface syntax into the smaller core language that later stages
+//! work with, so that they never see partial application or the pipeline
+//! and composition operators.
+
+#[cfg(test)]
+mod test;
+
+use std::{mem, sync::Arc};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{Ast, Binding, Bop, Expr, ExprKind, Item, ItemKind, TypeKind},
+    span::Span,
+};
+
+/// Desugars every item in `ast` in place, returning any problems found on the way.
+pub fn desugar(ast: &mut Ast) -> Vec<Diagnostic> {
+    let mut desugarer = Desugarer::default();
+    for item in ast {
+        desugarer.item(item);
+    }
+    desugarer.diagnostics
+}
+
+#[derive(Default)]
+struct Desugarer {
+    /// Used to generate parameter names that can't clash with user code.
+    next_name: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Desugarer {
+    fn item(&mut self, item: &mut Item) {
+        match &mut item.kind {
+            ItemKind::Const { ty, value, .. } => {
+                let holes = placeholders(value);
+                self.expr(value);
+
+                // A partially applied constant takes its parameter and return
+                // types from the declared function type.
+                if holes == 0 {
+                    return;
+                }
+                let TypeKind::Fn {
+                    params: declared_params,
+                    result,
+                } = &ty.kind
+                else {
+                    return;
+                };
+
+                if declared_params.len() != holes {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "partial application takes {holes} argument{}, but `{ty}` expects {}",
+                                if holes == 1 { "" } else { "s" },
+                                declared_params.len(),
+                            ),
+                            value.span,
+                        )
+                        .with_label(format!(
+                            "{holes} `_` placeholder{} here",
+                            if holes == 1 { "" } else { "s" },
+                        ))
+                        .with_secondary(ty.span, "declared type"),
+                    );
+                    return;
+                }
+
+                let ExprKind::Block { exprs, .. } = &mut value.kind else {
+                    unreachable!("a call with placeholders desugars to a block")
+                };
+                let Some(Expr {
+                    kind:
+                        ExprKind::Lambda {
+                            params,
+                            return_type,
+                            ..
+                        },
+                    ..
+                }) = exprs.last_mut()
+                else {
+                    unreachable!("the block ends in the lambda")
+                };
+                for (param, ty) in Arc::make_mut(params).iter_mut().zip(declared_params) {
+                    param.type_annotation = Some(ty.clone());
+                }
+                *return_type = Some((**result).clone());
+            }
+            ItemKind::Function { body, .. } => self.expr(body),
+            ItemKind::Struct { .. } | ItemKind::Enum { .. } | ItemKind::Error => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        for child in expr.children_mut() {
+            self.expr(child);
+        }
+
+        if placeholders(expr) > 0 {
+            self.partial_application(expr);
+        } else if let ExprKind::BinaryOp {
+            op: Bop::Pipeline | Bop::ComposeRight | Bop::ComposeLeft,
+            ..
+        } = expr.kind
+        {
+            self.function_operator(expr);
+        }
+    }
+
+    fn fresh_name(&mut self) -> String {
+        let name = format!("${}", self.next_name);
+        self.next_name += 1;
+        name
+    }
+
+    /// Stores `value` in a fresh variable declared by a `let` added to
+    /// `lets`, returning the variable.
+    fn bind(&mut self, value: Expr, lets: &mut Vec<Expr>) -> Expr {
+        let span = value.span;
+        let name = self.fresh_name();
+        lets.push(Expr::new(
+            ExprKind::Let {
+                binding: Binding {
+                    mutable: false,
+                    name: name.clone(),
+                    type_annotation: None,
+                    span,
+                },
+                value: Box::new(value),
+            },
+            span,
+        ));
+        Expr::new(ExprKind::Ident(name), span)
+    }
+
+    /// Turns `f(a, _, _)` into `{ let $0 = f; let $1 = a; |$2, $3| -> $0($1, $2, $3) }`,
+    /// so that the function and the other arguments are evaluated once, when
+    /// the partial application is.
+    fn partial_application(&mut self, expr: &mut Expr) {
+        let span = expr.span;
+        let ExprKind::FnCall { fun, args } = mem::replace(&mut expr.kind, ExprKind::Error) else {
+            unreachable!()
+        };
+
+        let mut lets = Vec::new();
+        let mut params = Vec::new();
+        let fun = self.bind(*fun, &mut lets);
+        let args = args
+            .into_iter()
+            .map(|arg| {
+                if arg.kind != ExprKind::Placeholder {
+                    return self.bind(arg, &mut lets);
+                }
+                let name = self.fresh_name();
+                params.push(Binding {
+                    mutable: false,
+                    name: name.clone(),
+                    type_annotation: None,
+                    span: arg.span,
+                });
+                Expr::new(ExprKind::Ident(name), arg.span)
+            })
+            .collect();
+
+        let call = Expr::new(
+            ExprKind::FnCall {
+                fun: Box::new(fun),
+                args,
+            },
+            span,
+        );
+        expr.kind = block(lets, lambda(params, call, span));
+    }
+
+    /// Turns `x |> f` into `{ let $0 = x; f($0) }`, `f >> g` into
+    /// `{ let $0 = f; let $1 = g; |$2| -> $1($0($2)) }` and `f << g` into
+    /// `{ let $0 = f; let $1 = g; |$2| -> $0($1($2)) }`, so that operands are
+    /// evaluated once, from left to right.
+    fn function_operator(&mut self, expr: &mut Expr) {
+        let span = expr.span;
+        let ExprKind::BinaryOp { op, lhs, rhs } = mem::replace(&mut expr.kind, ExprKind::Error)
+        else {
+            unreachable!()
+        };
+        let call = |fun: Expr, arg: Expr| {
+            Expr::new(
+                ExprKind::FnCall {
+                    fun: Box::new(fun),
+                    args: vec![arg],
+                },
+                span,
+            )
+        };
+
+        let mut lets = Vec::new();
+        let lhs = self.bind(*lhs, &mut lets);
+        expr.kind = match op {
+            Bop::Pipeline => block(lets, call(*rhs, lhs)),
+            Bop::ComposeRight | Bop::ComposeLeft => {
+                let rhs = self.bind(*rhs, &mut lets);
+                let (first, second) = if op == Bop::ComposeRight {
+                    (lhs, rhs)
+                } else {
+                    (rhs, lhs)
+                };
+
+                let name = self.fresh_name();
+                let param = Expr::new(ExprKind::Ident(name.clone()), span);
+                let params = vec![Binding {
+                    mutable: false,
+                    name,
+                    type_annotation: None,
+                    span,
+                }];
+                block(lets, lambda(params, call(second, call(first, param)), span))
+            }
+            _ => unreachable!(),
+        };
+    }
+}
+
+/// A block of `lets` that evaluates to `result`.
+fn block(mut lets: Vec<Expr>, result: Expr) -> ExprKind {
+    lets.push(result);
+    ExprKind::Block {
+        exprs: lets,
+        trailing: true,
+    }
+}
+
+fn lambda(params: Vec<Binding>, body: Expr, span: Span) -> Expr {
+    Expr::new(
+        ExprKind::Lambda {
+            params: params.into(),
+            return_type: None,
+            body: Arc::new(body),
+        },
+        span,
+    )
+}
+
+/// The number of `_` arguments if `expr` is a call, otherwise 0.
+fn placeholders(expr: &Expr) -> usize {
+    match &expr.kind {
+        ExprKind::FnCall { args, .. } => args
+            .iter()
+            .filter(|arg| arg.kind == ExprKind::Placeholder)
+            .count(),
+        _ => 0,
+    }
+}
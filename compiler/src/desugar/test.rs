@@ -0,0 +1,187 @@
+use super::desugar;
+use crate::parser::{
+    Parser,
+    ast::{Ast, Binding, Expr, ExprKind, ItemKind, Lit, TypeKind},
+};
+use crate::span::Span;
+
+fn desugar_source(input: &str) -> (Ast, Vec<String>) {
+    let (mut ast, errors) = Parser::new(input).file();
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let diagnostics = desugar(&mut ast);
+    (
+        ast,
+        diagnostics.into_iter().map(|diag| diag.message).collect(),
+    )
+}
+
+fn int() -> TypeKind {
+    TypeKind::Ident {
+        name: "Int".into(),
+        generics: vec![],
+    }
+}
+
+fn param(name: &str) -> Binding {
+    Binding {
+        mutable: false,
+        name: name.into(),
+        type_annotation: None,
+        span: Span::default(),
+    }
+}
+
+fn ident(name: &str) -> Expr {
+    ExprKind::Ident(name.into()).into()
+}
+
+fn call(fun: Expr, args: Vec<Expr>) -> Expr {
+    ExprKind::FnCall {
+        fun: Box::new(fun),
+        args,
+    }
+    .into()
+}
+
+/// `{ let $a = x; ... result }`, for each `($a, x)` in `lets`.
+fn block(lets: Vec<(&str, Expr)>, result: Expr) -> Expr {
+    let mut exprs: Vec<Expr> = lets
+        .into_iter()
+        .map(|(name, value)| {
+            ExprKind::Let {
+                binding: param(name),
+                value: Box::new(value),
+            }
+            .into()
+        })
+        .collect();
+    exprs.push(result);
+    ExprKind::Block {
+        exprs,
+        trailing: true,
+    }
+    .into()
+}
+
+fn lambda(params: &[&str], body: Expr) -> Expr {
+    ExprKind::Lambda {
+        params: params.iter().map(|name| param(name)).collect(),
+        return_type: None,
+        body: body.into(),
+    }
+    .into()
+}
+
+#[test]
+fn partial_application() {
+    let (ast, diagnostics) = desugar_source("fn f() -> g(1, _, h(_))");
+    assert!(diagnostics.is_empty());
+
+    let ItemKind::Function { body, .. } = &ast[0].kind else {
+        panic!("expected function");
+    };
+    // The function and the other arguments are evaluated once, up front.
+    assert_eq!(
+        *body,
+        block(
+            vec![
+                ("$2", ident("g")),
+                ("$3", Lit::Int(1).into()),
+                (
+                    "$5",
+                    block(
+                        vec![("$0", ident("h"))],
+                        lambda(&["$1"], call(ident("$0"), vec![ident("$1")]))
+                    )
+                ),
+            ],
+            lambda(
+                &["$4"],
+                call(ident("$2"), vec![ident("$3"), ident("$4"), ident("$5")])
+            )
+        )
+    );
+}
+
+#[test]
+fn partial_application_takes_declared_types() {
+    let (ast, diagnostics) = desugar_source(
+        "
+        const inc: fn(Int): Int = sum(1, _)
+        const bad: fn(Int): Int = sum(_, _)
+    ",
+    );
+    assert_eq!(
+        diagnostics,
+        ["partial application takes 2 arguments, but `fn(Int): Int` expects 1"]
+    );
+
+    let label = |input: &str| {
+        let (mut ast, _) = Parser::new(input).file();
+        desugar(&mut ast).remove(0).primary.message
+    };
+    assert_eq!(label("const f: fn(): Int = g(_)"), "1 `_` placeholder here");
+    assert_eq!(
+        label("const f: fn(): Int = g(_, _)"),
+        "2 `_` placeholders here"
+    );
+
+    let ItemKind::Const { value, .. } = &ast[0].kind else {
+        panic!("expected const");
+    };
+    let ExprKind::Block { exprs, .. } = &value.kind else {
+        panic!("expected block");
+    };
+    let Some(ExprKind::Lambda {
+        params,
+        return_type,
+        ..
+    }) = exprs.last().map(|expr| &expr.kind)
+    else {
+        panic!("expected lambda");
+    };
+    assert_eq!(params[0].type_annotation, Some(int().into()));
+    assert_eq!(return_type, &Some(int().into()));
+}
+
+#[test]
+fn pipeline_and_composition() {
+    let (ast, diagnostics) = desugar_source("fn f() -> x |> g >> h(1, _)");
+    assert!(diagnostics.is_empty());
+
+    let ItemKind::Function { body, .. } = &ast[0].kind else {
+        panic!("expected function");
+    };
+    let partial = block(
+        vec![("$0", ident("h")), ("$1", Lit::Int(1).into())],
+        lambda(&["$2"], call(ident("$0"), vec![ident("$1"), ident("$2")])),
+    );
+    let composed = block(
+        vec![("$3", ident("g")), ("$4", partial)],
+        lambda(
+            &["$5"],
+            call(ident("$4"), vec![call(ident("$3"), vec![ident("$5")])]),
+        ),
+    );
+    // Operands are evaluated once, from left to right.
+    assert_eq!(
+        *body,
+        block(vec![("$6", ident("x"))], call(composed, vec![ident("$6")]))
+    );
+
+    let (ast, _) = desugar_source("fn f() -> g << h");
+    let ItemKind::Function { body, .. } = &ast[0].kind else {
+        panic!("expected function");
+    };
+    assert_eq!(
+        *body,
+        block(
+            vec![("$0", ident("g")), ("$1", ident("h"))],
+            lambda(
+                &["$2"],
+                call(ident("$0"), vec![call(ident("$1"), vec![ident("$2")])])
+            )
+        )
+    );
+}
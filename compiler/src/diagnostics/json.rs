@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+use super::{Diagnostic, Label, Renderer, Severity};
+use crate::source_map::SourceMap;
+
+impl Diagnostic {
+    /// Formats the diagnostic as a single line of JSON, for editors and other
+    /// tools. Line and column numbers are 1-based, offsets are in bytes, and
+    /// `rendered` holds what the plain [`Renderer`] would print.
+    ///
+    /// ```text
+    /// {"severity":"error","message":"...","spans":[{"file":"main.ptn","start":4,"end":7,
+    ///  "line_start":1,"column_start":5,"line_end":1,"column_end":8,"primary":true,
+    ///  "label":"..."}],"notes":[],"rendered":"error: ..."}
+    /// ```
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let spans: Vec<_> = std::iter::once((&self.primary, true))
+            .chain(self.secondary.iter().map(|label| (label, false)))
+            .map(|(label, primary)| span(sources, label, primary))
+            .collect();
+        object(
+            self.severity,
+            &self.message,
+            &spans,
+            &self.notes,
+            &Renderer::plain().render(sources, self),
+        )
+    }
+}
+
+/// Formats a message that isn't about any particular source, like a file
+/// that couldn't be read, the same way as [`Diagnostic::to_json`].
+pub fn message_to_json(severity: Severity, message: &str) -> String {
+    object(
+        severity,
+        message,
+        &[],
+        &[],
+        &format!("{severity}: {message}\n"),
+    )
+}
+
+fn object(
+    severity: Severity,
+    message: &str,
+    spans: &[String],
+    notes: &[String],
+    rendered: &str,
+) -> String {
+    let notes: Vec<_> = notes.iter().map(|note| string(note)).collect();
+    format!(
+        r#"{{"severity":{},"message":{},"spans":[{}],"notes":[{}],"rendered":{}}}"#,
+        string(&severity.to_string()),
+        string(message),
+        spans.join(","),
+        notes.join(","),
+        string(rendered),
+    )
+}
+
+fn span(sources: &SourceMap, label: &Label, primary: bool) -> String {
+    let span = label.span;
+    let start = sources.line_col(span.file, span.start);
+    let end = sources.line_col(span.file, span.end);
+    format!(
+        r#"{{"file":{},"start":{},"end":{},"line_start":{},"column_start":{},"line_end":{},"column_end":{},"primary":{primary},"label":{}}}"#,
+        string(sources.get(span.file).name()),
+        span.start,
+        span.end,
+        start.line,
+        start.col,
+        end.line,
+        end.col,
+        string(&label.message),
+    )
+}
+
+/// Quotes and escapes `text` as a JSON string.
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
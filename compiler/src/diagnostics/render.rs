@@ -0,0 +1,186 @@
+use std::fmt::Write;
+
+use super::{Diagnostic, Label, Severity};
+use crate::{
+    source_map::{SourceFile, SourceMap},
+    span::FileId,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Formats diagnostics as text, quoting the source lines they point at.
+///
+/// ```text
+/// error: expected token ), found token eof
+///  --> example.ptn:1:9
+///   |
+/// 1 | foo(1, 2
+///   |         ^ expected `)`
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+
+/// A label along with where it starts, resolved against the source map.
+struct Marker<'a> {
+    label: &'a Label,
+    primary: bool,
+    line: usize,
+    col: usize,
+}
+
+impl Renderer {
+    /// A renderer that emits ANSI color codes, for terminals.
+    pub fn colored() -> Self {
+        Self { color: true }
+    }
+
+    /// A renderer that emits plain text, for logs and files.
+    pub fn plain() -> Self {
+        Self { color: false }
+    }
+
+    pub fn render(&self, sources: &SourceMap, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let _ = writeln!(
+            out,
+            "{}{}",
+            self.paint(severity_style, &diagnostic.severity.to_string()),
+            self.paint(BOLD, &format!(": {}", diagnostic.message)),
+        );
+
+        let markers: Vec<_> = std::iter::once((&diagnostic.primary, true))
+            .chain(diagnostic.secondary.iter().map(|label| (label, false)))
+            .map(|(label, primary)| {
+                let position = sources.line_col(label.span.file, label.span.start);
+                Marker {
+                    label,
+                    primary,
+                    line: position.line,
+                    col: position.col,
+                }
+            })
+            .collect();
+
+        let gutter_width = markers
+            .iter()
+            .map(|marker| marker.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = " ".repeat(gutter_width);
+
+        let mut files: Vec<FileId> = Vec::new();
+        for marker in &markers {
+            if !files.contains(&marker.label.span.file) {
+                files.push(marker.label.span.file);
+            }
+        }
+
+        for (i, file) in files.into_iter().enumerate() {
+            let source = sources.get(file);
+
+            let mut file_markers: Vec<_> = markers
+                .iter()
+                .filter(|marker| marker.label.span.file == file)
+                .collect();
+            file_markers.sort_by_key(|marker| (marker.line, marker.col));
+
+            let location = if i == 0 { &markers[0] } else { file_markers[0] };
+            let _ = writeln!(
+                out,
+                "{gutter}{} {}:{}:{}",
+                self.paint(BLUE, if i == 0 { "-->" } else { ":::" }),
+                source.name(),
+                location.line,
+                location.col,
+            );
+            let _ = writeln!(out, "{gutter} {}", self.paint(BLUE, "|"));
+
+            let mut previous_line = None;
+            for marker in file_markers {
+                if previous_line != Some(marker.line) {
+                    if previous_line.is_some_and(|previous| marker.line > previous + 1) {
+                        let _ = writeln!(out, "{}", self.paint(BLUE, "..."));
+                    }
+                    let text = source.line(marker.line).unwrap_or_default();
+                    let _ = writeln!(
+                        out,
+                        "{} {text}",
+                        self.paint(BLUE, &format!("{:<gutter_width$} |", marker.line)),
+                    );
+                    previous_line = Some(marker.line);
+                }
+
+                let _ = writeln!(
+                    out,
+                    "{gutter} {} {}",
+                    self.paint(BLUE, "|"),
+                    self.underline(source, marker, severity_style),
+                );
+            }
+        }
+
+        if !diagnostic.notes.is_empty() {
+            let _ = writeln!(out, "{gutter} {}", self.paint(BLUE, "|"));
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{gutter} {} note: {note}", self.paint(BLUE, "="));
+        }
+
+        out
+    }
+
+    /// Builds the `^^^ message` line that sits under a marker's source line.
+    fn underline(&self, source: &SourceFile, marker: &Marker, severity_style: &str) -> String {
+        let text = source.line(marker.line).unwrap_or_default();
+        let span = marker.label.span;
+
+        // Tabs are kept in the indentation so the underline lines up with the source.
+        let indent: String = text
+            .chars()
+            .take(marker.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let end = source.line_col(span.end);
+        let width = if end.line == marker.line {
+            end.col.saturating_sub(marker.col)
+        } else {
+            (text.chars().count() + 1).saturating_sub(marker.col)
+        }
+        .max(1);
+
+        let (style, symbol) = if marker.primary {
+            (severity_style, "^")
+        } else {
+            (BLUE, "-")
+        };
+
+        let mut underline = symbol.repeat(width);
+        if !marker.label.message.is_empty() {
+            underline.push(' ');
+            underline.push_str(&marker.label.message);
+        }
+
+        format!("{indent}{}", self.paint(style, &underline))
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
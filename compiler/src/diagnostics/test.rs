@@ -0,0 +1,92 @@
+use super::{Diagnostic, Renderer, Severity, message_to_json};
+use crate::{parser::Parser, source_map::SourceMap, span::Span};
+
+#[test]
+fn render_parse_error() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("example.ptn", "fn foo(a) -> {\n    bar(a, 2\n}\n");
+
+    let (_, errors) = Parser::with_file(sources.get(file).source(), file).file();
+    let rendered = Renderer::plain().render(&sources, &(&errors[0]).into());
+
+    assert_eq!(
+        rendered,
+        "\
+error: expected token ), found token }
+ --> example.ptn:3:1
+  |
+3 | }
+  | ^ expected )
+"
+    );
+}
+
+#[test]
+fn render_secondary_labels_and_notes() {
+    let mut sources = SourceMap::new();
+    let other = sources.add("lib.ptn", "const x: Int = 1\n");
+    let file = sources.add(
+        "main.ptn",
+        "fn main() -> {\n\tlet y = 1;\n\n\n\n\n\n\n\n    x = y\n}\n",
+    );
+
+    let diagnostic = Diagnostic::error("cannot assign twice", Span::new(file, 38, 43))
+        .with_label("assignment here")
+        .with_secondary(Span::new(file, 16, 21), "first binding")
+        .with_secondary(Span::new(other, 6, 7), "defined here")
+        .with_note("consider `let mut`");
+
+    assert_eq!(
+        Renderer::plain().render(&sources, &diagnostic),
+        "\
+error: cannot assign twice
+  --> main.ptn:10:5
+   |
+2  | \tlet y = 1;
+   | \t----- first binding
+...
+10 |     x = y
+   |     ^^^^^ assignment here
+  ::: lib.ptn:1:7
+   |
+1  | const x: Int = 1
+   |       - defined here
+   |
+   = note: consider `let mut`
+"
+    );
+}
+
+#[test]
+fn render_colored() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("a.ptn", "x");
+
+    let rendered = Renderer::colored().render(
+        &sources,
+        &Diagnostic::warning("unused", Span::new(file, 0, 1)),
+    );
+
+    assert!(rendered.starts_with("\x1b[1;33mwarning\x1b[0m\x1b[1m: unused\x1b[0m\n"));
+    assert!(rendered.contains("\x1b[1;33m^\x1b[0m"));
+}
+
+#[test]
+fn format_as_json() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("say \"hi\".ptn", "let x\n\ty");
+
+    let diagnostic = Diagnostic::error("unknown `y`", Span::new(file, 7, 8))
+        .with_label("not found")
+        .with_secondary(Span::new(file, 4, 5), "did you mean `x`?")
+        .with_note("names are\tcase sensitive");
+
+    assert_eq!(
+        diagnostic.to_json(&sources),
+        r#"{"severity":"error","message":"unknown `y`","spans":[{"file":"say \"hi\".ptn","start":7,"end":8,"line_start":2,"column_start":2,"line_end":2,"column_end":3,"primary":true,"label":"not found"},{"file":"say \"hi\".ptn","start":4,"end":5,"line_start":1,"column_start":5,"line_end":1,"column_end":6,"primary":false,"label":"did you mean `x`?"}],"notes":["names are\tcase sensitive"],"rendered":"error: unknown `y`\n --> say \"hi\".ptn:2:2\n  |\n1 | let x\n  |     - did you mean `x`?\n2 | \ty\n  | \t^ not found\n  |\n  = note: names are\tcase sensitive\n"}"#
+    );
+    assert_eq!(
+        message_to_json(Severity::Warning, "no\u{7}pe"),
+        r#"{"severity":"warning","message":"no\u0007pe","spans":[],"notes":[],"rendered":"warning: no\u0007pe\n"}"#
+    );
+}
@@ -0,0 +1,104 @@
+//! Compiling source straight into the runtime, for Rust programs that host
+//! patinac.
+//!
+//! ```
+//! use compiler::embed::LoadSource;
+//! use runtime::Vm;
+//!
+//! let source = "fn area(w, h) -> scale(w * h)";
+//! let mut vm = Vm::builder()
+//!     .native("scale", |n: i64| n * 10)
+//!     .load_source("shapes.ptn", source)
+//!     .unwrap();
+//! let area: i64 = vm.call("area", (2, 3)).unwrap();
+//! assert_eq!(area, 60);
+//! ```
+
+#[cfg(test)]
+mod test;
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+use runtime::{Builder, RuntimeError, Vm, bytecode::Module};
+
+use crate::{
+    codegen,
+    desugar::desugar,
+    diagnostics::{Diagnostic, Renderer},
+    mutability,
+    natives::Natives,
+    parser::Parser,
+    resolve::resolve,
+    source_map::SourceMap,
+    typeck,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The source has errors, each rendered as plain text.
+    Compile(Vec<String>),
+    /// The runtime refused the compiled module.
+    Runtime(RuntimeError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Compile(diagnostics) => write!(f, "{}", diagnostics.join("\n")),
+            LoadError::Runtime(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Loading source into a [`Vm`] that's being built.
+pub trait LoadSource<'a> {
+    /// Compiles `source`, calling it `name` in errors, and loads it. Calls
+    /// to natives the host added are checked against their signatures, and
+    /// the source doesn't need a `main`.
+    fn load_source(self, name: &str, source: &str) -> Result<Vm<'a>, LoadError>;
+}
+
+impl<'a> LoadSource<'a> for Builder<'a> {
+    fn load_source(self, name: &str, source: &str) -> Result<Vm<'a>, LoadError> {
+        let mut natives = Natives::builtins();
+        for (native, signature) in self.signatures() {
+            natives.add_signature(native, signature);
+        }
+        let module = compile(name, source, &natives).map_err(LoadError::Compile)?;
+        self.load(module).map_err(LoadError::Runtime)
+    }
+}
+
+/// Compiles `source` to a module that can call `natives`, stopping at the
+/// first stage that finds errors and returning them rendered as plain text.
+pub fn compile(name: &str, source: &str, natives: &Natives) -> Result<Module, Vec<String>> {
+    let mut sources = SourceMap::new();
+    let file = sources.add(name, source);
+    let render = |diagnostics: Vec<Diagnostic>| -> Result<(), Vec<String>> {
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            let renderer = Renderer::plain();
+            Err(diagnostics
+                .iter()
+                .map(|diagnostic| renderer.render(&sources, diagnostic))
+                .collect())
+        } else {
+            Ok(())
+        }
+    };
+
+    let (mut ast, errors) = Parser::with_file(source, file).file();
+    render(errors.iter().map(Diagnostic::from).collect())?;
+    render(desugar(&mut ast))?;
+    let (resolution, diagnostics) = resolve(&ast, natives);
+    render(diagnostics)?;
+    render(mutability::check(&ast, &resolution))?;
+    render(typeck::check(&ast, natives).1)?;
+    let (module, diagnostics) = codegen::compile_library(&ast, sources.get(file));
+    render(diagnostics)?;
+    Ok(module)
+}
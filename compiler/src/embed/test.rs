@@ -0,0 +1,101 @@
+use std::cell::Cell;
+
+use runtime::{GcConfig, Value, Vm};
+
+use super::{LoadError, LoadSource};
+
+const SCRIPT: &str = r#"
+const GREETING: Str = "hello"
+
+fn greet(name) -> {
+    log(GREETING + ", " + name);
+    name
+}
+
+fn total(prices) -> total_from(prices, 0)
+fn total_from(prices, i) ->
+    if (i == len(prices)) 0
+    else match prices[i] { (item, price) -> price * count(item) } + total_from(prices, i + 1)
+
+fn mean(values) -> sum(values) / len(values)
+
+fn checked(n) -> positive(n)
+"#;
+
+#[test]
+fn call_into_source_with_host_natives() {
+    let stressed = GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    };
+    for config in [GcConfig::default(), stressed] {
+        let logged = Cell::new(0);
+        let mut out = Vec::new();
+        let mut vm = Vm::builder()
+            .output(&mut out)
+            .gc(config)
+            .native("log", |message: Value| {
+                logged.set(logged.get() + 1);
+                message
+            })
+            .native("sum", |values: Vec<i64>| values.iter().sum::<i64>())
+            .native("count", |item: String| item.len() as i64)
+            .native("positive", |n: i64| {
+                if n > 0 {
+                    Ok(n)
+                } else {
+                    Err(format!("{n} isn't positive"))
+                }
+            })
+            .load_source("script.ptn", SCRIPT)
+            .unwrap();
+
+        let name: String = vm.call("greet", ("world",)).unwrap();
+        assert_eq!(name, "world");
+        let prices = vec![("ab".to_string(), 3), ("abc".to_string(), 5)];
+        assert_eq!(vm.call::<i64>("total", (prices,)).unwrap(), 21);
+        assert_eq!(vm.call::<i64>("mean", (vec![1, 2, 6],)).unwrap(), 3);
+        assert_eq!(vm.call::<i64>("checked", (4,)).unwrap(), 4);
+        let err = vm.call::<i64>("checked", (-1,)).unwrap_err();
+        assert_eq!(err.message, "-1 isn't positive");
+        assert_eq!(err.trace[0].function, "checked");
+        drop(vm);
+        assert_eq!(logged.get(), 1);
+        assert!(out.is_empty());
+    }
+}
+
+#[test]
+fn check_calls_to_host_natives() {
+    let load = |source: &str| {
+        Vm::builder()
+            .native("count", |item: String| item.len() as i64)
+            .load_source("script.ptn", source)
+    };
+
+    let Err(LoadError::Compile(errors)) = load("fn f() -> count(1)") else {
+        panic!("expected a type error");
+    };
+    assert_eq!(
+        errors,
+        [r#"error: mismatched types
+ --> script.ptn:1:17
+  |
+1 | fn f() -> count(1)
+  |                 ^ expected `Str`, found `Int`
+"#]
+    );
+
+    let Err(LoadError::Compile(errors)) = load("fn f() -> tally(\"a\")") else {
+        panic!("expected a resolution error");
+    };
+    assert!(
+        errors[0].contains("cannot find value `tally`"),
+        "{}",
+        errors[0]
+    );
+
+    // Items shadow natives, and the builtins are still there.
+    let mut vm = load("fn count(s) -> 0\nfn f() -> (count(\"abc\"), len(\"abc\"))").unwrap();
+    assert_eq!(vm.call::<(i64, i64)>("f", ()).unwrap(), (0, 3));
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+
+use super::{
+    Interpreter, RuntimeError,
+    value::{Builtin, BuiltinFn, Value},
+};
+use crate::span::Span;
+
+/// Functions that are always in scope, unless an item or variable shadows them.
+static BUILTINS: [BuiltinFn; 4] = [
+    BuiltinFn {
+        name: "print",
+        arity: 1,
+        call: |interpreter, args, span| interpreter.write(format_args!("{}", args[0]), span),
+    },
+    BuiltinFn {
+        name: "println",
+        arity: 1,
+        call: |interpreter, args, span| interpreter.write(format_args!("{}\n", args[0]), span),
+    },
+    BuiltinFn {
+        name: "len",
+        arity: 1,
+        call: |_, args, span| match &args[0] {
+            Value::Array(values) => Ok(Value::Int(values.len() as i64)),
+            Value::Str(string) => Ok(Value::Int(string.chars().count() as i64)),
+            value => Err(RuntimeError::new(
+                format!(
+                    "`len` expects an array or a string, found {}",
+                    value.type_name()
+                ),
+                span,
+            )),
+        },
+    },
+    BuiltinFn {
+        name: "to_string",
+        arity: 1,
+        call: |_, args, _| Ok(Value::Str(args[0].to_string().into())),
+    },
+];
+
+pub(super) fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Interpreter<'_> {
+    fn write(&mut self, args: std::fmt::Arguments, span: Span) -> Result<Value, RuntimeError> {
+        self.out
+            .write_fmt(args)
+            .map_err(|err| RuntimeError::new(format!("failed to write output: {err}"), span))?;
+        Ok(Value::unit())
+    }
+}
@@ -0,0 +1,52 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::value::Value;
+
+/// The local variables in scope at some point in the program.
+///
+/// This is a persistent list, so a closure can hold on to the scope it was
+/// created in while later `let`s in the same block extend a new one. The
+/// variables themselves are shared, so assignments are seen by every closure
+/// that captured them.
+#[derive(Debug, Clone, Default)]
+pub struct Env(Option<Rc<Variable>>);
+
+#[derive(Debug)]
+struct Variable {
+    name: String,
+    value: RefCell<Value>,
+    parent: Env,
+}
+
+impl Env {
+    /// A new scope with `name` bound to `value`, shadowing any outer `name`.
+    pub fn bind(&self, name: impl Into<String>, value: Value) -> Env {
+        Env(Some(Rc::new(Variable {
+            name: name.into(),
+            value: RefCell::new(value),
+            parent: self.clone(),
+        })))
+    }
+
+    fn find(&self, name: &str) -> Option<&Variable> {
+        let mut env = self;
+        while let Some(variable) = &env.0 {
+            if variable.name == name {
+                return Some(variable);
+            }
+            env = &variable.parent;
+        }
+        None
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.find(name)
+            .map(|variable| variable.value.borrow().clone())
+    }
+
+    /// Runs `f` on the innermost variable called `name`, if there is one.
+    pub fn with_mut<R>(&self, name: &str, f: impl FnOnce(&mut Value) -> R) -> Option<R> {
+        self.find(name)
+            .map(|variable| f(&mut variable.value.borrow_mut()))
+    }
+}
@@ -0,0 +1,904 @@
+//! A tree-walking interpreter over the desugared AST, for running programs
+//! before there's a real backend.
+
+mod builtins;
+mod env;
+#[cfg(test)]
+mod test;
+mod value;
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, hash_map::Entry},
+    error::Error,
+    fmt::Display,
+    io::{self, Write},
+    rc::Rc,
+    sync::Arc,
+};
+
+pub use value::{Function, Payload, Value};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{
+        Ast, Binding, Bop, Expr, ExprKind, FieldInit, FieldPattern, Item, ItemKind, Lit, MatchArm,
+        Pattern, PatternKind, StringPart, Unop, VariantKind, VariantPattern,
+    },
+    span::Span,
+};
+use env::Env;
+use value::Closure;
+
+/// How deep calls can nest before the program is stopped, rather than letting
+/// it overflow the interpreter's own stack.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    /// `None` if the error isn't about any place in the source, like a
+    /// missing `main`.
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span: Some(span),
+        }
+    }
+
+    fn without_span(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// The error as a diagnostic, if it has a place in the source to point at.
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        self.span
+            .map(|span| Diagnostic::error(self.message.clone(), span))
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for RuntimeError {}
+
+type RuntimeResult<T> = Result<T, RuntimeError>;
+
+enum Const<'a> {
+    Unevaluated(&'a Expr),
+    /// Being evaluated further up the stack, so needing it again is a cycle.
+    Evaluating,
+    Evaluated(Value),
+}
+
+/// Evaluates a desugared `Ast`.
+///
+/// Constants are evaluated the first time they're used, and everything a
+/// program prints goes to the writer the interpreter was created with.
+pub struct Interpreter<'a> {
+    items: HashMap<&'a str, &'a Item>,
+    consts: HashMap<&'a str, Const<'a>>,
+    out: Box<dyn Write + 'a>,
+    depth: usize,
+}
+
+/// The steps from a variable down to the part of it being assigned to.
+enum Projection {
+    Index(i64, Span),
+    Field(String, Span),
+}
+
+impl<'a> Interpreter<'a> {
+    /// Fails if two items share a name.
+    pub fn new(ast: &'a Ast, out: impl Write + 'a) -> RuntimeResult<Self> {
+        let mut items = HashMap::new();
+        let mut consts = HashMap::new();
+        for item in ast {
+            let name = match &item.kind {
+                ItemKind::Const { ident, value, .. } => {
+                    consts.insert(ident.as_str(), Const::Unevaluated(value));
+                    ident
+                }
+                ItemKind::Function { name, .. }
+                | ItemKind::Struct { name, .. }
+                | ItemKind::Enum { name, .. } => name,
+                ItemKind::Error => continue,
+            };
+
+            match items.entry(name.as_str()) {
+                Entry::Occupied(_) => {
+                    return Err(RuntimeError::new(
+                        format!("`{name}` is defined more than once"),
+                        item.span,
+                    ));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(item);
+                }
+            }
+        }
+
+        Ok(Self {
+            items,
+            consts,
+            out: Box::new(out),
+            depth: 0,
+        })
+    }
+
+    /// Interprets `ast`, printing to stdout.
+    pub fn run(ast: &Ast) -> RuntimeResult<Value> {
+        Interpreter::new(ast, io::stdout())?.run_main()
+    }
+
+    /// Calls the program's `fn main()` and returns what it evaluates to.
+    pub fn run_main(&mut self) -> RuntimeResult<Value> {
+        let Some(main) = self.items.get("main") else {
+            return Err(RuntimeError::without_span("no `main` function to run"));
+        };
+        match &main.kind {
+            ItemKind::Function { params, .. } if params.is_empty() => {}
+            _ => {
+                return Err(RuntimeError::new(
+                    "`main` must be a function without parameters",
+                    main.span,
+                ));
+            }
+        }
+
+        self.call(
+            Value::Function(Function::Item("main".into())),
+            Vec::new(),
+            main.span,
+        )
+    }
+
+    /// Calls the function item called `name`.
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> RuntimeResult<Value> {
+        let span = match self.items.get(name) {
+            Some(item) if matches!(item.kind, ItemKind::Function { .. }) => item.span,
+            _ => {
+                return Err(RuntimeError::without_span(format!(
+                    "no function called `{name}`"
+                )));
+            }
+        };
+        self.call(Value::Function(Function::Item(name.into())), args, span)
+    }
+
+    /// Gives the constant `name` its value, so that it isn't evaluated. Does
+    /// nothing if there's no such constant.
+    pub fn set_const(&mut self, name: &str, value: Value) {
+        if let Some(slot) = self.consts.get_mut(name) {
+            *slot = Const::Evaluated(value);
+        }
+    }
+
+    /// The constants that have been evaluated so far, with their values.
+    pub fn evaluated_consts(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.consts.iter().filter_map(|(&name, value)| match value {
+            Const::Evaluated(value) => Some((name, value)),
+            Const::Unevaluated(_) | Const::Evaluating => None,
+        })
+    }
+
+    // Every arm of any size lives in its own method, since `eval` recurses
+    // deeply and unoptimized builds give it a stack slot for every local.
+    fn eval(&mut self, expr: &Expr, env: &Env) -> RuntimeResult<Value> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(lit) => self.literal(lit, env, span),
+            ExprKind::Ident(name) => self.lookup(name, env, span),
+            ExprKind::Path { enum_name, variant } => self.path(enum_name, variant, span),
+            ExprKind::FnCall { fun, args } => self.fn_call(fun, args, env, span),
+            ExprKind::BinaryOp { op, lhs, rhs } => self.binary_op(*op, lhs, rhs, env, span),
+            ExprKind::UnaryOp { op, expr } => self.unary_op(*op, expr, env, span),
+            ExprKind::Index { arr, index } => self.index(arr, index, env, span),
+            ExprKind::FieldAccess { base, field } => {
+                let mut base = self.eval(base, env)?;
+                Ok(field_mut(&mut base, field, span)?.clone())
+            }
+            ExprKind::If { cond, th, el } => {
+                if self.bool(cond, env)? {
+                    self.eval(th, env)
+                } else if let Some(el) = el {
+                    self.eval(el, env)
+                } else {
+                    Ok(Value::unit())
+                }
+            }
+            // Outside of a block, the new variable immediately goes out of scope.
+            ExprKind::Let { value, .. } => {
+                self.eval(value, env)?;
+                Ok(Value::unit())
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                Ok(Value::Function(Function::Closure(Rc::new(Closure {
+                    params: Arc::clone(params),
+                    body: Arc::clone(body),
+                    env: env.clone(),
+                }))))
+            }
+            ExprKind::Block { exprs, trailing } => self.block(exprs, *trailing, env),
+            ExprKind::Match { scrutinee, arms } => self.match_(scrutinee, arms, env),
+            ExprKind::Interpolated(parts) => self.interpolated(parts, env),
+            ExprKind::Placeholder => unreachable!("removed by desugaring"),
+            ExprKind::Error => Err(RuntimeError::new(
+                "this expression couldn't be parsed",
+                span,
+            )),
+        }
+    }
+
+    fn fn_call(
+        &mut self,
+        fun: &Expr,
+        args: &[Expr],
+        env: &Env,
+        span: Span,
+    ) -> RuntimeResult<Value> {
+        let fun = self.eval(fun, env)?;
+        let args = args
+            .iter()
+            .map(|arg| self.eval(arg, env))
+            .collect::<RuntimeResult<_>>()?;
+        self.call(fun, args, span)
+    }
+
+    fn binary_op(
+        &mut self,
+        op: Bop,
+        lhs: &Expr,
+        rhs: &Expr,
+        env: &Env,
+        span: Span,
+    ) -> RuntimeResult<Value> {
+        match op {
+            Bop::Assign => {
+                let value = self.eval(rhs, env)?;
+                self.assign(lhs, value, env)?;
+                Ok(Value::unit())
+            }
+            Bop::And | Bop::Or => {
+                let lhs = self.bool(lhs, env)?;
+                if lhs == (op == Bop::Or) {
+                    Ok(Value::Bool(lhs))
+                } else {
+                    Ok(Value::Bool(self.bool(rhs, env)?))
+                }
+            }
+            _ => {
+                let lhs = self.eval(lhs, env)?;
+                let rhs = self.eval(rhs, env)?;
+                binary_op(op, lhs, rhs, span)
+            }
+        }
+    }
+
+    fn unary_op(&mut self, op: Unop, expr: &Expr, env: &Env, span: Span) -> RuntimeResult<Value> {
+        Ok(match (op, self.eval(expr, env)?) {
+            (Unop::Neg, Value::Int(int)) => Value::Int(
+                int.checked_neg()
+                    .ok_or_else(|| RuntimeError::new("integer overflow", span))?,
+            ),
+            (Unop::Neg, Value::Float(float)) => Value::Float(-float),
+            (Unop::Not, Value::Bool(bool)) => Value::Bool(!bool),
+            (Unop::Not, Value::Int(int)) => Value::Int(!int),
+            (op, value) => {
+                return Err(RuntimeError::new(
+                    format!("cannot apply `{op}` to {}", value.type_name()),
+                    span,
+                ));
+            }
+        })
+    }
+
+    fn index(&mut self, arr: &Expr, index: &Expr, env: &Env, span: Span) -> RuntimeResult<Value> {
+        match (self.eval(arr, env)?, self.eval(index, env)?) {
+            (Value::Array(mut values), Value::Int(index)) => {
+                let i = array_index(values.len(), index, span)?;
+                Ok(values.swap_remove(i))
+            }
+            (arr, index) => Err(RuntimeError::new(
+                format!(
+                    "cannot index {} with {}",
+                    arr.type_name(),
+                    index.type_name()
+                ),
+                span,
+            )),
+        }
+    }
+
+    /// Concatenates the parts of an interpolated string, showing each value
+    /// the way `to_string` does.
+    fn interpolated(&mut self, parts: &[StringPart], env: &Env) -> RuntimeResult<Value> {
+        let mut string = String::new();
+        for part in parts {
+            match part {
+                StringPart::Text(text) => string.push_str(text),
+                StringPart::Expr(expr) => string.push_str(&self.eval(expr, env)?.to_string()),
+            }
+        }
+        Ok(Value::Str(string.into()))
+    }
+
+    fn block(&mut self, exprs: &[Expr], trailing: bool, env: &Env) -> RuntimeResult<Value> {
+        let mut env = env.clone();
+        let mut value = Value::unit();
+        for expr in exprs {
+            value = match &expr.kind {
+                ExprKind::Let { binding, value } => {
+                    let value = self.eval(value, &env)?;
+                    env = env.bind(&binding.name, value);
+                    Value::unit()
+                }
+                _ => self.eval(expr, &env)?,
+            };
+        }
+        Ok(if trailing { value } else { Value::unit() })
+    }
+
+    fn match_(&mut self, scrutinee: &Expr, arms: &[MatchArm], env: &Env) -> RuntimeResult<Value> {
+        let value = self.eval(scrutinee, env)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if self.matches(&arm.pattern, &value, &mut bindings) {
+                let env = bindings
+                    .into_iter()
+                    .fold(env.clone(), |env, (name, value)| env.bind(name, value));
+                return self.eval(&arm.body, &env);
+            }
+        }
+        Err(RuntimeError::new(
+            format!("no match arm matches `{value}`"),
+            scrutinee.span,
+        ))
+    }
+
+    fn bool(&mut self, expr: &Expr, env: &Env) -> RuntimeResult<bool> {
+        match self.eval(expr, env)? {
+            Value::Bool(bool) => Ok(bool),
+            value => Err(RuntimeError::new(
+                format!("expected Bool, found {}", value.type_name()),
+                expr.span,
+            )),
+        }
+    }
+
+    fn literal(&mut self, lit: &Lit, env: &Env, span: Span) -> RuntimeResult<Value> {
+        let mut all = |exprs: &[Expr]| {
+            exprs
+                .iter()
+                .map(|expr| self.eval(expr, env))
+                .collect::<RuntimeResult<Vec<_>>>()
+        };
+
+        Ok(match lit {
+            Lit::Int(int) => Value::Int(*int),
+            Lit::Float(float) => Value::Float(*float),
+            Lit::Str(string) => Value::Str(string.as_str().into()),
+            Lit::Char(char) => Value::Char(*char),
+            Lit::Bool(bool) => Value::Bool(*bool),
+            Lit::Array(exprs) => Value::Array(all(exprs)?),
+            Lit::Tuple(exprs) => Value::Tuple(all(exprs)?),
+            Lit::Struct {
+                name,
+                variant: None,
+                fields,
+            } => {
+                let definition = match self.items.get(name.as_str()).map(|item| &item.kind) {
+                    Some(ItemKind::Struct { fields, .. }) => fields,
+                    _ => {
+                        return Err(RuntimeError::new(
+                            format!("cannot find struct `{name}`"),
+                            span,
+                        ));
+                    }
+                };
+                let names = definition.iter().map(|field| field.name.as_str());
+                let fields = self.field_inits(name, names.collect(), fields, env, span)?;
+                Value::Struct {
+                    name: name.as_str().into(),
+                    fields,
+                }
+            }
+            Lit::Struct {
+                name,
+                variant: Some(variant),
+                fields,
+            } => {
+                let definition = match self.variant(name, variant, span)? {
+                    VariantKind::Struct(_, fields) => fields,
+                    _ => {
+                        return Err(RuntimeError::new(
+                            format!("`{name}::{variant}` is not a struct variant"),
+                            span,
+                        ));
+                    }
+                };
+                let names = definition.iter().map(|field| field.name.as_str());
+                let fields = self.field_inits(
+                    &format!("{name}::{variant}"),
+                    names.collect(),
+                    fields,
+                    env,
+                    span,
+                )?;
+                Value::Variant {
+                    enum_name: name.as_str().into(),
+                    variant: variant.as_str().into(),
+                    payload: Payload::Struct(fields),
+                }
+            }
+        })
+    }
+
+    /// Evaluates the fields of a struct literal, in the order they're declared.
+    fn field_inits(
+        &mut self,
+        struct_name: &str,
+        names: Vec<&str>,
+        inits: &[FieldInit],
+        env: &Env,
+        span: Span,
+    ) -> RuntimeResult<Vec<(String, Value)>> {
+        let mut values: Vec<Option<Value>> = vec![None; names.len()];
+        for init in inits {
+            let Some(i) = names.iter().position(|name| *name == init.name) else {
+                return Err(RuntimeError::new(
+                    format!("`{struct_name}` has no field named `{}`", init.name),
+                    init.span,
+                ));
+            };
+            assert!(
+                values[i].is_none(),
+                "duplicate fields are rejected by type checking"
+            );
+            values[i] = Some(self.eval(&init.value, env)?);
+        }
+
+        names
+            .into_iter()
+            .zip(values)
+            .map(|(name, value)| match value {
+                Some(value) => Ok((name.to_string(), value)),
+                None => Err(RuntimeError::new(
+                    format!("missing field `{name}` in `{struct_name}`"),
+                    span,
+                )),
+            })
+            .collect()
+    }
+
+    fn variant(
+        &self,
+        enum_name: &str,
+        variant: &str,
+        span: Span,
+    ) -> RuntimeResult<&'a VariantKind> {
+        let Some(ItemKind::Enum { variants, .. }) =
+            self.items.get(enum_name).map(|item| &item.kind)
+        else {
+            return Err(RuntimeError::new(
+                format!("cannot find enum `{enum_name}`"),
+                span,
+            ));
+        };
+
+        variants
+            .iter()
+            .map(|v| &v.kind)
+            .find(|kind| match kind {
+                VariantKind::Unit(name)
+                | VariantKind::Tuple(name, _)
+                | VariantKind::Struct(name, _) => name == variant,
+            })
+            .ok_or_else(|| {
+                RuntimeError::new(format!("`{enum_name}` has no variant `{variant}`"), span)
+            })
+    }
+
+    fn path(&self, enum_name: &str, variant: &str, span: Span) -> RuntimeResult<Value> {
+        Ok(match self.variant(enum_name, variant, span)? {
+            VariantKind::Unit(_) => Value::Variant {
+                enum_name: enum_name.into(),
+                variant: variant.into(),
+                payload: Payload::Unit,
+            },
+            VariantKind::Tuple(_, types) => Value::Function(Function::Constructor {
+                enum_name: enum_name.into(),
+                variant: variant.into(),
+                arity: types.len(),
+            }),
+            VariantKind::Struct(..) => {
+                return Err(RuntimeError::new(
+                    format!("`{enum_name}::{variant}` is a struct variant, so it needs fields"),
+                    span,
+                ));
+            }
+        })
+    }
+
+    /// Looks `name` up as a local variable, then an item, then a builtin.
+    fn lookup(&mut self, name: &str, env: &Env, span: Span) -> RuntimeResult<Value> {
+        if let Some(value) = env.get(name) {
+            return Ok(value);
+        }
+
+        let Some((&name, item)) = self.items.get_key_value(name) else {
+            return builtins::lookup(name)
+                .map(|builtin| Value::Function(Function::Builtin(builtin)))
+                .ok_or_else(|| RuntimeError::new(format!("cannot find value `{name}`"), span));
+        };
+
+        match &item.kind {
+            ItemKind::Function { .. } => Ok(Value::Function(Function::Item(name.into()))),
+            ItemKind::Const { .. } => self.constant(name, span),
+            _ => Err(RuntimeError::new(
+                format!("`{name}` is a type, not a value"),
+                span,
+            )),
+        }
+    }
+
+    fn constant(&mut self, name: &'a str, span: Span) -> RuntimeResult<Value> {
+        let state = self
+            .consts
+            .insert(name, Const::Evaluating)
+            .expect("every const item has a state");
+
+        let value = match state {
+            Const::Evaluated(value) => value,
+            Const::Unevaluated(expr) => {
+                // Consts can't see the locals of whoever uses them first.
+                let value = self.eval(expr, &Env::default());
+                if value.is_err() {
+                    self.consts.insert(name, Const::Unevaluated(expr));
+                }
+                value?
+            }
+            Const::Evaluating => {
+                return Err(RuntimeError::new(
+                    format!("`{name}` depends on its own value"),
+                    span,
+                ));
+            }
+        };
+
+        self.consts.insert(name, Const::Evaluated(value.clone()));
+        Ok(value)
+    }
+
+    fn call(&mut self, fun: Value, args: Vec<Value>, span: Span) -> RuntimeResult<Value> {
+        let Value::Function(fun) = fun else {
+            return Err(RuntimeError::new(
+                format!("{} is not a function", fun.type_name()),
+                span,
+            ));
+        };
+
+        let arity = match &fun {
+            Function::Item(name) => match &self.items[&**name].kind {
+                ItemKind::Function { params, .. } => params.len(),
+                _ => unreachable!("only function items are called by name"),
+            },
+            Function::Closure(closure) => closure.params.len(),
+            Function::Constructor { arity, .. } => *arity,
+            Function::Builtin(builtin) => builtin.arity,
+        };
+        if args.len() != arity {
+            return Err(RuntimeError::new(
+                format!(
+                    "`{}` takes {arity} argument{}, but {} {} supplied",
+                    Value::Function(fun.clone()),
+                    if arity == 1 { "" } else { "s" },
+                    args.len(),
+                    if args.len() == 1 { "was" } else { "were" },
+                ),
+                span,
+            ));
+        }
+
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(
+                format!("stack overflow: calls nested more than {MAX_CALL_DEPTH} deep"),
+                span,
+            ));
+        }
+        self.depth += 1;
+        let result = match fun {
+            Function::Item(name) => {
+                let item = self.items[&*name];
+                let ItemKind::Function { params, body, .. } = &item.kind else {
+                    unreachable!()
+                };
+                let env = bind_params(&Env::default(), params, args);
+                self.eval(body, &env)
+            }
+            Function::Closure(closure) => {
+                let env = bind_params(&closure.env, &closure.params, args);
+                self.eval(&closure.body, &env)
+            }
+            Function::Constructor {
+                enum_name, variant, ..
+            } => Ok(Value::Variant {
+                enum_name,
+                variant,
+                payload: Payload::Tuple(args),
+            }),
+            Function::Builtin(builtin) => (builtin.call)(self, args, span),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    /// Stores `value` in the variable, element or field that `place` refers to.
+    fn assign(&mut self, place: &Expr, value: Value, env: &Env) -> RuntimeResult<()> {
+        let mut projections = Vec::new();
+        let mut root = place;
+        loop {
+            match &root.kind {
+                ExprKind::Index { arr, index } => {
+                    let Value::Int(index) = self.eval(index, env)? else {
+                        return Err(RuntimeError::new("arrays are indexed by Int", index.span));
+                    };
+                    projections.push(Projection::Index(index, root.span));
+                    root = arr;
+                }
+                ExprKind::FieldAccess { base, field } => {
+                    projections.push(Projection::Field(field.clone(), root.span));
+                    root = base;
+                }
+                _ => break,
+            }
+        }
+
+        let ExprKind::Ident(name) = &root.kind else {
+            return Err(RuntimeError::new(
+                "can only assign to variables, or elements and fields of them",
+                place.span,
+            ));
+        };
+
+        env.with_mut(name, |mut target| {
+            for projection in projections.into_iter().rev() {
+                target = match (projection, target) {
+                    (Projection::Index(index, span), Value::Array(values)) => {
+                        let i = array_index(values.len(), index, span)?;
+                        &mut values[i]
+                    }
+                    (Projection::Index(_, span), value) => {
+                        return Err(RuntimeError::new(
+                            format!("cannot index {}", value.type_name()),
+                            span,
+                        ));
+                    }
+                    (Projection::Field(field, span), value) => field_mut(value, &field, span)?,
+                };
+            }
+            *target = value;
+            Ok(())
+        })
+        .unwrap_or_else(|| {
+            Err(RuntimeError::new(
+                format!("cannot assign to `{name}`, it isn't a local variable"),
+                root.span,
+            ))
+        })
+    }
+
+    /// Checks `value` against `pattern`, collecting the variables it binds.
+    fn matches(
+        &self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> bool {
+        let mut all = |patterns: &[Pattern], values: &[Value]| {
+            patterns.len() == values.len()
+                && patterns
+                    .iter()
+                    .zip(values)
+                    .all(|(pattern, value)| self.matches(pattern, value, bindings))
+        };
+
+        match (&pattern.kind, value) {
+            (PatternKind::Wildcard, _) => true,
+            (PatternKind::Binding { name, .. }, value) => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            (PatternKind::Literal(lit), value) => {
+                let lit = match lit {
+                    Lit::Int(int) => Value::Int(*int),
+                    Lit::Float(float) => Value::Float(*float),
+                    Lit::Str(string) => Value::Str(string.as_str().into()),
+                    Lit::Char(char) => Value::Char(*char),
+                    Lit::Bool(bool) => Value::Bool(*bool),
+                    _ => unreachable!("literal patterns are scalars"),
+                };
+                lit.equals(value) == Some(true)
+            }
+            (PatternKind::Tuple(patterns), Value::Tuple(values))
+            | (PatternKind::Array(patterns), Value::Array(values)) => all(patterns, values),
+            (
+                PatternKind::Variant {
+                    enum_name,
+                    variant: pattern,
+                },
+                Value::Variant {
+                    enum_name: value_enum,
+                    variant,
+                    payload,
+                },
+            ) => {
+                if **enum_name != **value_enum {
+                    return false;
+                }
+                match (pattern, payload) {
+                    (VariantPattern::Unit(name), Payload::Unit) => **name == **variant,
+                    (VariantPattern::Tuple(name, patterns), Payload::Tuple(values)) => {
+                        **name == **variant && all(patterns, values)
+                    }
+                    (VariantPattern::Struct(name, patterns), Payload::Struct(fields)) => {
+                        **name == **variant && self.field_patterns(patterns, fields, bindings)
+                    }
+                    _ => false,
+                }
+            }
+            (
+                PatternKind::Struct {
+                    name,
+                    fields: patterns,
+                },
+                Value::Struct {
+                    name: value_name,
+                    fields,
+                },
+            ) => **name == **value_name && self.field_patterns(patterns, fields, bindings),
+            _ => false,
+        }
+    }
+
+    fn field_patterns(
+        &self,
+        patterns: &[FieldPattern],
+        fields: &[(String, Value)],
+        bindings: &mut Vec<(String, Value)>,
+    ) -> bool {
+        patterns.iter().all(|pattern| {
+            fields
+                .iter()
+                .find(|(name, _)| *name == pattern.name)
+                .is_some_and(|(_, value)| self.matches(&pattern.pattern, value, bindings))
+        })
+    }
+}
+
+fn bind_params(env: &Env, params: &[Binding], args: Vec<Value>) -> Env {
+    params
+        .iter()
+        .zip(args)
+        .fold(env.clone(), |env, (param, arg)| env.bind(&param.name, arg))
+}
+
+/// Checks that `index` is in bounds for an array of `len` elements.
+fn array_index(len: usize, index: i64, span: Span) -> RuntimeResult<usize> {
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < len)
+        .ok_or_else(|| {
+            RuntimeError::new(
+                format!("index {index} is out of bounds for an array of length {len}"),
+                span,
+            )
+        })
+}
+
+/// A named struct field, or a numbered tuple field like `_0`.
+fn field_mut<'v>(value: &'v mut Value, field: &str, span: Span) -> RuntimeResult<&'v mut Value> {
+    let type_name = value.type_name();
+    let found = match value {
+        Value::Struct { fields, .. } => fields
+            .iter_mut()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value),
+        Value::Tuple(values) => field
+            .strip_prefix('_')
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| values.get_mut(index)),
+        _ => None,
+    };
+    found.ok_or_else(|| RuntimeError::new(format!("{type_name} has no field `{field}`"), span))
+}
+
+fn binary_op(op: Bop, lhs: Value, rhs: Value, span: Span) -> RuntimeResult<Value> {
+    let overflow = || RuntimeError::new("integer overflow", span);
+
+    Ok(match (op, &lhs, &rhs) {
+        (Bop::Add, Value::Int(lhs), Value::Int(rhs)) => {
+            Value::Int(lhs.checked_add(*rhs).ok_or_else(overflow)?)
+        }
+        (Bop::Sub, Value::Int(lhs), Value::Int(rhs)) => {
+            Value::Int(lhs.checked_sub(*rhs).ok_or_else(overflow)?)
+        }
+        (Bop::Mul, Value::Int(lhs), Value::Int(rhs)) => {
+            Value::Int(lhs.checked_mul(*rhs).ok_or_else(overflow)?)
+        }
+        (Bop::Div, Value::Int(_), Value::Int(0)) => {
+            return Err(RuntimeError::new("division by zero", span));
+        }
+        (Bop::Div, Value::Int(lhs), Value::Int(rhs)) => {
+            Value::Int(lhs.checked_div(*rhs).ok_or_else(overflow)?)
+        }
+        (Bop::Exp, Value::Int(lhs), Value::Int(rhs)) => {
+            let Ok(rhs) = u32::try_from(*rhs) else {
+                return Err(if *rhs < 0 {
+                    RuntimeError::new("cannot raise an Int to a negative power", span)
+                } else {
+                    overflow()
+                });
+            };
+            Value::Int(lhs.checked_pow(rhs).ok_or_else(overflow)?)
+        }
+        (Bop::Add, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs + rhs),
+        (Bop::Sub, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs - rhs),
+        (Bop::Mul, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs * rhs),
+        (Bop::Div, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs / rhs),
+        (Bop::Exp, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs.powf(*rhs)),
+        (Bop::Add, Value::Str(lhs), Value::Str(rhs)) => Value::Str(format!("{lhs}{rhs}").into()),
+        (Bop::Xor, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs ^ rhs),
+        (Bop::BOr, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs | rhs),
+        (Bop::BAnd, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs & rhs),
+        (Bop::Xor, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs ^ rhs),
+        (Bop::BOr, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs | rhs),
+        (Bop::BAnd, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs & rhs),
+        (Bop::Eqq | Bop::Neq, lhs, rhs) => match lhs.equals(rhs) {
+            Some(equal) => Value::Bool(equal == (op == Bop::Eqq)),
+            None => return Err(RuntimeError::new("functions can't be compared", span)),
+        },
+        (Bop::Lt | Bop::Gt | Bop::Leq | Bop::Geq, lhs, rhs) => {
+            let ordering = match (lhs, rhs) {
+                (Value::Int(lhs), Value::Int(rhs)) => lhs.partial_cmp(rhs),
+                (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+                (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(rhs),
+                (Value::Str(lhs), Value::Str(rhs)) => lhs.partial_cmp(rhs),
+                _ => return Err(mismatched(op, lhs, rhs, span)),
+            };
+            Value::Bool(matches!(
+                (op, ordering),
+                (Bop::Lt, Some(Ordering::Less))
+                    | (Bop::Gt, Some(Ordering::Greater))
+                    | (Bop::Leq, Some(Ordering::Less | Ordering::Equal))
+                    | (Bop::Geq, Some(Ordering::Greater | Ordering::Equal))
+            ))
+        }
+        (Bop::Assign | Bop::And | Bop::Or, ..) => unreachable!("evaluated lazily"),
+        (Bop::Pipeline | Bop::ComposeRight | Bop::ComposeLeft, ..) => {
+            unreachable!("removed by desugaring")
+        }
+        _ => return Err(mismatched(op, &lhs, &rhs, span)),
+    })
+}
+
+fn mismatched(op: Bop, lhs: &Value, rhs: &Value, span: Span) -> RuntimeError {
+    RuntimeError::new(
+        format!(
+            "cannot apply `{op}` to {} and {}",
+            lhs.type_name(),
+            rhs.type_name()
+        ),
+        span,
+    )
+}
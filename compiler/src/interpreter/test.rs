@@ -0,0 +1,262 @@
+use super::{Interpreter, Value};
+use crate::{desugar::desugar, parser::Parser};
+
+/// Runs `main` in `input`, returning what it evaluated to (or the runtime
+/// error message) along with everything it printed.
+fn run(input: &str) -> (Result<Value, String>, String) {
+    let (mut ast, errors) = Parser::new(input).file();
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(desugar(&mut ast).is_empty());
+
+    let mut out = Vec::new();
+    let result = Interpreter::new(&ast, &mut out)
+        .and_then(|mut interpreter| interpreter.run_main())
+        .map_err(|err| err.message);
+    (result, String::from_utf8(out).unwrap())
+}
+
+fn eval(input: &str) -> String {
+    match run(input) {
+        (Ok(value), _) => value.to_string(),
+        (Err(err), _) => panic!("{err}"),
+    }
+}
+
+fn error(input: &str) -> String {
+    match run(input) {
+        (Ok(value), _) => panic!("expected an error, got {value}"),
+        (Err(err), _) => err,
+    }
+}
+
+#[test]
+fn arithmetic_and_logic() {
+    assert_eq!(eval("fn main() -> 1 + 2 * 3 - 8 / 2"), "3");
+    assert_eq!(eval("fn main() -> 2 ** 3 ** 2"), "512");
+    assert_eq!(eval("fn main() -> 1.5 * 2."), "3.0");
+    assert_eq!(eval(r#"fn main() -> "con" + "cat""#), "concat");
+    assert_eq!(
+        eval("fn main() -> (6 & 3, 6 | 3, 6 ^ 3, -5, !true)"),
+        "(2, 7, 5, -5, false)"
+    );
+    assert_eq!(
+        eval("fn main() -> 1 < 2 && 'a' >= 'b' || \"a\" < \"b\""),
+        "true"
+    );
+    assert_eq!(eval("fn main() -> [(1, 'x')] == [(1, 'x')]"), "true");
+
+    // The right side isn't evaluated once the result is known.
+    assert_eq!(eval("fn main() -> false && 1 / 0 == 0"), "false");
+}
+
+#[test]
+fn functions_and_closures() {
+    assert_eq!(
+        eval(
+            "
+            fn fib(n) -> if (n < 2) n else fib(n - 1) + fib(n - 2)
+            fn main() -> fib(15)
+        "
+        ),
+        "610"
+    );
+
+    // Closures see later assignments to the variables they capture, but not
+    // variables shadowed after they were created.
+    assert_eq!(
+        eval(
+            "
+            fn main() -> {
+                let mut n = 1;
+                let get = |_unused| -> n;
+                let add = |by| -> n = n + by;
+                add(10);
+                let n = 100;
+                (get(0), n)
+            }
+        "
+        ),
+        "(11, 100)"
+    );
+
+    assert_eq!(
+        eval(
+            "
+            fn twice(f) -> |x| -> f(f(x))
+            const inc: fn(Int): Int = add(1, _)
+            fn add(a, b) -> a + b
+            fn main() -> (twice(inc)(0), 1 |> inc >> twice(inc))
+        "
+        ),
+        "(2, 4)"
+    );
+
+    // Operands of `|>`, `>>` and `<<` are evaluated once, left to right.
+    let (result, out) = run(r#"
+        fn id(x) -> x
+        fn counter() -> { let mut n = 0; |by| -> { n = n + by; n } }
+        fn noisy(f) -> { print("noisy "); f }
+        fn lhs() -> { print("lhs "); 1 }
+        fn rhs() -> { print("rhs "); id }
+        fn main() -> {
+            let up = counter() >> id;
+            let down = id << noisy(counter());
+            (up(1), up(1), up(1), down(2), down(2), lhs() |> rhs())
+        }
+    "#);
+    assert_eq!(result.unwrap().to_string(), "(1, 2, 3, 2, 4, 1)");
+    assert_eq!(out, "noisy lhs rhs ");
+
+    // So are the arguments of a partial application.
+    let (result, out) = run(r#"
+        fn sum(a, b) -> a + b
+        fn noisy(n) -> { print("noisy "); n }
+        fn main() -> {
+            let add = sum(noisy(1), _);
+            (add(1), add(2))
+        }
+    "#);
+    assert_eq!(result.unwrap().to_string(), "(2, 3)");
+    assert_eq!(out, "noisy ");
+}
+
+#[test]
+fn structs_enums_and_match() {
+    let source = r#"
+        struct Point { x: Int, y: Int }
+        enum Shape {
+            Circle(Float),
+            Rect { w: Int, h: Int },
+            Empty,
+        }
+
+        fn describe(shape) -> match shape {
+            Shape::Circle(r) -> "circle",
+            Shape::Rect { w, h: 1 } -> "line",
+            Shape::Rect { w, h } -> to_string(w * h),
+            Shape::Empty -> "empty",
+        }
+
+        fn main() -> {
+            let mut p = Point { y: 2, x: 1 };
+            p.x = 10;
+            let shapes = [Shape::Circle(1.), Shape::Rect { w: 4, h: 1 }, Shape::Rect { w: 2, h: 3 }];
+            (p, describe(shapes[0]), describe(shapes[1]), describe(shapes[2]), describe(Shape::Empty))
+        }
+    "#;
+    assert_eq!(
+        eval(source),
+        r#"(Point { x: 10, y: 2 }, "circle", "line", "6", "empty")"#
+    );
+
+    assert_eq!(
+        eval("fn main() -> match (1, [2, 3]) { (a, [_, b]) -> a + b, _ -> 0 }"),
+        "4"
+    );
+}
+
+#[test]
+fn assignment_through_places() {
+    assert_eq!(
+        eval(
+            "
+            struct Grid { rows: [[Int]] }
+            fn main() -> {
+                let mut grid = Grid { rows: [[0, 0], [0, 0]] };
+                grid.rows[1][0] = 5;
+                let mut pair = (1, 2);
+                pair._1 = 3;
+                (grid.rows, pair)
+            }
+        "
+        ),
+        "([[0, 0], [5, 0]], (1, 3))"
+    );
+}
+
+#[test]
+fn consts_and_output() {
+    let (result, out) = run(r#"
+        const greeting: Str = "hello " + name
+        const name: Str = "world"
+        fn main() -> {
+            println(greeting);
+            print(len([1, 2, 3]));
+        }
+    "#);
+    assert!(result.is_ok());
+    assert_eq!(out, "hello world\n3");
+}
+
+#[test]
+fn runtime_errors() {
+    assert_eq!(
+        error("fn main() -> [1, 2][2]"),
+        "index 2 is out of bounds for an array of length 2"
+    );
+    assert_eq!(error("fn main() -> 1 / 0"), "division by zero");
+    assert_eq!(
+        error("fn main() -> 9223372036854775807 + 1"),
+        "integer overflow"
+    );
+    assert_eq!(
+        error("fn main() -> 1 + 1.0"),
+        "cannot apply `+` to Int and Float"
+    );
+    assert_eq!(
+        error("fn main() -> undefined"),
+        "cannot find value `undefined`"
+    );
+    assert_eq!(
+        error("fn f(a) -> a fn main() -> f(1, 2)"),
+        "`<fn f>` takes 1 argument, but 2 were supplied"
+    );
+    assert_eq!(
+        error("fn f(a, b) -> a fn main() -> f(1)"),
+        "`<fn f>` takes 2 arguments, but 1 was supplied"
+    );
+    assert_eq!(
+        error("fn main() -> match 3 { 1 -> 1 }"),
+        "no match arm matches `3`"
+    );
+    assert_eq!(
+        error("const a: Int = b const b: Int = a fn main() -> a"),
+        "`a` depends on its own value"
+    );
+    assert_eq!(
+        error("struct P { x: Int } fn main() -> P { y: 1 }"),
+        "`P` has no field named `y`"
+    );
+    assert_eq!(error("fn f() -> 1"), "no `main` function to run");
+    // Which isn't about any place in the source.
+    let (ast, _) = Parser::new("fn f() -> 1").file();
+    let err = Interpreter::new(&ast, Vec::new())
+        .and_then(|mut interpreter| interpreter.run_main())
+        .unwrap_err();
+    assert_eq!(err.span, None);
+    assert_eq!(
+        error("fn main() -> 1 fn main() -> 2"),
+        "`main` is defined more than once"
+    );
+
+    // The parser leaves an error node in place of what it couldn't parse.
+    let (ast, errors) = Parser::new(r#"fn main() -> "\q""#).file();
+    assert!(!errors.is_empty());
+    let err = Interpreter::new(&ast, Vec::new())
+        .and_then(|mut interpreter| interpreter.run_main())
+        .unwrap_err();
+    assert_eq!(err.message, "this expression couldn't be parsed");
+    let span = err.span.unwrap();
+    assert_eq!((span.start, span.end), (13, 17));
+}
+
+#[test]
+fn deep_recursion_is_an_error() {
+    let result = std::thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(|| error("fn count(n) -> 1 + count(n) fn main() -> count(0)"))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(result, "stack overflow: calls nested more than 10000 deep");
+}
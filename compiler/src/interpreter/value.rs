@@ -0,0 +1,257 @@
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+    sync::Arc,
+};
+
+use super::{Interpreter, RuntimeError, env::Env};
+use crate::{
+    parser::ast::{Binding, Expr},
+    span::Span,
+};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(Rc<str>),
+    Char(char),
+    Bool(bool),
+    Array(Vec<Value>),
+    /// The empty tuple doubles as the unit value.
+    Tuple(Vec<Value>),
+    Struct {
+        name: Rc<str>,
+        fields: Vec<(String, Value)>,
+    },
+    Variant {
+        enum_name: Rc<str>,
+        variant: Rc<str>,
+        payload: Payload,
+    },
+    Function(Function),
+}
+
+/// The data carried by an enum variant, mirroring `VariantKind`.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Unit,
+    Tuple(Vec<Value>),
+    Struct(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Function {
+    /// A `fn` item, looked up by name when called.
+    Item(Rc<str>),
+    Closure(Rc<Closure>),
+    /// A tuple variant used as a function, like `Option::Some`.
+    Constructor {
+        enum_name: Rc<str>,
+        variant: Rc<str>,
+        arity: usize,
+    },
+    Builtin(Builtin),
+}
+
+/// A lambda along with the variables in scope where it was created.
+#[derive(Debug)]
+pub struct Closure {
+    pub params: Arc<[Binding]>,
+    pub body: Arc<Expr>,
+    pub env: Env,
+}
+
+pub type Builtin = &'static BuiltinFn;
+
+pub struct BuiltinFn {
+    pub name: &'static str,
+    pub arity: usize,
+    pub call: fn(&mut Interpreter, Vec<Value>, Span) -> Result<Value, RuntimeError>,
+}
+
+impl fmt::Debug for BuiltinFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<builtin {}>", self.name)
+    }
+}
+
+impl Value {
+    pub fn unit() -> Self {
+        Value::Tuple(Vec::new())
+    }
+
+    /// The name of the value's type, for error messages.
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Int(_) => "Int".into(),
+            Value::Float(_) => "Float".into(),
+            Value::Str(_) => "Str".into(),
+            Value::Char(_) => "Char".into(),
+            Value::Bool(_) => "Bool".into(),
+            Value::Array(_) => "array".into(),
+            Value::Tuple(values) if values.is_empty() => "()".into(),
+            Value::Tuple(_) => "tuple".into(),
+            Value::Struct { name, .. } => name.to_string(),
+            Value::Variant { enum_name, .. } => enum_name.to_string(),
+            Value::Function(_) => "function".into(),
+        }
+    }
+
+    /// Structural equality. Returns `None` if a function is compared, since
+    /// those have no meaningful notion of equality.
+    pub fn equals(&self, other: &Value) -> Option<bool> {
+        fn all(lhs: &[Value], rhs: &[Value]) -> Option<bool> {
+            if lhs.len() != rhs.len() {
+                return Some(false);
+            }
+            for (lhs, rhs) in lhs.iter().zip(rhs) {
+                if !lhs.equals(rhs)? {
+                    return Some(false);
+                }
+            }
+            Some(true)
+        }
+
+        fn fields(lhs: &[(String, Value)], rhs: &[(String, Value)]) -> Option<bool> {
+            if lhs.len() != rhs.len() {
+                return Some(false);
+            }
+            for ((lhs_name, lhs), (rhs_name, rhs)) in lhs.iter().zip(rhs) {
+                if lhs_name != rhs_name || !lhs.equals(rhs)? {
+                    return Some(false);
+                }
+            }
+            Some(true)
+        }
+
+        Some(match (self, other) {
+            (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+            (Value::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+            (Value::Str(lhs), Value::Str(rhs)) => lhs == rhs,
+            (Value::Char(lhs), Value::Char(rhs)) => lhs == rhs,
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+            (Value::Array(lhs), Value::Array(rhs)) | (Value::Tuple(lhs), Value::Tuple(rhs)) => {
+                all(lhs, rhs)?
+            }
+            (
+                Value::Struct {
+                    name: lhs_name,
+                    fields: lhs,
+                },
+                Value::Struct {
+                    name: rhs_name,
+                    fields: rhs,
+                },
+            ) => lhs_name == rhs_name && fields(lhs, rhs)?,
+            (
+                Value::Variant {
+                    enum_name: lhs_enum,
+                    variant: lhs_variant,
+                    payload: lhs,
+                },
+                Value::Variant {
+                    enum_name: rhs_enum,
+                    variant: rhs_variant,
+                    payload: rhs,
+                },
+            ) => {
+                lhs_enum == rhs_enum
+                    && lhs_variant == rhs_variant
+                    && match (lhs, rhs) {
+                        (Payload::Unit, Payload::Unit) => true,
+                        (Payload::Tuple(lhs), Payload::Tuple(rhs)) => all(lhs, rhs)?,
+                        (Payload::Struct(lhs), Payload::Struct(rhs)) => fields(lhs, rhs)?,
+                        _ => false,
+                    }
+            }
+            (Value::Function(_), _) | (_, Value::Function(_)) => return None,
+            _ => false,
+        })
+    }
+
+    /// Formats the value the way it's written in source, so strings and
+    /// chars nested inside other values are quoted.
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(string) => write!(f, "{string:?}"),
+            Value::Char(char) => write!(f, "{char:?}"),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+fn list(f: &mut fmt::Formatter<'_>, values: &[Value]) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        value.fmt_nested(f)?;
+    }
+    Ok(())
+}
+
+fn field_list(f: &mut fmt::Formatter<'_>, fields: &[(String, Value)]) -> fmt::Result {
+    write!(f, " {{ ")?;
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{name}: ")?;
+        value.fmt_nested(f)?;
+    }
+    write!(f, " }}")
+}
+
+/// Strings and chars are printed as their contents, everything else the way
+/// it would be written in source.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{int}"),
+            Value::Float(float) => write!(f, "{float:?}"),
+            Value::Str(string) => write!(f, "{string}"),
+            Value::Char(char) => write!(f, "{char}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                list(f, values)?;
+                write!(f, "]")
+            }
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                list(f, values)?;
+                if values.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Struct { name, fields } => {
+                write!(f, "{name}")?;
+                field_list(f, fields)
+            }
+            Value::Variant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                write!(f, "{enum_name}::{variant}")?;
+                match payload {
+                    Payload::Unit => Ok(()),
+                    Payload::Tuple(values) => {
+                        write!(f, "(")?;
+                        list(f, values)?;
+                        write!(f, ")")
+                    }
+                    Payload::Struct(fields) => field_list(f, fields),
+                }
+            }
+            Value::Function(Function::Item(name)) => write!(f, "<fn {name}>"),
+            Value::Function(Function::Closure(_)) => write!(f, "<closure>"),
+            Value::Function(Function::Constructor {
+                enum_name, variant, ..
+            }) => write!(f, "<fn {enum_name}::{variant}>"),
+            Value::Function(Function::Builtin(builtin)) => write!(f, "<fn {}>", builtin.name),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::Display;
+
+/// Why a piece of input couldn't be turned into a valid token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnterminatedString,
+    UnterminatedChar,
+    /// A `/*` without a matching `*/`, which covers the rest of the input.
+    UnterminatedComment,
+    /// A char literal with no characters, or more than one.
+    InvalidCharLiteral,
+    /// An escape sequence that isn't supported or is missing its digits,
+    /// as far as it goes, and its byte offset in the literal.
+    InvalidEscape {
+        escape: String,
+        offset: usize,
+    },
+    /// A numeric escape sequence naming a code point that isn't a valid
+    /// char, or above `\x7F` for a `\x` escape, and its byte offset in the
+    /// literal.
+    EscapeOutOfRange {
+        escape: String,
+        offset: usize,
+    },
+    IntegerOverflow,
+    /// Characters that can't start any token.
+    StrayCharacters(String),
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+            LexError::UnterminatedChar => write!(f, "unterminated char literal"),
+            LexError::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexError::InvalidCharLiteral => {
+                write!(f, "char literal must contain exactly one character")
+            }
+            LexError::InvalidEscape { escape, .. } => {
+                // The escaped character might be a tab or line break.
+                write!(
+                    f,
+                    "invalid escape sequence `\\{}`",
+                    escape[1..].escape_debug()
+                )
+            }
+            LexError::EscapeOutOfRange { escape, .. } => {
+                write!(f, "escape sequence `{escape}` is out of range")
+            }
+            LexError::IntegerOverflow => write!(f, "integer literal is too large"),
+            LexError::StrayCharacters(chars) => write!(f, "unexpected characters `{chars}`"),
+        }
+    }
+}
@@ -1,77 +1,154 @@
-mod rules;
+mod error;
+mod scanner;
 #[cfg(test)]
 mod test;
 mod token;
 
+pub use error::LexError;
 pub use token::Token;
 
+use crate::span::{FileId, Span, Spanned};
+
 pub struct Lexer<'input> {
     input: &'input str,
+    file: FileId,
     position: usize,
     eof: bool,
+    /// For each expression in an interpolated string being lexed, innermost
+    /// last, how many of the `{`s in it are still open.
+    interpolations: Vec<usize>,
+    /// A token that goes right after the one just scanned, with no length.
+    pending: Option<Token>,
 }
 
 impl<'input> Iterator for Lexer<'input> {
-    type Item = Token;
+    type Item = Spanned<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.position >= self.input.len() {
+        if let Some(token) = self.pending.take() {
+            let span = Span::new(self.file, self.position, self.position);
+            return Some(Spanned::new(token, span));
+        }
+        self.skip_trivia();
+
+        let start = self.position;
+        let token = if self.position >= self.input.len() {
             if self.eof {
                 return None;
             }
             self.eof = true;
-            Some(Token::Eof)
+            Token::Eof
         } else {
-            Some(self.next_token(&self.input[self.position..]))
-        }
+            self.next_token(&self.input[self.position..])
+        };
+
+        Some(Spanned::new(
+            token,
+            Span::new(self.file, start, self.position),
+        ))
     }
 }
 
 impl<'input> Lexer<'input> {
     pub fn new(input: &'input str) -> Self {
+        Self::with_file(input, FileId::default())
+    }
+
+    /// Creates a lexer whose token spans point into `file`.
+    pub fn with_file(input: &'input str, file: FileId) -> Self {
         Self {
             input,
+            file,
             position: 0,
             eof: false,
+            interpolations: Vec::new(),
+            pending: None,
         }
     }
 
-    pub fn tokenize(&mut self) -> Vec<Token> {
+    pub fn tokenize(&mut self) -> Vec<Spanned<Token>> {
         self.collect()
     }
 
     pub fn next_token(&mut self, input: &str) -> Token {
-        self.valid_token(input)
-            .unwrap_or_else(|| self.invalid_token(input))
+        // Terminated comments are skipped as trivia, so this one never ends.
+        if input.starts_with("/*") {
+            self.position += input.len();
+            return Token::Error(LexError::UnterminatedComment);
+        }
+        // A `}` that the expression didn't open goes back to the string.
+        if input.starts_with('}') && self.interpolations.last() == Some(&0) {
+            self.interpolations.pop();
+            return self.string(input);
+        }
+        if input.starts_with('"') && !input.starts_with("\"\"\"") {
+            return self.string(input);
+        }
+        match scanner::token(input) {
+            Some((token, len)) => {
+                self.position += len;
+                if let Some(open) = self.interpolations.last_mut() {
+                    match token {
+                        Token::LBrace => *open += 1,
+                        Token::RBrace => *open -= 1,
+                        _ => {}
+                    }
+                }
+                token
+            }
+            None => self.invalid_token(input),
+        }
     }
 
-    /// Returns `None` if the lexer cannot find a token at the start of `input`.
-    fn valid_token(&mut self, input: &str) -> Option<Token> {
-        if input.starts_with("//") {
-            self.position += input
-                .char_indices()
-                .find(|(_, c)| *c == '\n')
-                .expect("expected newline to terminate comment")
-                .0;
-            self.next()
-        } else if input.chars().next().unwrap().is_whitespace() {
-            self.position += input
-                .char_indices()
-                .take_while(|(_, c)| c.is_whitespace())
-                .last()
-                .unwrap()
-                .0
-                + 1;
-            self.next()
-        } else {
-            let (token, len) = rules::RULES
-                .iter()
-                .rev()
-                .filter_map(|rule| rule(input))
-                .max_by_key(|&(_, len)| len)?;
+    /// Scans a string literal, or the rest of one after an interpolated
+    /// expression, keeping track of the next expression if it has one.
+    ///
+    /// A part of an interpolated string with invalid text is still followed
+    /// by an empty token for the part, so that the parser can tell where the
+    /// string goes on and ends.
+    fn string(&mut self, input: &str) -> Token {
+        let (token, len, interpolates) = scanner::string(input);
+        self.position += len;
+        if interpolates {
+            self.interpolations.push(0);
+        }
+        let continued = input.starts_with('}');
+        if matches!(token, Token::Error(_)) && (continued || interpolates) {
+            self.pending = Some(scanner::fragment(continued, interpolates, String::new()));
+        }
+        token
+    }
 
+    /// Advances past any whitespace and comments.
+    fn skip_trivia(&mut self) {
+        while let Some(len) = Self::trivia(&self.input[self.position..]) {
             self.position += len;
-            Some(token)
+        }
+    }
+
+    /// Returns the length of the whitespace or comment at the start of `input`, if any.
+    ///
+    /// Block comments that are never closed aren't trivia, so that they can
+    /// be reported, and neither are doc comments, which the parser keeps.
+    fn trivia(input: &str) -> Option<usize> {
+        if scanner::is_doc_comment(input) {
+            None
+        } else if input.starts_with("//") {
+            Some(input.find('\n').unwrap_or(input.len()))
+        } else if input.starts_with("/*") {
+            block_comment(input)
+        } else if input.chars().next()?.is_whitespace() {
+            Some(
+                input
+                    .char_indices()
+                    .take_while(|(_, c)| c.is_whitespace())
+                    .last()
+                    .unwrap()
+                    .0
+                    + 1,
+            )
+        } else {
+            None
         }
     }
 
@@ -81,13 +158,36 @@ impl<'input> Lexer<'input> {
         let len = input
             .char_indices()
             .map(|(pos, _)| pos)
-            .find(|pos| self.valid_token(&input[*pos..]).is_some())
+            .skip(1)
+            .find(|pos| {
+                Self::trivia(&input[*pos..]).is_some() || scanner::token(&input[*pos..]).is_some()
+            })
             .unwrap_or(input.len());
 
         self.position = start + len;
-        Token::Error {
-            start,
-            end: self.position,
+        Token::Error(LexError::StrayCharacters(input[..len].into()))
+    }
+}
+
+/// The length of the block comment at the start of `input`, including any
+/// comments nested in it, or `None` if it isn't closed.
+fn block_comment(input: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"/*" => depth += 1,
+            b"*/" => depth -= 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+        if depth == 0 {
+            return Some(i);
         }
     }
+    None
 }
@@ -0,0 +1,387 @@
+use std::iter::Peekable;
+
+use super::{LexError, token::Token};
+
+use Token as T;
+
+/// Scans the token at the start of `input`, returning it along with its
+/// length in bytes, or `None` if no token starts with the first character.
+///
+/// The first character (and, for operators, the second) decides what kind of
+/// token to scan, so the input is only ever looked at once.
+pub(super) fn token(input: &str) -> Option<(Token, usize)> {
+    let bytes = input.as_bytes();
+    let token = match (*bytes.first()?, bytes.get(1).copied().unwrap_or(0)) {
+        (b'0'..=b'9', _) | (b'.', b'0'..=b'9') => return Some(number(input)),
+        (b'r', b'"' | b'#') => return Some(raw_string(input).unwrap_or_else(|| word(input))),
+        (b'A'..=b'Z' | b'a'..=b'z' | b'_', _) => return Some(word(input)),
+        (b'"', b'"') if input.starts_with("\"\"\"") => return Some(triple_quoted(input)),
+        (b'"', _) => {
+            let (token, len, _) = string(input);
+            return Some((token, len));
+        }
+        (b'\'', _) => return Some(char(input)),
+        (b'/', b'/') if is_doc_comment(input) => return Some(doc_comment(input)),
+        (b'-', b'>') => (T::Arrow, 2),
+        (b':', b':') => (T::PathSep, 2),
+        (b'=', b'=') => (T::Eqq, 2),
+        (b'!', b'=') => (T::Neq, 2),
+        (b'*', b'*') => (T::Exponent, 2),
+        (b'&', b'&') => (T::And, 2),
+        (b'|', b'|') => (T::Or, 2),
+        (b'|', b'>') => (T::Pipeline, 2),
+        (b'<', b'=') => (T::Leq, 2),
+        (b'<', b'<') => (T::ComposeLeft, 2),
+        (b'>', b'=') => (T::Geq, 2),
+        (b'>', b'>') => (T::ComposeRight, 2),
+        (b'[', _) => (T::LBracket, 1),
+        (b']', _) => (T::RBracket, 1),
+        (b'{', _) => (T::LBrace, 1),
+        (b'}', _) => (T::RBrace, 1),
+        (b'(', _) => (T::LParen, 1),
+        (b')', _) => (T::RParen, 1),
+        (b'=', _) => (T::Eq, 1),
+        (b'&', _) => (T::Ampersand, 1),
+        (b'|', _) => (T::Pipe, 1),
+        (b'!', _) => (T::Bang, 1),
+        (b'^', _) => (T::Xor, 1),
+        (b'<', _) => (T::LAngle, 1),
+        (b'>', _) => (T::RAngle, 1),
+        (b'+', _) => (T::Plus, 1),
+        (b'-', _) => (T::Minus, 1),
+        (b'*', _) => (T::Times, 1),
+        (b'/', _) => (T::FSlash, 1),
+        (b'\\', _) => (T::BSlash, 1),
+        (b'.', _) => (T::Dot, 1),
+        (b',', _) => (T::Comma, 1),
+        (b':', _) => (T::Colon, 1),
+        (b';', _) => (T::Semicolon, 1),
+        _ => return None,
+    };
+    Some(token)
+}
+
+/// Whether `input` starts with a `///` or `//!` doc comment. Four or more
+/// slashes make an ordinary comment, as they often draw a line.
+pub(super) fn is_doc_comment(input: &str) -> bool {
+    input.starts_with("//!") || (input.starts_with("///") && !input.starts_with("////"))
+}
+
+/// Scans a doc comment up to the end of its line, dropping the marker and
+/// the space after it.
+fn doc_comment(input: &str) -> (Token, usize) {
+    let len = input.find('\n').unwrap_or(input.len());
+    let text = input[3..len].trim_end_matches('\r');
+    let text = text.strip_prefix(' ').unwrap_or(text).to_string();
+    let token = if input.as_bytes()[2] == b'!' {
+        T::InnerDocComment(text)
+    } else {
+        T::DocComment(text)
+    };
+    (token, len)
+}
+
+/// Scans an int literal, or a float literal if there's a `.` after the
+/// digits. Floats may also start with the `.` and end with an exponent.
+fn number(input: &str) -> (Token, usize) {
+    let bytes = input.as_bytes();
+    let digits = |from: usize| {
+        from + bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+
+    let int_end = digits(0);
+    if bytes.get(int_end) != Some(&b'.') {
+        let token = input[..int_end]
+            .parse()
+            .map_or(T::Error(LexError::IntegerOverflow), T::IntLit);
+        return (token, int_end);
+    }
+
+    let mut end = digits(int_end + 1);
+    // `1.5e` is the float `1.5` followed by the identifier `e`.
+    if let Some(b'e' | b'E') = bytes.get(end) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        let exponent_end = digits(end + 1 + sign);
+        if exponent_end > end + 1 + sign {
+            end = exponent_end;
+        }
+    }
+    (T::FloatLit(input[..end].parse().unwrap()), end)
+}
+
+/// Scans an identifier, keyword or `_`.
+fn word(input: &str) -> (Token, usize) {
+    let len = input
+        .bytes()
+        .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        .count();
+    let token = match &input[..len] {
+        "_" => T::Underscore,
+        "let" => T::Let,
+        "mut" => T::Mut,
+        "const" => T::Const,
+        "fn" => T::Fn,
+        "struct" => T::Struct,
+        "enum" => T::Enum,
+        "if" => T::If,
+        "else" => T::Else,
+        "match" => T::Match,
+        "true" => T::True,
+        "false" => T::False,
+        ident => T::Ident(ident.into()),
+    };
+    (token, len)
+}
+
+/// Scans a string literal, or the part of an interpolated one from the `}`
+/// that ends an expression. Also returns whether it stopped at the `{` of
+/// another expression, which holds even if the text so far is invalid.
+pub(super) fn string(input: &str) -> (Token, usize, bool) {
+    let continued = input.starts_with('}');
+    let (contents, len, interpolates) = quoted(input, '"', LexError::UnterminatedString);
+    let token = contents.map_or_else(T::Error, |contents| {
+        fragment(continued, interpolates, contents)
+    });
+    (token, len, interpolates)
+}
+
+/// The token for a string with `text` in it, which is part of an
+/// interpolated one if it's `continued` from a `}` or it `interpolates`.
+pub(super) fn fragment(continued: bool, interpolates: bool, text: String) -> Token {
+    match (continued, interpolates) {
+        (false, false) => T::StringLit(text),
+        (false, true) => T::InterpolationStart(text),
+        (true, true) => T::InterpolationMiddle(text),
+        (true, false) => T::InterpolationEnd(text),
+    }
+}
+
+/// Scans a raw string, in which a `\\` is just a `\\`. Any number of `#`s
+/// can go between the `r` and the opening quote, and the string only ends at
+/// a quote followed by as many, so that it can contain `"` and `"#`.
+///
+/// Returns `None` if no quote follows the `r` and `#`s, since then the `r` is
+/// an identifier.
+fn raw_string(input: &str) -> Option<(Token, usize)> {
+    let hashes = input[1..].bytes().take_while(|&b| b == b'#').count();
+    let start = hashes + 2;
+    if input.as_bytes().get(start - 1) != Some(&b'"') {
+        return None;
+    }
+
+    let closing = format!("\"{}", "#".repeat(hashes));
+    Some(match input[start..].find(&closing) {
+        Some(len) => (
+            T::StringLit(input[start..start + len].into()),
+            start + len + closing.len(),
+        ),
+        None => (T::Error(LexError::UnterminatedString), input.len()),
+    })
+}
+
+/// Scans a string delimited by `\"\"\"`, which can contain quotes without
+/// escaping them.
+///
+/// The rest of the opening line and the whole closing line are dropped if
+/// they're blank, along with the indentation that every other non-blank line
+/// shares, so that the string can be indented along with the code around it.
+fn triple_quoted(input: &str) -> (Token, usize) {
+    let bytes = input.as_bytes();
+    let mut close = 3;
+    loop {
+        match bytes.get(close..close + 3) {
+            None => return (T::Error(LexError::UnterminatedString), input.len()),
+            Some(b"\"\"\"") => break,
+            Some([b'\\', ..]) => close += 2,
+            Some(_) => close += 1,
+        }
+    }
+
+    let mut start = 3;
+    if let Some(newline) = input[start..close].find('\n')
+        && input[start..start + newline].trim().is_empty()
+    {
+        start += newline + 1;
+    }
+    let last_line = input[start..close]
+        .rfind('\n')
+        .map_or(start, |i| start + i + 1);
+    let end = if input[last_line..close].trim().is_empty() {
+        start.max(last_line.saturating_sub(1))
+    } else {
+        close
+    };
+
+    let is_indent = |c: char| c == ' ' || c == '\t';
+    let indent = input[start..end]
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(is_indent).len())
+        .min()
+        .unwrap_or(0);
+
+    let body = &input[..end];
+    let mut contents = Ok(String::new());
+    let mut chars = body
+        .char_indices()
+        .skip_while(|&(i, _)| i < start)
+        .peekable();
+    let mut line_start = true;
+    loop {
+        if line_start {
+            // Lines shorter than the indentation are blank, so stripping
+            // stops at their end.
+            for _ in 0..indent {
+                chars.next_if(|&(_, c)| is_indent(c));
+            }
+        }
+        let Some((i, c)) = chars.next() else {
+            break;
+        };
+        line_start = c == '\n';
+        let decoded = match c {
+            '\\' => escape(body, i, &mut chars, true).unwrap_or_else(|| {
+                Err(LexError::InvalidEscape {
+                    escape: "\\".into(),
+                    offset: i,
+                })
+            }),
+            c => Ok(c),
+        };
+        push(&mut contents, decoded);
+    }
+    (contents.map_or_else(T::Error, T::StringLit), close + 3)
+}
+
+fn char(input: &str) -> (Token, usize) {
+    let (contents, len, _) = quoted(input, '\'', LexError::UnterminatedChar);
+    let token = contents.and_then(|contents| {
+        let mut chars = contents.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(T::CharLit(c)),
+            _ => Err(LexError::InvalidCharLiteral),
+        }
+    });
+    (token.unwrap_or_else(T::Error), len)
+}
+
+/// Scans a string or char literal delimited by `quote`, decoding its escape
+/// sequences along the way. Strings may span lines, but a char literal ends
+/// at the first newline.
+///
+/// A string also ends at a `{`, which starts an interpolated expression, and
+/// then the last value returned is `true`.
+///
+/// An unterminated literal still covers everything it would have, and an
+/// invalid escape doesn't end the literal, so either way the whole literal
+/// becomes a single error token.
+fn quoted(
+    input: &str,
+    quote: char,
+    unterminated: LexError,
+) -> (Result<String, LexError>, usize, bool) {
+    let multiline = quote == '"';
+    let mut contents = Ok(String::new());
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        let decoded = match c {
+            _ if c == quote => return (contents, i + 1, false),
+            '{' if quote == '"' => return (contents, i + 1, true),
+            '\n' if !multiline => return (Err(unterminated), i, false),
+            '\\' => match escape(input, i, &mut chars, multiline) {
+                Some(decoded) => decoded,
+                // Nothing left on the line to escape, so there's no closing
+                // quote either.
+                None => continue,
+            },
+            c => Ok(c),
+        };
+
+        push(&mut contents, decoded);
+    }
+    (Err(unterminated), input.len(), false)
+}
+
+/// Adds a decoded char to the contents of a literal, unless an earlier one
+/// couldn't be decoded, in which case the first error is kept.
+fn push(contents: &mut Result<String, LexError>, decoded: Result<char, LexError>) {
+    match (contents.as_mut(), decoded) {
+        (Ok(contents), Ok(c)) => contents.push(c),
+        (Ok(_), Err(err)) => *contents = Err(err),
+        (Err(_), _) => {}
+    }
+}
+
+/// Decodes the escape sequence whose `\` is at byte `start` of `input`,
+/// taking the rest of it from `chars`. Returns `None` if the `\` is the last
+/// thing on the line, or in the input.
+///
+/// A malformed escape covers as much as it could have been part of, so that
+/// errors point at all of it.
+fn escape(
+    input: &str,
+    start: usize,
+    chars: &mut Peekable<impl Iterator<Item = (usize, char)>>,
+    multiline: bool,
+) -> Option<Result<char, LexError>> {
+    let (_, c) = chars.next_if(|&(_, c)| multiline || c != '\n')?;
+    // `None` if malformed, and `Some(None)` if out of range.
+    let decoded = match c {
+        'n' => Some(Some('\n')),
+        't' => Some(Some('\t')),
+        'r' => Some(Some('\r')),
+        '0' => Some(Some('\0')),
+        '\\' | '"' | '\'' | '{' | '}' => Some(Some(c)),
+        'x' => {
+            let (value, digits) = hex_digits(chars, 2);
+            (digits == 2).then(|| char::from_u32(value).filter(char::is_ascii))
+        }
+        'u' => {
+            if chars.next_if(|&(_, c)| c == '{').is_some() {
+                let (value, digits) = hex_digits(chars, usize::MAX);
+                (digits > 0 && chars.next_if(|&(_, c)| c == '}').is_some())
+                    .then(|| char::from_u32(value))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
+    let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+    let escape = input[start..end].to_string();
+    Some(match decoded {
+        Some(Some(c)) => Ok(c),
+        Some(None) => Err(LexError::EscapeOutOfRange {
+            escape,
+            offset: start,
+        }),
+        None => Err(LexError::InvalidEscape {
+            escape,
+            offset: start,
+        }),
+    })
+}
+
+/// Takes up to `max` hex digits from `chars`, returning their value, which
+/// saturates rather than overflowing, and how many there were.
+fn hex_digits(
+    chars: &mut Peekable<impl Iterator<Item = (usize, char)>>,
+    max: usize,
+) -> (u32, usize) {
+    let mut value = 0u32;
+    let mut digits = 0;
+    while digits < max {
+        let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) else {
+            break;
+        };
+        value = value
+            .saturating_mul(16)
+            .saturating_add(c.to_digit(16).unwrap());
+        digits += 1;
+    }
+    (value, digits)
+}
@@ -1,4 +1,9 @@
-use super::{Lexer, token::Token as T};
+use super::{LexError, Lexer, token::Token as T};
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ParseError,
+    span::{FileId, Span},
+};
 
 /// walks `$tokens` and compares them to the given kinds.
 macro_rules! assert_tokens {
@@ -7,7 +12,7 @@ macro_rules! assert_tokens {
             let mut iter = $tokens.iter();
             $(
                 let token = iter.next().expect("not enough tokens");
-                assert_eq!(*token, $token);
+                assert_eq!(token.node, $token);
             )*
         }
     };
@@ -37,7 +42,26 @@ fn unknown_input() {
     let tokens = lexer.tokenize();
     assert_tokens!(
         tokens,
-        [T::LBrace, T::Error { start: 1, end: 8 }, T::Plus, T::Eof,]
+        [
+            T::LBrace,
+            T::Error(LexError::StrayCharacters("$$$$$$$".into())),
+            T::Plus,
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn token_spans() {
+    let mut lexer = Lexer::new("let x  = // one\n  \"hi\"");
+    let spans: Vec<_> = lexer.tokenize().into_iter().map(|t| t.span).collect();
+    assert_eq!(
+        spans,
+        [(0, 3), (4, 5), (7, 8), (18, 22), (22, 22)].map(|(start, end)| Span::new(
+            FileId::default(),
+            start,
+            end
+        ))
     );
 }
 
@@ -61,7 +85,7 @@ fn single_char_tokens_with_whitespace() {
 
 #[test]
 fn maybe_multiple_char_tokens() {
-    let mut lexer = Lexer::new("&&=<=_!=||**->");
+    let mut lexer = Lexer::new("&&=<=_!=||**->::|>>><<");
     let tokens = lexer.tokenize();
     assert_tokens!(
         tokens,
@@ -74,6 +98,10 @@ fn maybe_multiple_char_tokens() {
             T::Or,
             T::Exponent,
             T::Arrow,
+            T::PathSep,
+            T::Pipeline,
+            T::ComposeRight,
+            T::ComposeLeft,
             T::Eof,
         ]
     );
@@ -105,6 +133,69 @@ fn comment() {
     let mut lexer = Lexer::new("//hello, world!\nif let");
     let tokens: Vec<_> = lexer.tokenize();
     assert_tokens!(tokens, [T::If, T::Let,]);
+
+    let mut lexer = Lexer::new("if // no newline after this");
+    let tokens = lexer.tokenize();
+    assert_tokens!(tokens, [T::If, T::Eof,]);
+}
+
+#[test]
+fn doc_comments() {
+    let mut lexer = Lexer::new("/// Adds.\n///\n//!  Inner\r\n//// not docs\nfn ///last");
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::DocComment("Adds.".into()),
+            T::DocComment("".into()),
+            T::InnerDocComment(" Inner".into()),
+            T::Fn,
+            T::DocComment("last".into()),
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn block_comments() {
+    let mut lexer = Lexer::new("if /* a /* nested */ comment\n*/ let/**/else /*/ */ fn");
+    let tokens = lexer.tokenize();
+    assert_tokens!(tokens, [T::If, T::Let, T::Else, T::Fn, T::Eof,]);
+
+    let mut lexer = Lexer::new("1 */ 2");
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [T::IntLit(1), T::Times, T::FSlash, T::IntLit(2), T::Eof,]
+    );
+}
+
+#[test]
+fn unterminated_block_comment() {
+    let source = "let x = 1; /* outer /* inner */\nlet y = 2;";
+    let tokens = Lexer::new(source).tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::Let,
+            T::Ident("x".into()),
+            T::Eq,
+            T::IntLit(1),
+            T::Semicolon,
+            T::Error(LexError::UnterminatedComment),
+            T::Eof,
+        ]
+    );
+    let span = tokens[5].span;
+    assert_eq!((span.start, span.end), (11, source.len()));
+
+    let diagnostic = Diagnostic::from(&ParseError::Lex(LexError::UnterminatedComment, span));
+    assert_eq!(diagnostic.message, "unterminated block comment");
+    assert_eq!(
+        (diagnostic.primary.span.start, diagnostic.primary.span.end),
+        (11, 13)
+    );
+    assert_eq!(diagnostic.primary.message, "comment opened here");
 }
 
 #[test]
@@ -126,6 +217,216 @@ fn literals() {
     );
 }
 
+#[test]
+fn literal_errors() {
+    let mut lexer = Lexer::new(
+        r#"99999999999999999999 "bad \q" 'ab' '' '\z' "ok\\n" 'x
+"open"#,
+    );
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::Error(LexError::IntegerOverflow),
+            T::Error(LexError::InvalidEscape {
+                escape: r"\q".into(),
+                offset: 5
+            }),
+            T::Error(LexError::InvalidCharLiteral),
+            T::Error(LexError::InvalidCharLiteral),
+            T::Error(LexError::InvalidEscape {
+                escape: r"\z".into(),
+                offset: 1
+            }),
+            T::StringLit("ok\\n".into()),
+            T::Error(LexError::UnterminatedChar),
+            T::Error(LexError::UnterminatedString),
+            T::Eof,
+        ]
+    );
+    assert_eq!((tokens[7].span.start, tokens[7].span.end), (54, 59));
+}
+
+#[test]
+fn escapes() {
+    let mut lexer = Lexer::new(
+        r#""\t\r\0\\n\"\'" '\'' '"' '\x41' '\x7f' "\u{48}\u{e9}\u{1F600}" 'é' '🦀' "\\\u{5C}""#,
+    );
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::StringLit("\t\r\0\\n\"'".into()),
+            T::CharLit('\''),
+            T::CharLit('"'),
+            T::CharLit('A'),
+            T::CharLit('\x7f'),
+            T::StringLit("Hé😀".into()),
+            T::CharLit('é'),
+            T::CharLit('🦀'),
+            T::StringLit("\\\\".into()),
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn escape_errors() {
+    let source = r#""\x4g" "\x80" "\u41" "\u{}" "\u{41" "ok \u{110000}" '\u{D800}' "é\é""#;
+    let tokens = Lexer::new(source).tokenize();
+    let invalid = |escape: &str, offset| {
+        T::Error(LexError::InvalidEscape {
+            escape: escape.into(),
+            offset,
+        })
+    };
+    let out_of_range = |escape: &str, offset| {
+        T::Error(LexError::EscapeOutOfRange {
+            escape: escape.into(),
+            offset,
+        })
+    };
+    assert_tokens!(
+        tokens,
+        [
+            invalid(r"\x4", 1),
+            out_of_range(r"\x80", 1),
+            invalid(r"\u", 1),
+            invalid(r"\u{", 1),
+            invalid(r"\u{41", 1),
+            out_of_range(r"\u{110000}", 4),
+            out_of_range(r"\u{D800}", 1),
+            invalid(r"\é", 3),
+            T::Eof,
+        ]
+    );
+
+    let diagnostic = |token: usize| {
+        let T::Error(err) = &tokens[token].node else {
+            panic!("expected an error token");
+        };
+        Diagnostic::from(&ParseError::Lex(err.clone(), tokens[token].span))
+    };
+
+    let out_of_range = diagnostic(5);
+    assert_eq!(
+        out_of_range.message,
+        r"escape sequence `\u{110000}` is out of range"
+    );
+    let start = source.find(r"\u{110000}").unwrap();
+    assert_eq!(
+        (
+            out_of_range.primary.span.start,
+            out_of_range.primary.span.end
+        ),
+        (start, start + 10)
+    );
+
+    let invalid = diagnostic(0);
+    assert_eq!(invalid.message, r"invalid escape sequence `\x4`");
+    assert_eq!(
+        (invalid.primary.span.start, invalid.primary.span.end),
+        (1, 4)
+    );
+    assert_eq!(
+        invalid.primary.message,
+        r"expected two hex digits, as in `\x41`"
+    );
+}
+
+#[test]
+fn raw_strings() {
+    let mut lexer = Lexer::new(r####"r"\d+\n" r#"say "hi""# r##"a "# b"## r"" r#x r"open"####);
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::StringLit(r"\d+\n".into()),
+            T::StringLit(r#"say "hi""#.into()),
+            T::StringLit(r##"a "# b"##.into()),
+            T::StringLit("".into()),
+            T::Ident("r".into()),
+            T::Error(LexError::StrayCharacters("#".into())),
+            T::Ident("x".into()),
+            T::Error(LexError::UnterminatedString),
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn triple_quoted_strings() {
+    let source = r#"
+    let query = """
+        SELECT "name"
+          FROM users\t
+
+        WHERE id = \u{31}
+        """
+    """one "line""" """
+    end""" """""" """open"#;
+    let tokens = Lexer::new(source).tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::Let,
+            T::Ident("query".into()),
+            T::Eq,
+            T::StringLit("SELECT \"name\"\n  FROM users\t\n\nWHERE id = 1".into()),
+            T::StringLit("one \"line".into()),
+            T::StringLit("end".into()),
+            T::StringLit("".into()),
+            T::Error(LexError::UnterminatedString),
+            T::Eof,
+        ]
+    );
+
+    // Escapes in them are still checked, and point into the source.
+    let tokens = Lexer::new("\"\"\"\n  a\\q\n\"\"\"").tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::Error(LexError::InvalidEscape {
+                escape: r"\q".into(),
+                offset: 7,
+            }),
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn interpolated_strings() {
+    let mut lexer = Lexer::new(r#""a{x}b{ { p }.y }c" "\{}" "{"in{1}"}" "{f(}"#);
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::InterpolationStart("a".into()),
+            T::Ident("x".into()),
+            T::InterpolationMiddle("b".into()),
+            T::LBrace,
+            T::Ident("p".into()),
+            T::RBrace,
+            T::Dot,
+            T::Ident("y".into()),
+            T::InterpolationEnd("c".into()),
+            T::StringLit("{}".into()),
+            T::InterpolationStart("".into()),
+            T::InterpolationStart("in".into()),
+            T::IntLit(1),
+            T::InterpolationEnd("".into()),
+            T::InterpolationEnd("".into()),
+            T::InterpolationStart("".into()),
+            T::Ident("f".into()),
+            T::LParen,
+            T::Error(LexError::UnterminatedString),
+            T::InterpolationEnd("".into()),
+            T::Eof,
+        ]
+    );
+}
+
 #[test]
 fn function() {
     let input = r#"
@@ -140,7 +441,7 @@ fn function() {
             }
         }
     "#;
-    let mut lexer = Lexer::new(&input);
+    let mut lexer = Lexer::new(input);
     let tokens = lexer.tokenize();
     assert_tokens!(
         tokens,
@@ -224,3 +525,36 @@ fn function() {
         ]
     );
 }
+
+#[test]
+fn numbers() {
+    let mut lexer = Lexer::new("1.5e3 2.E-1 3.5e .5x 7.y 1.2.3");
+    let tokens = lexer.tokenize();
+    assert_tokens!(
+        tokens,
+        [
+            T::FloatLit(1.5e3),
+            T::FloatLit(2e-1),
+            T::FloatLit(3.5),
+            T::Ident("e".into()),
+            T::FloatLit(0.5),
+            T::Ident("x".into()),
+            T::FloatLit(7.),
+            T::Ident("y".into()),
+            T::FloatLit(1.2),
+            T::FloatLit(0.3),
+            T::Eof,
+        ]
+    );
+}
+
+#[test]
+fn escaped_quote_at_end_of_input() {
+    let mut lexer = Lexer::new(r#""open \""#);
+    let tokens = lexer.tokenize();
+    assert_tokens!(tokens, [T::Error(LexError::UnterminatedString), T::Eof,]);
+
+    let mut lexer = Lexer::new("'\\'");
+    let tokens = lexer.tokenize();
+    assert_tokens!(tokens, [T::Error(LexError::UnterminatedChar), T::Eof,]);
+}
@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use super::LexError;
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Token {
     // Literals
@@ -7,6 +9,15 @@ pub enum Token {
     FloatLit(f64),
     StringLit(String),
     CharLit(char),
+    /// The text of an interpolated string up to the `{` of its first
+    /// expression.
+    InterpolationStart(String),
+    /// The text of an interpolated string between the `}` of one expression
+    /// and the `{` of the next.
+    InterpolationMiddle(String),
+    /// The text of an interpolated string from the `}` of its last expression
+    /// to its closing quote.
+    InterpolationEnd(String),
     // Delimiters
     LParen,
     RParen,
@@ -32,6 +43,7 @@ pub enum Token {
     Semicolon,
     Underscore,
     Arrow,
+    PathSep,
     // Operators
     Exponent,
     And,
@@ -41,6 +53,9 @@ pub enum Token {
     Neq,
     Leq,
     Geq,
+    Pipeline,
+    ComposeRight,
+    ComposeLeft,
     // Keywords
     Let,
     Mut,
@@ -55,7 +70,12 @@ pub enum Token {
     False,
     // Misc
     Ident(String),
-    Error { start: usize, end: usize },
+    /// A `///` comment, which documents the item, field or variant after it.
+    /// Holds the text after the `///` and one space.
+    DocComment(String),
+    /// A `//!` comment, which documents the item whose body it starts.
+    InnerDocComment(String),
+    Error(LexError),
     Eof,
 }
 
@@ -69,6 +89,9 @@ impl Display for Token {
                 Token::FloatLit(_) => "float literal",
                 Token::StringLit(_) => "string literal",
                 Token::CharLit(_) => "char literal",
+                Token::InterpolationStart(_) => "start of interpolated string",
+                Token::InterpolationMiddle(_) => "middle of interpolated string",
+                Token::InterpolationEnd(_) => "end of interpolated string",
                 Token::LParen => "(",
                 Token::RParen => ")",
                 Token::LBrace => "{",
@@ -92,6 +115,7 @@ impl Display for Token {
                 Token::Semicolon => ";",
                 Token::Underscore => "_",
                 Token::Arrow => "->",
+                Token::PathSep => "::",
                 Token::Exponent => "**",
                 Token::And => "&&",
                 Token::Or => "||",
@@ -100,6 +124,9 @@ impl Display for Token {
                 Token::Neq => "!=",
                 Token::Leq => "<=",
                 Token::Geq => ">=",
+                Token::Pipeline => "|>",
+                Token::ComposeRight => ">>",
+                Token::ComposeLeft => "<<",
                 Token::Let => "let",
                 Token::Mut => "mut",
                 Token::Const => "const",
@@ -112,7 +139,9 @@ impl Display for Token {
                 Token::True => "true",
                 Token::False => "false",
                 Token::Ident(i) => return write!(f, "identifier {i}"),
-                Token::Error { start, end } => return write!(f, "ERROR start:{start} end:{end}"),
+                Token::DocComment(_) => "doc comment",
+                Token::InnerDocComment(_) => "inner doc comment",
+                Token::Error(err) => return write!(f, "invalid token ({err})"),
                 Token::Eof => "eof",
             }
         )
@@ -0,0 +1,13 @@
+pub mod codegen;
+pub mod desugar;
+pub mod diagnostics;
+pub mod embed;
+pub mod interpreter;
+pub mod lexer;
+pub mod mutability;
+pub mod natives;
+pub mod parser;
+pub mod resolve;
+pub mod source_map;
+pub mod span;
+pub mod typeck;
@@ -0,0 +1,101 @@
+//! Checks that assignments only change what was declared mutable: `let mut`
+//! bindings, `mut` parameters and pattern bindings, and the elements and
+//! fields of values held in them.
+
+#[cfg(test)]
+mod test;
+
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{Ast, Bop, Expr, ExprKind, ItemKind},
+    resolve::{DefKind, Resolution},
+};
+
+/// Checks every assignment in `ast`, using the names `resolution` found.
+pub fn check(ast: &Ast, resolution: &Resolution) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for item in ast {
+        match &item.kind {
+            ItemKind::Const { value: body, .. } | ItemKind::Function { body, .. } => {
+                assignments(body, resolution, &mut diagnostics);
+            }
+            ItemKind::Struct { .. } | ItemKind::Enum { .. } | ItemKind::Error => {}
+        }
+    }
+    diagnostics
+}
+
+/// Checks the assignments in `expr` and everything nested inside it.
+fn assignments(expr: &Expr, resolution: &Resolution, diagnostics: &mut Vec<Diagnostic>) {
+    if let ExprKind::BinaryOp {
+        op: Bop::Assign,
+        lhs,
+        ..
+    } = &expr.kind
+        && let Some(diagnostic) = assignment(expr, lhs, resolution)
+    {
+        diagnostics.push(diagnostic);
+    }
+
+    for child in expr.children() {
+        assignments(child, resolution, diagnostics);
+    }
+}
+
+/// Checks the assignment `assign` to `place`.
+fn assignment(assign: &Expr, place: &Expr, resolution: &Resolution) -> Option<Diagnostic> {
+    let mut root = place;
+    while let ExprKind::Index { arr: inner, .. } | ExprKind::FieldAccess { base: inner, .. } =
+        &root.kind
+    {
+        root = inner;
+    }
+
+    let ExprKind::Ident(name) = &root.kind else {
+        return Some(
+            Diagnostic::error(
+                "can only assign to variables, or elements and fields of them",
+                place.span,
+            )
+            .with_label("not a place that can be assigned to"),
+        );
+    };
+    // Undefined names were already reported.
+    let def = resolution.def(resolution.use_at(root.span)?);
+
+    let target = match &place.kind {
+        ExprKind::Index { .. } => format!("an element of `{name}`"),
+        ExprKind::FieldAccess { field, .. } => format!("field `{field}` of `{name}`"),
+        _ => format!("`{name}`"),
+    };
+    let diagnostic = match def.kind {
+        DefKind::Param | DefKind::Local if def.mutable => return None,
+        DefKind::Param | DefKind::Local => Diagnostic::error(
+            format!("cannot assign to {target}, as `{name}` is not declared as mutable"),
+            assign.span,
+        )
+        .with_label("cannot assign")
+        .with_secondary(
+            def.span,
+            format!("consider making this binding mutable: `mut {name}`"),
+        ),
+        DefKind::Const => Diagnostic::error(
+            format!("cannot assign to {target}, as `{name}` is a const"),
+            assign.span,
+        )
+        .with_label("cannot assign")
+        .with_secondary(def.span, format!("`{name}` is defined here")),
+        DefKind::Function | DefKind::Struct | DefKind::Enum => Diagnostic::error(
+            format!("cannot assign to {target}, as `{name}` is an item"),
+            assign.span,
+        )
+        .with_label("cannot assign")
+        .with_secondary(def.span, format!("`{name}` is defined here")),
+        DefKind::Builtin => Diagnostic::error(
+            format!("cannot assign to {target}, as `{name}` is a builtin function"),
+            assign.span,
+        )
+        .with_label("cannot assign"),
+    };
+    Some(diagnostic)
+}
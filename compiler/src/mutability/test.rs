@@ -0,0 +1,105 @@
+use super::check;
+use crate::{desugar::desugar, natives::Natives, parser::Parser, resolve::resolve, span::Span};
+
+/// Each error's message, and the text its secondary label points at.
+fn errors(input: &str) -> Vec<(String, Option<&str>)> {
+    let (mut ast, errors) = Parser::new(input).file();
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(desugar(&mut ast).is_empty());
+    let (resolution, diagnostics) = resolve(&ast, &Natives::builtins());
+    assert!(diagnostics.iter().all(|diag| !diag.is_error()));
+
+    let text = |span: Span| &input[span.start..span.end];
+    check(&ast, &resolution)
+        .into_iter()
+        .map(|diag| {
+            (
+                diag.message,
+                diag.secondary.first().map(|label| text(label.span)),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn allow_mutable_places() {
+    let input = "
+        struct Point { x: Int, y: Int }
+        fn f(mut p, q) -> {
+            let mut x = 1;
+            x = 2;
+            p.x = x;
+            let mut xs = [q, q];
+            xs[0] = 3;
+            let mut nested = ([1], Point { x: 1, y: 2 });
+            nested._0[0] = nested._1.y;
+            match q {
+                mut n -> n = 1,
+            };
+            let set = |mut v| -> v = 4;
+        }
+    ";
+    assert_eq!(errors(input), []);
+}
+
+#[test]
+fn reject_immutable_places() {
+    let input = "
+        const limit: Int = 10
+        fn f(p, xs) -> {
+            let x = 1;
+            x = 2;
+            p.x = 3;
+            xs[0] = 4;
+            limit = 5;
+            f = f;
+            (x, x)._0 = 6;
+            match x {
+                n -> n = 7,
+            };
+            let set = |v| -> v = 8;
+        }
+    ";
+    let expected = [
+        (
+            "cannot assign to `x`, as `x` is not declared as mutable",
+            Some("x"),
+        ),
+        (
+            "cannot assign to field `x` of `p`, as `p` is not declared as mutable",
+            Some("p"),
+        ),
+        (
+            "cannot assign to an element of `xs`, as `xs` is not declared as mutable",
+            Some("xs"),
+        ),
+        (
+            "cannot assign to `limit`, as `limit` is a const",
+            Some("const limit: Int = 10"),
+        ),
+        ("cannot assign to `f`, as `f` is an item", None),
+        (
+            "can only assign to variables, or elements and fields of them",
+            None,
+        ),
+        (
+            "cannot assign to `n`, as `n` is not declared as mutable",
+            Some("n"),
+        ),
+        (
+            "cannot assign to `v`, as `v` is not declared as mutable",
+            Some("v"),
+        ),
+    ];
+
+    let errors = errors(input);
+    assert_eq!(errors.len(), expected.len());
+    for ((message, secondary), (expected_message, expected_secondary)) in
+        errors.iter().zip(expected)
+    {
+        assert_eq!(message, expected_message);
+        if let Some(expected_secondary) = expected_secondary {
+            assert_eq!(*secondary, Some(expected_secondary));
+        }
+    }
+}
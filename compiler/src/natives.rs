@@ -0,0 +1,100 @@
+//! The functions a program can call without defining them: the builtins,
+//! and any a Rust program hosting the runtime provides.
+
+use runtime::convert::{Signature, Type};
+
+use crate::typeck::{Class, Scheme, Ty};
+
+/// Natives by name, with their types. Any item or variable of the same name
+/// shadows them.
+#[derive(Debug, Clone)]
+pub struct Natives {
+    natives: Vec<(String, Scheme)>,
+}
+
+impl Natives {
+    /// The functions every program can call, which both the interpreter
+    /// and the runtime provide.
+    pub fn builtins() -> Self {
+        let any = Ty::Gen(0);
+        let builtins = [
+            ("print", Ty::Fn(vec![any.clone()], Box::new(Ty::unit()))),
+            ("println", Ty::Fn(vec![any.clone()], Box::new(Ty::unit()))),
+            ("len", Ty::Fn(vec![any.clone()], Box::new(Ty::int()))),
+            ("to_string", Ty::Fn(vec![any], Box::new(Ty::str()))),
+        ];
+        let natives = builtins
+            .into_iter()
+            .map(|(name, ty)| {
+                let scheme = Scheme {
+                    vars: vec![None],
+                    ty,
+                };
+                (name.to_string(), scheme)
+            })
+            .collect();
+        Natives { natives }
+    }
+
+    /// Adds a native, replacing any existing one called `name`.
+    pub fn add(&mut self, name: &str, scheme: Scheme) {
+        match self.natives.iter_mut().find(|(native, _)| native == name) {
+            Some((_, existing)) => *existing = scheme,
+            None => self.natives.push((name.into(), scheme)),
+        }
+    }
+
+    /// Adds a native a host registered with the runtime. Each `Type::Any`
+    /// in its signature can be a different type.
+    pub fn add_signature(&mut self, name: &str, signature: &Signature) {
+        let mut vars = Vec::new();
+        let params = signature
+            .params
+            .iter()
+            .map(|param| ty(param, &mut vars))
+            .collect();
+        let result = ty(&signature.result, &mut vars);
+        let ty = Ty::Fn(params, Box::new(result));
+        self.add(name, Scheme { vars, ty });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scheme> {
+        self.natives
+            .iter()
+            .find(|(native, _)| native == name)
+            .map(|(_, scheme)| scheme)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.natives.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Default for Natives {
+    fn default() -> Self {
+        Natives::builtins()
+    }
+}
+
+/// Converts a runtime type, giving each `Type::Any` a generic of its own.
+fn ty(ty: &Type, vars: &mut Vec<Option<Class>>) -> Ty {
+    match ty {
+        Type::Unit => Ty::unit(),
+        Type::Bool => Ty::bool(),
+        Type::Int => Ty::int(),
+        Type::Float => Ty::float(),
+        Type::Char => Ty::char(),
+        Type::Str => Ty::str(),
+        Type::Array(element) => Ty::Array(Box::new(self::ty(element, vars))),
+        Type::Tuple(elements) => Ty::Tuple(
+            elements
+                .iter()
+                .map(|element| self::ty(element, vars))
+                .collect(),
+        ),
+        Type::Any => {
+            vars.push(None);
+            Ty::Gen(vars.len() as u32 - 1)
+        }
+    }
+}
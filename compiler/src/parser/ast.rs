@@ -1,7 +1,34 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::span::Span;
+
 pub type Ast = Vec<Item>;
 
+/// Implements `PartialEq` over every field except `span`, so that trees
+/// parsed from differently laid out source still compare equal.
+macro_rules! eq_ignoring_span {
+    ($ty:ident { $($field:ident),+ }) => {
+        impl PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                $(self.$field == other.$field)&&+
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// The lines of its `///` comments, followed by those of any `//!`
+    /// comments at the start of its body.
+    pub docs: Vec<String>,
+    pub span: Span,
+}
+
+eq_ignoring_span!(Item { kind, docs });
+
 #[derive(Debug, Clone, PartialEq)]
-pub enum Item {
+pub enum ItemKind {
     Const {
         ident: String,
         ty: Type,
@@ -23,30 +50,64 @@ pub enum Item {
         generic_params: Vec<String>,
         variants: Vec<Variant>,
     },
+    /// An item that failed to parse.
+    Error,
 }
 
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub kind: VariantKind,
+    /// The lines of its doc comments, in order.
+    pub docs: Vec<String>,
+    pub span: Span,
+}
+
+eq_ignoring_span!(Variant { kind, docs });
+
 #[derive(Debug, Clone, PartialEq)]
-pub enum Variant {
+pub enum VariantKind {
     Unit(String),
     Tuple(String, Vec<Type>),
     Struct(String, Vec<Field>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Field {
     pub name: String,
     pub ty: Type,
+    /// The lines of its doc comments, in order.
+    pub docs: Vec<String>,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+eq_ignoring_span!(Field { name, ty, docs });
+
+#[derive(Debug, Clone)]
 pub struct Binding {
     pub mutable: bool,
     pub name: String,
     pub type_annotation: Option<Type>,
+    pub span: Span,
+}
+
+eq_ignoring_span!(Binding {
+    mutable,
+    name,
+    type_annotation
+});
+
+#[derive(Debug, Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
 }
 
+eq_ignoring_span!(Type { kind });
+
+impl Eq for Type {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Type {
+pub enum TypeKind {
     Ident {
         name: String,
         generics: Vec<Type>,
@@ -59,10 +120,133 @@ pub enum Type {
     },
 }
 
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list(types: &[Type]) -> String {
+            types
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        match &self.kind {
+            TypeKind::Ident { name, generics } if generics.is_empty() => write!(f, "{name}"),
+            TypeKind::Ident { name, generics } => write!(f, "{name}<{}>", list(generics)),
+            TypeKind::Array(inner) => write!(f, "[{inner}]"),
+            TypeKind::Tuple(types) => write!(f, "({})", list(types)),
+            TypeKind::Fn { params, result } => write!(f, "fn({}): {result}", list(params)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+eq_ignoring_span!(Expr { kind });
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// The expressions directly nested inside this one, in source order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Literal(Lit::Array(exprs) | Lit::Tuple(exprs))
+            | ExprKind::Block { exprs, .. } => exprs.iter().collect(),
+            ExprKind::Literal(Lit::Struct { fields, .. }) => {
+                fields.iter().map(|field| &field.value).collect()
+            }
+            ExprKind::FnCall { fun, args } => std::iter::once(&**fun).chain(args.iter()).collect(),
+            ExprKind::BinaryOp { lhs, rhs, .. } => vec![lhs, rhs],
+            ExprKind::Index {
+                arr: lhs,
+                index: rhs,
+            } => vec![lhs, rhs],
+            ExprKind::UnaryOp { expr, .. }
+            | ExprKind::FieldAccess { base: expr, .. }
+            | ExprKind::Let { value: expr, .. } => vec![expr],
+            ExprKind::Lambda { body, .. } => vec![body],
+            ExprKind::If { cond, th, el } => [Some(cond), Some(th), el.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(|expr| &**expr)
+                .collect(),
+            ExprKind::Match { scrutinee, arms } => std::iter::once(&**scrutinee)
+                .chain(arms.iter().map(|arm| &arm.body))
+                .collect(),
+            ExprKind::Interpolated(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    StringPart::Expr(expr) => Some(expr),
+                    StringPart::Text(_) => None,
+                })
+                .collect(),
+            ExprKind::Literal(_)
+            | ExprKind::Ident(_)
+            | ExprKind::Path { .. }
+            | ExprKind::Placeholder
+            | ExprKind::Error => Vec::new(),
+        }
+    }
+
+    /// Like `children`, but mutable.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match &mut self.kind {
+            ExprKind::Literal(Lit::Array(exprs) | Lit::Tuple(exprs))
+            | ExprKind::Block { exprs, .. } => exprs.iter_mut().collect(),
+            ExprKind::Literal(Lit::Struct { fields, .. }) => {
+                fields.iter_mut().map(|field| &mut field.value).collect()
+            }
+            ExprKind::FnCall { fun, args } => {
+                std::iter::once(&mut **fun).chain(args.iter_mut()).collect()
+            }
+            ExprKind::BinaryOp { lhs, rhs, .. } => vec![lhs, rhs],
+            ExprKind::Index {
+                arr: lhs,
+                index: rhs,
+            } => vec![lhs, rhs],
+            ExprKind::UnaryOp { expr, .. }
+            | ExprKind::FieldAccess { base: expr, .. }
+            | ExprKind::Let { value: expr, .. } => vec![expr],
+            ExprKind::Lambda { body, .. } => vec![Arc::make_mut(body)],
+            ExprKind::If { cond, th, el } => [Some(cond), Some(th), el.as_mut()]
+                .into_iter()
+                .flatten()
+                .map(|expr| &mut **expr)
+                .collect(),
+            ExprKind::Match { scrutinee, arms } => std::iter::once(&mut **scrutinee)
+                .chain(arms.iter_mut().map(|arm| &mut arm.body))
+                .collect(),
+            ExprKind::Interpolated(parts) => parts
+                .iter_mut()
+                .filter_map(|part| match part {
+                    StringPart::Expr(expr) => Some(expr),
+                    StringPart::Text(_) => None,
+                })
+                .collect(),
+            ExprKind::Literal(_)
+            | ExprKind::Ident(_)
+            | ExprKind::Path { .. }
+            | ExprKind::Placeholder
+            | ExprKind::Error => Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expr {
+pub enum ExprKind {
     Literal(Lit),
     Ident(String),
+    /// `Enum::Variant`, either a unit variant's value or a tuple variant's constructor.
+    Path {
+        enum_name: String,
+        variant: String,
+    },
     FnCall {
         fun: Box<Expr>,
         args: Vec<Expr>,
@@ -93,15 +277,34 @@ pub enum Expr {
         binding: Binding,
         value: Box<Expr>,
     },
+    /// Shared, so that the interpreter's closures can hold on to the
+    /// parameters and body without copying them.
     Lambda {
-        params: Vec<Binding>,
+        params: Arc<[Binding]>,
         return_type: Option<Type>,
-        body: Box<Expr>,
+        body: Arc<Expr>,
     },
     Block {
         exprs: Vec<Expr>,
         trailing: bool,
     },
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    /// A string literal with expressions in it, like `"total: {a + b}"`.
+    Interpolated(Vec<StringPart>),
+    /// A `_` call argument, left for partial application to fill in.
+    Placeholder,
+    /// An expression that failed to parse.
+    Error,
+}
+
+/// A piece of an interpolated string. Empty text is left out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Expr(Expr),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,17 +316,125 @@ pub enum Lit {
     Bool(bool),
     Array(Vec<Expr>),
     Tuple(Vec<Expr>),
+    /// `Struct { field: value }`, or `Enum::Variant { field: value }` when
+    /// `variant` is set.
+    Struct {
+        name: String,
+        variant: Option<String>,
+        fields: Vec<FieldInit>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
+
+eq_ignoring_span!(FieldInit { name, value });
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+eq_ignoring_span!(MatchArm { pattern, body });
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+eq_ignoring_span!(Pattern { kind });
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    /// Only ever holds a scalar literal, never an array or tuple.
+    Literal(Lit),
+    Binding {
+        mutable: bool,
+        name: String,
+    },
+    Tuple(Vec<Pattern>),
+    Array(Vec<Pattern>),
+    /// `Enum::Variant`, `Enum::Variant(..)` or `Enum::Variant { .. }`.
+    Variant {
+        enum_name: String,
+        variant: VariantPattern,
+    },
+    /// `Struct { field: pattern, shorthand }`
+    Struct {
+        name: String,
+        fields: Vec<FieldPattern>,
+    },
+}
+
+/// The shape of an enum variant pattern, mirroring `VariantKind`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantPattern {
+    Unit(String),
+    Tuple(String, Vec<Pattern>),
+    Struct(String, Vec<FieldPattern>),
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldPattern {
+    pub name: String,
+    pub pattern: Pattern,
+    pub span: Span,
+}
+
+eq_ignoring_span!(FieldPattern { name, pattern });
+
+/// Builds a node with an empty span, for trees that don't come from source
+/// text. Any other fields listed, like `docs`, are left empty too.
+macro_rules! from_kind {
+    ($kind:ident => $node:ident $(, $field:ident)*) => {
+        impl From<$kind> for $node {
+            fn from(kind: $kind) -> Self {
+                $node {
+                    kind,
+                    $($field: Default::default(),)*
+                    span: Span::default(),
+                }
+            }
+        }
+
+        impl From<$kind> for Box<$node> {
+            fn from(kind: $kind) -> Self {
+                Box::new(kind.into())
+            }
+        }
+
+        impl From<$kind> for Arc<$node> {
+            fn from(kind: $kind) -> Self {
+                Arc::new(kind.into())
+            }
+        }
+    };
 }
 
+from_kind!(ItemKind => Item, docs);
+from_kind!(VariantKind => Variant, docs);
+from_kind!(TypeKind => Type);
+from_kind!(ExprKind => Expr);
+from_kind!(PatternKind => Pattern);
+
 impl From<Lit> for Expr {
     fn from(value: Lit) -> Self {
-        Expr::Literal(value)
+        ExprKind::Literal(value).into()
     }
 }
 
 impl From<Lit> for Box<Expr> {
     fn from(value: Lit) -> Self {
-        Box::new(Expr::Literal(value))
+        Box::new(value.into())
     }
 }
 
@@ -146,6 +457,43 @@ pub enum Bop {
     Geq,
     Leq,
     Assign,
+    /// `x |> f`, which calls `f(x)`. Removed by desugaring.
+    Pipeline,
+    /// `f >> g`, which calls `f` and then `g`. Removed by desugaring.
+    ComposeRight,
+    /// `f << g`, which calls `g` and then `f`. Removed by desugaring.
+    ComposeLeft,
+}
+
+impl Display for Bop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Bop::Add => "+",
+                Bop::Sub => "-",
+                Bop::Mul => "*",
+                Bop::Div => "/",
+                Bop::Exp => "**",
+                Bop::And => "&&",
+                Bop::Or => "||",
+                Bop::Xor => "^",
+                Bop::BOr => "|",
+                Bop::BAnd => "&",
+                Bop::Gt => ">",
+                Bop::Lt => "<",
+                Bop::Eqq => "==",
+                Bop::Neq => "!=",
+                Bop::Geq => ">=",
+                Bop::Leq => "<=",
+                Bop::Assign => "=",
+                Bop::Pipeline => "|>",
+                Bop::ComposeRight => ">>",
+                Bop::ComposeLeft => "<<",
+            }
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -153,3 +501,16 @@ pub enum Unop {
     Not,
     Neg,
 }
+
+impl Display for Unop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Unop::Not => "!",
+                Unop::Neg => "-",
+            }
+        )
+    }
+}
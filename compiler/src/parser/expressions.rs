@@ -1,7 +1,8 @@
 use super::{
-    ParseError, ParseResult, Parser, Token,
-    ast::{Bop, Expr, Lit, Unop},
+    ParseError, ParseResult, Parser, Spanned, Token,
+    ast::{Bop, Expr, ExprKind, FieldInit, Lit, MatchArm, StringPart, Unop},
 };
+use std::{mem, sync::Arc};
 
 trait PrefixOperator {
     fn binding_power(&self) -> u8;
@@ -23,27 +24,141 @@ impl InfixOperator for Bop {
     fn binding_power(&self) -> (u8, u8) {
         match self {
             Bop::Assign => (1, 2),
-            Bop::Or => (3, 4),
-            Bop::And => (5, 6),
-            Bop::Eqq | Bop::Neq => (7, 8),
-            Bop::Gt | Bop::Lt | Bop::Leq | Bop::Geq => (9, 10),
-            Bop::BOr => (11, 12),
-            Bop::Xor => (13, 14),
-            Bop::BAnd => (15, 16),
-            Bop::Add | Bop::Sub => (17, 18),
-            Bop::Mul | Bop::Div => (19, 20),
-            Bop::Exp => (22, 21),
+            Bop::Pipeline => (3, 4),
+            Bop::ComposeRight | Bop::ComposeLeft => (5, 6),
+            Bop::Or => (7, 8),
+            Bop::And => (9, 10),
+            Bop::Eqq | Bop::Neq => (11, 12),
+            Bop::Gt | Bop::Lt | Bop::Leq | Bop::Geq => (13, 14),
+            Bop::BOr => (15, 16),
+            Bop::Xor => (17, 18),
+            Bop::BAnd => (19, 20),
+            Bop::Add | Bop::Sub => (21, 22),
+            Bop::Mul | Bop::Div => (23, 24),
+            Bop::Exp => (26, 25),
         }
     }
 }
 
-impl<I: Iterator<Item = Token>> Parser<I> {
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
     pub fn expression(&mut self) -> ParseResult<Expr> {
-        self.parse_expression(0)
+        self.with_brace_ends_expr(false)
+    }
+
+    /// Parses an expression that is directly followed by a `{` which isn't
+    /// part of it, like the scrutinee of a `match`. An `Ident {` there starts
+    /// the block rather than a struct literal.
+    ///
+    /// The restriction is lifted again inside any nested delimiters, since
+    /// those always call `expression`.
+    fn expression_before_brace(&mut self) -> ParseResult<Expr> {
+        self.with_brace_ends_expr(true)
+    }
+
+    fn with_brace_ends_expr(&mut self, brace_ends_expr: bool) -> ParseResult<Expr> {
+        let outer = mem::replace(&mut self.brace_ends_expr, brace_ends_expr);
+        let expr = self.parse_expression(0);
+        self.brace_ends_expr = outer;
+        expr
+    }
+
+    /// Parses `{ name: value, shorthand }`.
+    fn field_inits(&mut self) -> ParseResult<Vec<FieldInit>> {
+        self.delimited_list(
+            |this| {
+                let start = this.peek_span();
+                let name = this.ident()?;
+
+                let value = if this.consume_at(&Token::Colon) {
+                    this.expression()?
+                } else {
+                    Expr::new(ExprKind::Ident(name.clone()), this.span_from(start))
+                };
+
+                Ok(FieldInit {
+                    name,
+                    value,
+                    span: this.span_from(start),
+                })
+            },
+            &Token::LBrace,
+            &Token::RBrace,
+        )
+    }
+
+    /// A call argument, which may be a `_` placeholder for partial application.
+    fn argument(&mut self) -> ParseResult<Expr> {
+        if self.at(&Token::Underscore) {
+            let span = self.next().unwrap().span;
+            Ok(Expr::new(ExprKind::Placeholder, span))
+        } else {
+            self.expression()
+        }
+    }
+
+    fn match_arm(&mut self) -> ParseResult<MatchArm> {
+        let start = self.peek_span();
+
+        let pattern = self.pattern()?;
+        self.consume(&Token::Arrow)?;
+        let body = self.expression()?;
+
+        Ok(MatchArm {
+            pattern,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses an interpolated string, whose text the lexer has split into
+    /// tokens around the expressions in it.
+    fn interpolated(&mut self) -> ParseResult<ExprKind> {
+        let Some(Spanned {
+            node: Token::InterpolationStart(text),
+            ..
+        }) = self.next()
+        else {
+            unreachable!()
+        };
+
+        let mut parts = vec![StringPart::Text(text)];
+        // Whether the text of any part couldn't be lexed, which `next`
+        // records the error for.
+        let mut invalid = false;
+        loop {
+            parts.push(StringPart::Expr(self.expression()?));
+            if let Token::Error(_) = self.peek() {
+                self.next();
+                invalid = true;
+            }
+            let span = self.peek_span();
+            match self.next().map_or(Token::Eof, |token| token.node) {
+                Token::InterpolationMiddle(text) => parts.push(StringPart::Text(text)),
+                Token::InterpolationEnd(text) => {
+                    parts.push(StringPart::Text(text));
+                    break;
+                }
+                token => {
+                    return Err(ParseError::MismatchedToken {
+                        expected: Token::RBrace.to_string(),
+                        found: token.to_string(),
+                        span,
+                    });
+                }
+            }
+        }
+        if invalid {
+            return Ok(ExprKind::Error);
+        }
+
+        parts.retain(|part| !matches!(part, StringPart::Text(text) if text.is_empty()));
+        Ok(ExprKind::Interpolated(parts))
     }
 
     fn parse_expression(&mut self, binding_power: u8) -> ParseResult<Expr> {
-        let mut lhs = match self.peek() {
+        let start = self.peek_span();
+
+        let kind = match self.peek() {
             Token::LParen => {
                 self.next();
                 let expr = self.expression()?;
@@ -58,10 +173,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     }
                     self.consume(&Token::RParen)?;
 
-                    Expr::Literal(Lit::Tuple(exprs))
+                    ExprKind::Literal(Lit::Tuple(exprs))
                 } else {
                     self.consume(&Token::RParen)?;
-                    expr
+                    expr.kind
                 }
             }
             Token::IntLit(_)
@@ -69,7 +184,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             | Token::StringLit(_)
             | Token::CharLit(_)
             | Token::True
-            | Token::False => Expr::Literal(match self.next().unwrap() {
+            | Token::False => ExprKind::Literal(match self.next().unwrap().node {
                 Token::IntLit(int) => Lit::Int(int),
                 Token::FloatLit(float) => Lit::Float(float),
                 Token::StringLit(string) => Lit::Str(string),
@@ -78,17 +193,51 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 Token::False => Lit::Bool(false),
                 _ => unreachable!(),
             }),
-            Token::LBracket => Expr::Literal(Lit::Array(self.delimited_list(
+            Token::InterpolationStart(_) => self.interpolated()?,
+            // The lexer error is recorded by `next`, so stand in for the broken
+            // literal. If it's the start of an interpolated string, the rest of
+            // the string still has to be parsed.
+            Token::Error(_) => {
+                self.next();
+                if self.at(&Token::InterpolationStart(String::new())) {
+                    self.interpolated()?;
+                }
+                ExprKind::Error
+            }
+            Token::LBracket => ExprKind::Literal(Lit::Array(self.delimited_list(
                 Self::expression,
                 &Token::LBracket,
                 &Token::RBracket,
             )?)),
             Token::Ident(_) => {
-                let Some(Token::Ident(ident)) = self.next() else {
+                let Some(Spanned {
+                    node: Token::Ident(ident),
+                    ..
+                }) = self.next()
+                else {
                     unreachable!()
                 };
 
-                Expr::Ident(ident)
+                let variant = if self.consume_at(&Token::PathSep) {
+                    Some(self.ident()?)
+                } else {
+                    None
+                };
+
+                if !self.brace_ends_expr && self.at(&Token::LBrace) {
+                    ExprKind::Literal(Lit::Struct {
+                        name: ident,
+                        variant,
+                        fields: self.field_inits()?,
+                    })
+                } else if let Some(variant) = variant {
+                    ExprKind::Path {
+                        enum_name: ident,
+                        variant,
+                    }
+                } else {
+                    ExprKind::Ident(ident)
+                }
             }
             Token::If => {
                 self.next();
@@ -104,7 +253,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     None
                 };
 
-                Expr::If {
+                ExprKind::If {
                     cond: Box::new(cond),
                     th: Box::new(th),
                     el,
@@ -121,7 +270,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 let right_binding_power = op.binding_power();
                 let expr = self.parse_expression(right_binding_power)?;
-                Expr::UnaryOp {
+                ExprKind::UnaryOp {
                     op,
                     expr: Box::new(expr),
                 }
@@ -134,7 +283,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 self.consume(&Token::Eq)?;
                 let value = self.expression()?;
 
-                Expr::Let {
+                ExprKind::Let {
                     binding,
                     value: Box::new(value),
                 }
@@ -150,10 +299,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 self.consume(&Token::Arrow)?;
 
-                let body = Box::new(self.expression()?);
+                let body = Arc::new(self.expression()?);
 
-                Expr::Lambda {
-                    params,
+                ExprKind::Lambda {
+                    params: params.into(),
                     return_type,
                     body,
                 }
@@ -164,7 +313,17 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 let mut trailing = true;
                 let mut exprs = Vec::new();
                 while !self.at(&Token::RBrace) {
-                    exprs.push(self.expression()?);
+                    let expr_start = self.peek_span();
+                    match self.expression() {
+                        Ok(expr) => exprs.push(expr),
+                        // Without a `;` or `}` to resume at, the block can't be
+                        // closed, so let the enclosing item recover instead.
+                        Err(err) if !self.synchronize_block() => return Err(err),
+                        Err(err) => {
+                            self.errors.push(err);
+                            exprs.push(Expr::new(ExprKind::Error, self.span_from(expr_start)));
+                        }
+                    }
 
                     if self.consume_at(&Token::Semicolon) && self.at(&Token::RBrace) {
                         trailing = false;
@@ -173,15 +332,27 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 }
                 self.consume(&Token::RBrace)?;
 
-                Expr::Block { exprs, trailing }
+                ExprKind::Block { exprs, trailing }
+            }
+            Token::Match => {
+                self.next();
+
+                let scrutinee = Box::new(self.expression_before_brace()?);
+                let arms = self.delimited_list(Self::match_arm, &Token::LBrace, &Token::RBrace)?;
+
+                ExprKind::Match { scrutinee, arms }
             }
             token => {
                 return Err(ParseError::UnexpectedToken(
                     token.to_string(),
                     Some("start of expression".into()),
+                    start,
                 ));
             }
         };
+        let mut lhs = Expr::new(kind, self.span_from(start));
+
+        let brace_ends_expr = self.brace_ends_expr;
         loop {
             let op = match self.peek() {
                 Token::Eq => Bop::Assign,
@@ -201,49 +372,75 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 Token::Leq => Bop::Leq,
                 Token::RAngle => Bop::Gt,
                 Token::Geq => Bop::Geq,
+                Token::Pipeline => Bop::Pipeline,
+                Token::ComposeRight => Bop::ComposeRight,
+                Token::ComposeLeft => Bop::ComposeLeft,
                 Token::LBracket => {
                     self.next();
 
                     let index = Box::new(self.expression()?);
                     self.consume(&Token::RBracket)?;
 
-                    lhs = Expr::Index {
-                        arr: Box::new(lhs),
-                        index
-                    };
+                    lhs = Expr::new(
+                        ExprKind::Index {
+                            arr: Box::new(lhs),
+                            index,
+                        },
+                        self.span_from(start),
+                    );
                     continue;
                 }
                 Token::Dot => {
                     self.next();
 
-                    lhs = Expr::FieldAccess {
-                        base: Box::new(lhs),
-                        field: self.ident()?
-                    };
+                    let field = self.ident()?;
+
+                    lhs = Expr::new(
+                        ExprKind::FieldAccess {
+                            base: Box::new(lhs),
+                            field,
+                        },
+                        self.span_from(start),
+                    );
                     continue;
                 }
                 Token::LParen => {
                     let args =
-                        self.delimited_list(Self::expression, &Token::LParen, &Token::RParen)?;
-
-                    lhs = Expr::FnCall {
-                        fun: Box::new(lhs),
-                        args,
-                    };
+                        self.delimited_list(Self::argument, &Token::LParen, &Token::RParen)?;
+
+                    lhs = Expr::new(
+                        ExprKind::FnCall {
+                            fun: Box::new(lhs),
+                            args,
+                        },
+                        self.span_from(start),
+                    );
                     continue;
                 }
                 Token::Eof => break,
+                Token::LBrace if brace_ends_expr => break,
                 Token::Else
                 | Token::RParen // Delimiters
                 | Token::RBrace
                 | Token::RBracket
+                | Token::InterpolationMiddle(_)
+                | Token::InterpolationEnd(_)
+                // Reported where it's parsed, as an error rather than as an
+                // unexpected token.
+                | Token::Error(_)
                 | Token::Comma
                 | Token::Semicolon
                 | Token::Fn
                 | Token::Const
                 | Token::Struct
                 | Token::Enum => break,
-                token => return Err(ParseError::UnexpectedToken(token.to_string(), Some("end of expression".into()))),
+                token => {
+                    return Err(ParseError::UnexpectedToken(
+                        token.to_string(),
+                        Some("end of expression".into()),
+                        self.peek_span(),
+                    ));
+                }
             };
 
             let (left_binding_power, right_binding_power) = op.binding_power();
@@ -255,11 +452,15 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             self.next();
 
             let rhs = self.parse_expression(right_binding_power)?;
-            lhs = Expr::BinaryOp {
-                op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-            };
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr::new(
+                ExprKind::BinaryOp {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span,
+            );
         }
 
         Ok(lhs)
@@ -1,12 +1,14 @@
 use crate::parser::ParseError;
 
 use super::{
-    ParseResult, Parser, Token,
-    ast::{Binding, Type},
+    ParseResult, Parser, Spanned, Token,
+    ast::{Binding, Type, TypeKind},
 };
 
-impl<I: Iterator<Item = Token>> Parser<I> {
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
     pub fn binding(&mut self) -> ParseResult<Binding> {
+        let start = self.peek_span();
+
         let mutable = self.consume_at(&Token::Mut);
 
         let name = self.ident()?;
@@ -21,13 +23,20 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             mutable,
             name,
             type_annotation,
+            span: self.span_from(start),
         })
     }
 
     pub fn type_(&mut self) -> ParseResult<Type> {
-        Ok(match self.peek() {
+        let start = self.peek_span();
+
+        let kind = match self.peek() {
             Token::Ident(_) => {
-                let Some(Token::Ident(name)) = self.next() else {
+                let Some(Spanned {
+                    node: Token::Ident(name),
+                    ..
+                }) = self.next()
+                else {
                     unreachable!()
                 };
 
@@ -37,41 +46,52 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                     Vec::new()
                 };
 
-                Type::Ident { name, generics }
+                TypeKind::Ident { name, generics }
             }
             Token::LBracket => {
                 self.next();
                 let inner_type = self.type_()?;
                 self.consume(&Token::RBracket)?;
-                Type::Array(Box::new(inner_type))
+                TypeKind::Array(Box::new(inner_type))
             }
             Token::LParen => {
-                Type::Tuple(self.delimited_list(Self::type_, &Token::LParen, &Token::RParen)?)
+                TypeKind::Tuple(self.delimited_list(Self::type_, &Token::LParen, &Token::RParen)?)
             }
             Token::Fn => {
                 self.next();
                 let params = self.delimited_list(Self::type_, &Token::LParen, &Token::RParen)?;
                 self.consume(&Token::Colon)?;
                 let result = Box::new(self.type_()?);
-                Type::Fn { params, result }
+                TypeKind::Fn { params, result }
             }
             token => {
                 return Err(ParseError::UnexpectedToken(
                     token.to_string(),
                     Some("start of type name".into()),
+                    start,
                 ));
             }
+        };
+
+        Ok(Type {
+            kind,
+            span: self.span_from(start),
         })
     }
 
     pub fn ident(&mut self) -> ParseResult<String> {
+        let span = self.peek_span();
         match self.next() {
-            Some(Token::Ident(ident)) => Ok(ident),
+            Some(Spanned {
+                node: Token::Ident(ident),
+                ..
+            }) => Ok(ident),
             Some(token) => Err(ParseError::MismatchedToken {
-                expected: Token::Ident(String::new()).to_string(),
-                found: token.to_string(),
+                expected: "identifier".into(),
+                found: token.node.to_string(),
+                span: token.span,
             }),
-            None => Err(ParseError::MissingToken),
+            None => Err(ParseError::MissingToken(span)),
         }
     }
 
@@ -94,6 +114,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 break;
             }
         }
+        // The `>` closing nested generics is lexed as part of `>>` (or `>=`).
+        if *end == Token::RAngle {
+            self.split_closing_angle();
+        }
         self.consume(end)?;
 
         Ok(items)
@@ -1,19 +1,40 @@
 use super::{
-    ParseError, ParseResult, Parser, Token,
-    ast::{Ast, Field, Item, Variant},
+    ParseError, ParseResult, Parser, Spanned, Token,
+    ast::{Ast, Field, Item, ItemKind, Variant, VariantKind},
 };
 
-impl<I: Iterator<Item = Token>> Parser<I> {
-    pub fn file(&mut self) -> ParseResult<Ast> {
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
+    /// Parses a whole file, recovering from errors so that as many as possible
+    /// are reported at once. Items that failed to parse are left in the
+    /// `Ast` as `ItemKind::Error`.
+    pub fn file(&mut self) -> (Ast, Vec<ParseError>) {
         let mut items = Vec::new();
         while !self.at(&Token::Eof) {
-            items.push(self.item()?);
+            let start = self.peek_span();
+            let item = self.item().unwrap_or_else(|err| {
+                self.errors.push(err);
+                self.synchronize_item();
+
+                Item {
+                    kind: ItemKind::Error,
+                    docs: Vec::new(),
+                    span: self.span_from(start),
+                }
+            });
+            items.push(item);
         }
-        Ok(items)
+        let mut errors = self.take_errors();
+        errors.sort_by_key(|err| err.span().start);
+        (items, errors)
     }
 
+    /// Parses an item, along with the `///` comments before it and any `//!`
+    /// comments at the start of its body.
     pub fn item(&mut self) -> ParseResult<Item> {
-        Ok(match self.peek() {
+        let mut docs = self.take_docs();
+        let start = self.peek_span();
+
+        let kind = match self.peek() {
             Token::Const => {
                 self.next();
 
@@ -25,7 +46,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 self.consume(&Token::Eq)?;
                 let value = self.expression()?;
 
-                Item::Const { ident, ty, value }
+                ItemKind::Const { ident, ty, value }
             }
             Token::Fn => {
                 self.next();
@@ -42,9 +63,9 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 self.consume(&Token::Arrow)?;
 
-                let body = self.expression()?;
+                let body = self.body_docs(&mut docs, Self::expression)?;
 
-                Item::Function {
+                ItemKind::Function {
                     name,
                     params,
                     return_type,
@@ -56,10 +77,10 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 let (name, generic_params) = self.type_name()?;
 
-                Item::Struct {
+                ItemKind::Struct {
                     name,
                     generic_params,
-                    fields: self.fields()?,
+                    fields: self.body_docs(&mut docs, Self::fields)?,
                 }
             }
             Token::Enum => {
@@ -67,30 +88,11 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
                 let (name, generic_params) = self.type_name()?;
 
-                let variants = self.delimited_list(
-                    |this| {
-                        let variant_name = this.ident()?;
-
-                        Ok(match this.peek() {
-                            Token::LBrace => Variant::Struct(variant_name, this.fields()?),
-                            Token::LParen => Variant::Tuple(
-                                variant_name,
-                                this.delimited_list(Self::type_, &Token::LParen, &Token::RParen)?,
-                            ),
-                            Token::Comma => Variant::Unit(variant_name),
-                            token => {
-                                return Err(ParseError::MismatchedToken {
-                                    expected: "one of `,` `(` `{`".into(),
-                                    found: token.to_string(),
-                                });
-                            }
-                        })
-                    },
-                    &Token::LBrace,
-                    &Token::RBrace,
-                )?;
-
-                Item::Enum {
+                let variants = self.body_docs(&mut docs, |this| {
+                    this.delimited_list(Self::variant, &Token::LBrace, &Token::RBrace)
+                })?;
+
+                ItemKind::Enum {
                     name,
                     generic_params,
                     variants,
@@ -100,8 +102,43 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 return Err(ParseError::UnexpectedToken(
                     token.to_string(),
                     Some("start of item".into()),
+                    start,
                 ));
             }
+        };
+
+        Ok(Item {
+            kind,
+            docs,
+            span: self.span_from(start),
+        })
+    }
+
+    fn variant(&mut self) -> ParseResult<Variant> {
+        let docs = self.take_docs();
+        let start = self.peek_span();
+        let variant_name = self.ident()?;
+
+        let kind = match self.peek() {
+            Token::LBrace => VariantKind::Struct(variant_name, self.fields()?),
+            Token::LParen => VariantKind::Tuple(
+                variant_name,
+                self.delimited_list(Self::type_, &Token::LParen, &Token::RParen)?,
+            ),
+            Token::Comma => VariantKind::Unit(variant_name),
+            token => {
+                return Err(ParseError::MismatchedToken {
+                    expected: "one of `,` `(` `{`".into(),
+                    found: token.to_string(),
+                    span: self.peek_span(),
+                });
+            }
+        };
+
+        Ok(Variant {
+            kind,
+            docs,
+            span: self.span_from(start),
         })
     }
 
@@ -120,12 +157,19 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     fn fields(&mut self) -> ParseResult<Vec<Field>> {
         self.delimited_list(
             |this| {
+                let docs = this.take_docs();
+                let start = this.peek_span();
                 let name = this.ident()?;
 
                 this.consume(&Token::Colon)?;
                 let ty = this.type_()?;
 
-                Ok(Field { name, ty })
+                Ok(Field {
+                    name,
+                    ty,
+                    docs,
+                    span: this.span_from(start),
+                })
             },
             &Token::LBrace,
             &Token::RBrace,
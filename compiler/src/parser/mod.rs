@@ -1,58 +1,203 @@
-mod ast;
+pub mod ast;
 mod expressions;
 mod helpers;
 mod items;
+mod patterns;
+mod recovery;
 #[cfg(test)]
 mod test;
 
-use crate::lexer::{Lexer, Token};
+use crate::{
+    diagnostics::Diagnostic,
+    lexer::{LexError, Lexer, Token},
+    span::{FileId, Span, Spanned},
+};
 use std::{error::Error, fmt::Display, iter::Peekable, mem};
 
 #[derive(Debug)]
 pub enum ParseError {
-    MissingToken,
-    MismatchedToken { expected: String, found: String },
-    UnexpectedToken(String, Option<String>),
+    MissingToken(Span),
+    MismatchedToken {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    UnexpectedToken(String, Option<String>, Span),
+    /// A token the lexer couldn't make sense of.
+    Lex(LexError, Span),
+}
+
+impl ParseError {
+    /// The location in the source that the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::MissingToken(span)
+            | ParseError::MismatchedToken { span, .. }
+            | ParseError::UnexpectedToken(_, _, span)
+            | ParseError::Lex(_, span) => *span,
+        }
+    }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::MissingToken => write!(f, "expected another token"),
-            ParseError::MismatchedToken { expected, found } => {
+            ParseError::MissingToken(_) => write!(f, "expected another token"),
+            ParseError::MismatchedToken {
+                expected, found, ..
+            } => {
                 write!(f, "expected token {expected}, found token {found}")
             }
-            ParseError::UnexpectedToken(token, Some(desc)) => {
+            ParseError::UnexpectedToken(token, Some(desc), _) => {
                 write!(f, "unexpected token `{token}` at {desc}")
             }
-            ParseError::UnexpectedToken(token, None) => write!(f, "unexpected token `{token:?}`"),
+            ParseError::UnexpectedToken(token, None, _) => {
+                write!(f, "unexpected token `{token:?}`")
+            }
+            ParseError::Lex(err, _) => write!(f, "{err}"),
         }
     }
 }
 
 impl Error for ParseError {}
 
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        let diagnostic = Diagnostic::error(err.to_string(), err.span());
+        match err {
+            ParseError::MissingToken(_) => diagnostic.with_label("input ends here"),
+            ParseError::MismatchedToken { expected, .. } => {
+                diagnostic.with_label(format!("expected {expected}"))
+            }
+            ParseError::UnexpectedToken(..) => diagnostic.with_label("unexpected token"),
+            ParseError::Lex(lex_err, span) => match lex_err {
+                LexError::UnterminatedString | LexError::UnterminatedChar => diagnostic
+                    .with_label("missing closing quote")
+                    .with_note("literals can't be left open at the end of a line or file"),
+                LexError::InvalidCharLiteral => diagnostic
+                    .with_label("expected one character")
+                    .with_note("use a string literal for text of any other length"),
+                // Point at just the escape, rather than the whole literal.
+                LexError::InvalidEscape { escape, offset } => {
+                    let label = if escape.starts_with("\\x") {
+                        "expected two hex digits, as in `\\x41`"
+                    } else if escape.starts_with("\\u") {
+                        "expected hex digits in braces, as in `\\u{1F600}`"
+                    } else {
+                        "unknown escape"
+                    };
+                    Diagnostic::error(err.to_string(), escape_span(*span, escape, *offset))
+                        .with_label(label)
+                        .with_note(
+                            r#"supported escapes are `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, `\{`, `\}`, `\xNN` and `\u{NNNN}`"#,
+                        )
+                }
+                LexError::EscapeOutOfRange { escape, offset } => {
+                    Diagnostic::error(err.to_string(), escape_span(*span, escape, *offset))
+                        .with_label("not a valid character")
+                        .with_note(
+                            r#"`\x` escapes go up to `\x7F`, and `\u{...}` escapes up to `\u{10FFFF}` except for the surrogates `\u{D800}` to `\u{DFFF}`"#,
+                        )
+                }
+                LexError::IntegerOverflow => diagnostic
+                    .with_label("doesn't fit in `Int`")
+                    .with_note(format!("the largest `Int` is {}", i64::MAX)),
+                LexError::StrayCharacters(_) => diagnostic.with_label("not valid here"),
+                // The comment runs to the end of the input, so point at where
+                // it starts instead.
+                LexError::UnterminatedComment => Diagnostic::error(
+                    err.to_string(),
+                    Span::new(span.file, span.start, span.start + 2),
+                )
+                .with_label("comment opened here")
+                .with_note("block comments nest, so every `/*` needs its own `*/`"),
+            },
+        }
+    }
+}
+
+/// The span of an escape sequence `offset` bytes into the literal at `literal`.
+fn escape_span(literal: Span, escape: &str, offset: usize) -> Span {
+    let start = literal.start + offset;
+    Span::new(literal.file, start, start + escape.len())
+}
+
 type ParseResult<T> = Result<T, ParseError>;
 
 pub struct Parser<I>
 where
-    I: Iterator<Item = Token>,
+    I: Iterator<Item = Spanned<Token>>,
 {
     tokens: Peekable<I>,
+    /// Halves of a token broken up by `split_closing_angle`, which come
+    /// before anything left in `tokens`. The next one is at the end.
+    split: Vec<Spanned<Token>>,
+    /// Span of the most recently consumed token.
+    prev_span: Span,
+    /// Errors that the parser has already recovered from.
+    errors: Vec<ParseError>,
+    /// Whether a `{` ends the expression being parsed, as in a `match` scrutinee.
+    brace_ends_expr: bool,
+    /// Lines of the `///` comments skipped since the last token was consumed.
+    docs: Vec<String>,
+    /// Lines of the `//!` comments skipped since the last token was consumed.
+    inner_docs: Vec<String>,
+    /// Whether the next `{` opens the body of the item being parsed, so that
+    /// the `//!` comments just inside it document the item.
+    opens_body: bool,
+    /// The `//!` comments from the start of that body.
+    body_docs: Vec<String>,
 }
 
 impl<'input> Parser<Lexer<'input>> {
     pub fn new(input: &'input str) -> Parser<Lexer<'input>> {
+        Self::with_file(input, FileId::default())
+    }
+
+    /// Creates a parser whose node spans point into `file`.
+    pub fn with_file(input: &'input str, file: FileId) -> Parser<Lexer<'input>> {
         Parser {
-            tokens: Lexer::new(input).peekable(),
+            tokens: Lexer::with_file(input, file).peekable(),
+            split: Vec::new(),
+            prev_span: Span::new(file, 0, 0),
+            errors: Vec::new(),
+            brace_ends_expr: false,
+            docs: Vec::new(),
+            inner_docs: Vec::new(),
+            opens_body: false,
+            body_docs: Vec::new(),
         }
     }
 }
 
-impl<I: Iterator<Item = Token>> Parser<I> {
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
     /// Look-ahead one token and see what kind of token it is.
     pub(crate) fn peek(&mut self) -> &Token {
-        self.tokens.peek().unwrap_or(&Token::Eof)
+        if !self.split.is_empty() {
+            return &self.split.last().unwrap().node;
+        }
+
+        self.skip_ignored();
+        self.tokens
+            .peek()
+            .map(|token| &token.node)
+            .unwrap_or(&Token::Eof)
+    }
+
+    /// Span of the next token, or an empty span after the last one at the end of input.
+    pub(crate) fn peek_span(&mut self) -> Span {
+        if let Some(split) = self.split.last() {
+            return split.span;
+        }
+
+        self.skip_ignored();
+        match self.tokens.peek() {
+            Some(token) => token.span,
+            None => Span {
+                start: self.prev_span.end,
+                ..self.prev_span
+            },
+        }
     }
 
     /// Check if the next token is the same variant as another token.
@@ -69,20 +214,136 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     /// Get the next token.
-    pub(crate) fn next(&mut self) -> Option<Token> {
-        self.tokens.next()
+    ///
+    /// Any lexer error the token carries is recorded, so that it's reported
+    /// no matter where in the grammar the token turns up.
+    pub(crate) fn next(&mut self) -> Option<Spanned<Token>> {
+        let token = match self.split.pop() {
+            Some(token) => token,
+            None => {
+                self.skip_ignored();
+                self.tokens.next()?
+            }
+        };
+        self.prev_span = token.span;
+        self.docs.clear();
+        self.inner_docs.clear();
+        if let Token::Error(err) = &token.node {
+            self.errors.push(ParseError::Lex(err.clone(), token.span));
+        }
+        if token.node == Token::LBrace && mem::take(&mut self.opens_body) {
+            self.skip_ignored();
+            self.body_docs = mem::take(&mut self.inner_docs);
+        }
+        Some(token)
+    }
+
+    /// Skips over the tokens that no part of the grammar uses: doc comments,
+    /// which are kept until the next token is consumed for whatever they
+    /// document to take, and stray characters and unterminated comments,
+    /// whose errors are recorded.
+    fn skip_ignored(&mut self) {
+        while let Some(token) = self.tokens.next_if(|token| {
+            matches!(
+                token.node,
+                Token::DocComment(_)
+                    | Token::InnerDocComment(_)
+                    | Token::Error(LexError::StrayCharacters(_) | LexError::UnterminatedComment)
+            )
+        }) {
+            match token.node {
+                Token::DocComment(text) => self.docs.push(text),
+                Token::InnerDocComment(text) => self.inner_docs.push(text),
+                Token::Error(err) => {
+                    self.prev_span = token.span;
+                    self.errors.push(ParseError::Lex(err, token.span));
+                }
+                _ => unreachable!("only ignored tokens are skipped"),
+            }
+        }
+    }
+
+    /// Takes the lines of the `///` comments right before the next token.
+    pub(crate) fn take_docs(&mut self) -> Vec<String> {
+        self.skip_ignored();
+        mem::take(&mut self.docs)
+    }
+
+    /// Takes the `//!` comments from the start of the body that the next `{`
+    /// opens, after `parse` has parsed it. Taken even if `parse` fails, so
+    /// that they aren't left for a later item.
+    pub(crate) fn body_docs<T>(
+        &mut self,
+        docs: &mut Vec<String>,
+        parse: impl FnOnce(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<T> {
+        self.opens_body = self.at(&Token::LBrace);
+        let result = parse(self);
+        self.opens_body = false;
+        docs.append(&mut self.body_docs);
+        result
+    }
+
+    /// Splits a `>` off the front of a `>>` or `>=` token, so that the `>` can
+    /// close a list of generics like `Option<Box<T>>` or `x: Option<T>= y`.
+    pub(crate) fn split_closing_angle(&mut self) {
+        let rest = match self.peek() {
+            Token::ComposeRight => Token::RAngle,
+            Token::Geq => Token::Eq,
+            _ => return,
+        };
+
+        let span = self.next().unwrap().span;
+        self.split.push(Spanned::new(
+            rest,
+            Span::new(span.file, span.start + 1, span.end),
+        ));
+        self.split.push(Spanned::new(
+            Token::RAngle,
+            Span::new(span.file, span.start, span.start + 1),
+        ));
+    }
+
+    /// The span from the start of `start` to the end of the most recently consumed token.
+    pub(crate) fn span_from(&self, start: Span) -> Span {
+        Span::new(start.file, start.start, self.prev_span.end.max(start.start))
+    }
+
+    /// Fails unless all of the input has been parsed, for callers parsing a
+    /// single item or expression.
+    pub fn end(&mut self) -> ParseResult<()> {
+        if self.at(&Token::Eof) {
+            return Ok(());
+        }
+        let span = self.peek_span();
+        Err(ParseError::UnexpectedToken(
+            self.peek().to_string(),
+            Some("end of input".into()),
+            span,
+        ))
+    }
+
+    /// Takes the errors the parser has recovered from so far.
+    ///
+    /// `item` and `expression` recover from errors inside blocks and leave an
+    /// error node in their place, so callers should check this after a
+    /// successful parse.
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        mem::take(&mut self.errors)
     }
 
     /// Move forward one token in the input and check
     /// that we pass the kind of token we expect.
-    pub(crate) fn consume(&mut self, expected: &Token) -> ParseResult<()> {
-        let token = self.next().ok_or(ParseError::MissingToken)?;
-        if mem::discriminant(&token) == mem::discriminant(expected) {
-            Ok(())
+    pub(crate) fn consume(&mut self, expected: &Token) -> ParseResult<Span> {
+        let span = self.peek_span();
+        let token = self.next().ok_or(ParseError::MissingToken(span))?;
+        if mem::discriminant(&token.node) == mem::discriminant(expected) {
+            Ok(token.span)
         } else {
             Err(ParseError::MismatchedToken {
                 expected: expected.to_string(),
-                found: token.to_string(),
+                found: token.node.to_string(),
+                span: token.span,
             })
         }
     }
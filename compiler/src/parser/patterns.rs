@@ -0,0 +1,154 @@
+use super::{
+    ParseError, ParseResult, Parser, Spanned, Token,
+    ast::{FieldPattern, Lit, Pattern, PatternKind, VariantPattern},
+};
+
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
+    pub fn pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.peek_span();
+
+        let kind = match self.peek() {
+            Token::Underscore => {
+                self.next();
+                PatternKind::Wildcard
+            }
+            Token::IntLit(_)
+            | Token::FloatLit(_)
+            | Token::StringLit(_)
+            | Token::CharLit(_)
+            | Token::True
+            | Token::False => PatternKind::Literal(match self.next().unwrap().node {
+                Token::IntLit(int) => Lit::Int(int),
+                Token::FloatLit(float) => Lit::Float(float),
+                Token::StringLit(string) => Lit::Str(string),
+                Token::CharLit(char) => Lit::Char(char),
+                Token::True => Lit::Bool(true),
+                Token::False => Lit::Bool(false),
+                _ => unreachable!(),
+            }),
+            Token::Minus => {
+                self.next();
+                PatternKind::Literal(match self.next().map(|token| token.node) {
+                    Some(Token::IntLit(int)) => Lit::Int(-int),
+                    Some(Token::FloatLit(float)) => Lit::Float(-float),
+                    token => {
+                        return Err(ParseError::MismatchedToken {
+                            expected: "number literal".into(),
+                            found: token.unwrap_or(Token::Eof).to_string(),
+                            span: self.prev_span,
+                        });
+                    }
+                })
+            }
+            Token::Mut => {
+                self.next();
+                PatternKind::Binding {
+                    mutable: true,
+                    name: self.ident()?,
+                }
+            }
+            Token::LParen => {
+                self.next();
+                let pattern = self.pattern()?;
+                if self.consume_at(&Token::Comma) {
+                    let mut patterns = vec![pattern];
+                    while !self.at(&Token::RParen) {
+                        patterns.push(self.pattern()?);
+
+                        if !self.consume_at(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.consume(&Token::RParen)?;
+
+                    PatternKind::Tuple(patterns)
+                } else {
+                    self.consume(&Token::RParen)?;
+                    pattern.kind
+                }
+            }
+            Token::LBracket => PatternKind::Array(self.delimited_list(
+                Self::pattern,
+                &Token::LBracket,
+                &Token::RBracket,
+            )?),
+            Token::Ident(_) => {
+                let name = self.ident()?;
+
+                if self.consume_at(&Token::PathSep) {
+                    let variant_name = self.ident()?;
+
+                    let variant = match self.peek() {
+                        Token::LParen => VariantPattern::Tuple(
+                            variant_name,
+                            self.delimited_list(Self::pattern, &Token::LParen, &Token::RParen)?,
+                        ),
+                        Token::LBrace => {
+                            VariantPattern::Struct(variant_name, self.field_patterns()?)
+                        }
+                        _ => VariantPattern::Unit(variant_name),
+                    };
+
+                    PatternKind::Variant {
+                        enum_name: name,
+                        variant,
+                    }
+                } else if self.at(&Token::LBrace) {
+                    PatternKind::Struct {
+                        name,
+                        fields: self.field_patterns()?,
+                    }
+                } else {
+                    PatternKind::Binding {
+                        mutable: false,
+                        name,
+                    }
+                }
+            }
+            token => {
+                return Err(ParseError::UnexpectedToken(
+                    token.to_string(),
+                    Some("start of pattern".into()),
+                    start,
+                ));
+            }
+        };
+
+        Ok(Pattern {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses `{ name: pattern, shorthand, mut shorthand }`.
+    fn field_patterns(&mut self) -> ParseResult<Vec<FieldPattern>> {
+        self.delimited_list(
+            |this| {
+                let start = this.peek_span();
+
+                let mutable = this.consume_at(&Token::Mut);
+                let name = this.ident()?;
+
+                let pattern = if !mutable && this.consume_at(&Token::Colon) {
+                    this.pattern()?
+                } else {
+                    Pattern {
+                        kind: PatternKind::Binding {
+                            mutable,
+                            name: name.clone(),
+                        },
+                        span: this.span_from(start),
+                    }
+                };
+
+                Ok(FieldPattern {
+                    name,
+                    pattern,
+                    span: this.span_from(start),
+                })
+            },
+            &Token::LBrace,
+            &Token::RBrace,
+        )
+    }
+}
@@ -0,0 +1,39 @@
+use super::{Parser, Spanned, Token};
+
+impl<I: Iterator<Item = Spanned<Token>>> Parser<I> {
+    /// Skips tokens until the start of the next top-level item.
+    pub(super) fn synchronize_item(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Token::Eof => return,
+                Token::Fn | Token::Const | Token::Struct | Token::Enum if depth == 0 => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    /// Skips tokens until the end of the current expression in a block,
+    /// stopping before the `;` or `}` that ends it.
+    ///
+    /// Returns `false` if the input ran out or a new item started first.
+    pub(super) fn synchronize_block(&mut self) -> bool {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Token::Eof => return false,
+                Token::Fn | Token::Const | Token::Struct | Token::Enum if depth == 0 => {
+                    return false;
+                }
+                Token::Semicolon | Token::RBrace if depth == 0 => return true,
+                Token::LBrace | Token::LParen | Token::LBracket => depth += 1,
+                Token::RBrace | Token::RParen | Token::RBracket => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.next();
+        }
+    }
+}
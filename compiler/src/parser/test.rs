@@ -1,19 +1,29 @@
 use super::Parser;
-use super::ast::{Ast, Binding, Bop, Expr, Field, Item, Lit, Type, Unop, Variant};
+use super::ast::{
+    Ast, Binding, Bop, Expr, ExprKind, Field, FieldInit, FieldPattern, Item, ItemKind, Lit,
+    MatchArm, PatternKind, StringPart, TypeKind, Unop, VariantKind, VariantPattern,
+};
+use crate::span::Span;
 
 fn parse_expr(input: &str) -> Expr {
     let mut parser = Parser::new(input);
-    parser.expression().unwrap()
+    let expr = parser.expression().unwrap();
+    assert!(parser.take_errors().is_empty());
+    expr
 }
 
 fn parse_item(input: &str) -> Item {
     let mut parser = Parser::new(input);
-    parser.item().unwrap()
+    let item = parser.item().unwrap();
+    assert!(parser.take_errors().is_empty());
+    item
 }
 
 fn parse_ast(input: &str) -> Ast {
     let mut parser = Parser::new(input);
-    parser.file().unwrap()
+    let (ast, errors) = parser.file();
+    assert!(errors.is_empty(), "{errors:?}");
+    ast
 }
 
 #[test]
@@ -51,26 +61,26 @@ fn parse_lit_expressions() {
     );
 
     let expr = parse_expr("foo");
-    assert_eq!(expr, Expr::Ident("foo".into()));
+    assert_eq!(expr.kind, ExprKind::Ident("foo".into()));
 }
 
 #[test]
 fn parse_unop_expressions() {
     let expr = parse_expr("!  is_visible");
     assert_eq!(
-        expr,
-        Expr::UnaryOp {
+        expr.kind,
+        ExprKind::UnaryOp {
             op: Unop::Not,
-            expr: Expr::Ident("is_visible".into()).into(),
+            expr: ExprKind::Ident("is_visible".into()).into(),
         }
     );
 
     let expr = parse_expr("-(-13)");
     assert_eq!(
-        expr,
-        Expr::UnaryOp {
+        expr.kind,
+        ExprKind::UnaryOp {
             op: Unop::Neg,
-            expr: Expr::UnaryOp {
+            expr: ExprKind::UnaryOp {
                 op: Unop::Neg,
                 expr: Lit::Int(13).into(),
             }
@@ -79,97 +89,165 @@ fn parse_unop_expressions() {
     );
 }
 
+#[test]
+fn parse_spans() {
+    let expr = parse_expr("foo(1, bar) + -x.y");
+    assert_eq!((expr.span.start, expr.span.end), (0, 18));
+
+    let ExprKind::BinaryOp { lhs, rhs, .. } = expr.kind else {
+        panic!("expected binary op");
+    };
+    assert_eq!((lhs.span.start, lhs.span.end), (0, 11));
+    assert_eq!((rhs.span.start, rhs.span.end), (14, 18));
+
+    let item = parse_item("fn foo(mut a: Int) -> a");
+    assert_eq!((item.span.start, item.span.end), (0, 23));
+
+    let ItemKind::Function { params, .. } = item.kind else {
+        panic!("expected function");
+    };
+    assert_eq!((params[0].span.start, params[0].span.end), (7, 17));
+    let ty = params[0].type_annotation.as_ref().unwrap();
+    assert_eq!((ty.span.start, ty.span.end), (14, 17));
+}
+
 #[test]
 fn parse_binop_expressions() {
     let expr = parse_expr("4 + 2 * 3");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Add,
             lhs: Lit::Int(4).into(),
-            rhs: Box::new(Expr::BinaryOp {
-                op: Bop::Mul,
-                lhs: Lit::Int(2).into(),
-                rhs: Lit::Int(3).into()
-            })
+            rhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Mul,
+                    lhs: Lit::Int(2).into(),
+                    rhs: Lit::Int(3).into()
+                }
+                .into()
+            )
         }
     );
 
     let expr = parse_expr("4 * 2 + 3");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Add,
-            lhs: Box::new(Expr::BinaryOp {
-                op: Bop::Mul,
-                lhs: Lit::Int(4).into(),
-                rhs: Lit::Int(2).into()
-            }),
+            lhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Mul,
+                    lhs: Lit::Int(4).into(),
+                    rhs: Lit::Int(2).into()
+                }
+                .into()
+            ),
             rhs: Lit::Int(3).into(),
         }
     );
 
     let expr = parse_expr("4 - 2 - 3");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Sub,
-            lhs: Box::new(Expr::BinaryOp {
-                op: Bop::Sub,
-                lhs: Lit::Int(4).into(),
-                rhs: Lit::Int(2).into()
-            }),
+            lhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Sub,
+                    lhs: Lit::Int(4).into(),
+                    rhs: Lit::Int(2).into()
+                }
+                .into()
+            ),
             rhs: Lit::Int(3).into(),
         }
     );
 
     let expr = parse_expr("4 ** 2 ** 3");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Exp,
             lhs: Lit::Int(4).into(),
-            rhs: Box::new(Expr::BinaryOp {
-                op: Bop::Exp,
-                lhs: Lit::Int(2).into(),
-                rhs: Lit::Int(3).into()
-            })
+            rhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Exp,
+                    lhs: Lit::Int(2).into(),
+                    rhs: Lit::Int(3).into()
+                }
+                .into()
+            )
         }
     );
 
     let expr = parse_expr("4 ^ 2 ^ 3");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Xor,
-            lhs: Box::new(Expr::BinaryOp {
-                op: Bop::Xor,
-                lhs: Lit::Int(4).into(),
-                rhs: Lit::Int(2).into()
-            }),
+            lhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Xor,
+                    lhs: Lit::Int(4).into(),
+                    rhs: Lit::Int(2).into()
+                }
+                .into()
+            ),
             rhs: Lit::Int(3).into(),
         }
     );
+
+    let expr = parse_expr("x + 1 |> f >> g << h");
+    assert_eq!(
+        expr.kind,
+        ExprKind::BinaryOp {
+            op: Bop::Pipeline,
+            lhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::Add,
+                    lhs: ExprKind::Ident("x".into()).into(),
+                    rhs: Lit::Int(1).into()
+                }
+                .into()
+            ),
+            rhs: Box::new(
+                ExprKind::BinaryOp {
+                    op: Bop::ComposeLeft,
+                    lhs: Box::new(
+                        ExprKind::BinaryOp {
+                            op: Bop::ComposeRight,
+                            lhs: ExprKind::Ident("f".into()).into(),
+                            rhs: ExprKind::Ident("g".into()).into()
+                        }
+                        .into()
+                    ),
+                    rhs: ExprKind::Ident("h".into()).into()
+                }
+                .into()
+            )
+        }
+    );
 }
 
 #[test]
 fn parse_compound_expressions() {
     let expr = parse_expr("bar (  x, 2)");
     assert_eq!(
-        expr,
-        Expr::FnCall {
-            fun: Expr::Ident("bar".into()).into(),
-            args: vec![Expr::Ident("x".into()), Lit::Int(2).into(),],
+        expr.kind,
+        ExprKind::FnCall {
+            fun: ExprKind::Ident("bar".into()).into(),
+            args: vec![ExprKind::Ident("x".into()).into(), Lit::Int(2).into(),],
         }
     );
 
     let expr = parse_expr("if (0.5) foo()");
     assert_eq!(
-        expr,
-        Expr::If {
+        expr.kind,
+        ExprKind::If {
             cond: Lit::Float(0.5).into(),
-            th: Expr::FnCall {
-                fun: Expr::Ident("foo".into()).into(),
+            th: ExprKind::FnCall {
+                fun: ExprKind::Ident("foo".into()).into(),
                 args: Vec::new()
             }
             .into(),
@@ -179,39 +257,45 @@ fn parse_compound_expressions() {
 
     let expr = parse_expr("if (0.5) foo else bar");
     assert_eq!(
-        expr,
-        Expr::If {
+        expr.kind,
+        ExprKind::If {
             cond: Lit::Float(0.5).into(),
-            th: Expr::Ident("foo".into()).into(),
-            el: Some(Expr::Ident("bar".into()).into())
+            th: ExprKind::Ident("foo".into()).into(),
+            el: Some(ExprKind::Ident("bar".into()).into())
         }
     );
 
     let expr = parse_expr("(|a, b: Int| -> a + b)(1, 2)");
     assert_eq!(
-        expr,
-        Expr::FnCall {
-            fun: Expr::Lambda {
+        expr.kind,
+        ExprKind::FnCall {
+            fun: ExprKind::Lambda {
                 params: vec![
                     Binding {
                         mutable: false,
                         name: "a".into(),
-                        type_annotation: None
+                        type_annotation: None,
+                        span: Span::default(),
                     },
                     Binding {
                         mutable: false,
                         name: "b".into(),
-                        type_annotation: Some(Type::Ident {
-                            name: "Int".into(),
-                            generics: vec![]
-                        })
+                        type_annotation: Some(
+                            TypeKind::Ident {
+                                name: "Int".into(),
+                                generics: vec![]
+                            }
+                            .into()
+                        ),
+                        span: Span::default(),
                     }
-                ],
+                ]
+                .into(),
                 return_type: None,
-                body: Expr::BinaryOp {
+                body: ExprKind::BinaryOp {
                     op: Bop::Add,
-                    lhs: Expr::Ident("a".into()).into(),
-                    rhs: Expr::Ident("b".into()).into()
+                    lhs: ExprKind::Ident("a".into()).into(),
+                    rhs: ExprKind::Ident("b".into()).into()
                 }
                 .into()
             }
@@ -220,17 +304,26 @@ fn parse_compound_expressions() {
         }
     );
 
+    let expr = parse_expr("sum(_, 1)");
+    assert_eq!(
+        expr.kind,
+        ExprKind::FnCall {
+            fun: ExprKind::Ident("sum".into()).into(),
+            args: vec![ExprKind::Placeholder.into(), Lit::Int(1).into()],
+        }
+    );
+
     let expr = parse_expr("[1, 2, 3][1-1]");
     assert_eq!(
-        expr,
-        Expr::Index {
+        expr.kind,
+        ExprKind::Index {
             arr: Lit::Array(vec![
                 Lit::Int(1).into(),
                 Lit::Int(2).into(),
                 Lit::Int(3).into()
             ])
             .into(),
-            index: Expr::BinaryOp {
+            index: ExprKind::BinaryOp {
                 op: Bop::Sub,
                 lhs: Lit::Int(1).into(),
                 rhs: Lit::Int(1).into()
@@ -241,9 +334,9 @@ fn parse_compound_expressions() {
 
     let expr = parse_expr("self._0");
     assert_eq!(
-        expr,
-        Expr::FieldAccess {
-            base: Expr::Ident("self".into()).into(),
+        expr.kind,
+        ExprKind::FieldAccess {
+            base: ExprKind::Ident("self".into()).into(),
             field: "_0".into()
         }
     );
@@ -253,18 +346,19 @@ fn parse_compound_expressions() {
 fn parse_var_expresssions() {
     let expr = parse_expr("let x = 7 + sin(3.);");
     assert_eq!(
-        expr,
-        Expr::Let {
+        expr.kind,
+        ExprKind::Let {
             binding: Binding {
                 mutable: false,
                 name: "x".into(),
-                type_annotation: None
+                type_annotation: None,
+                span: Span::default(),
             },
-            value: Expr::BinaryOp {
+            value: ExprKind::BinaryOp {
                 op: Bop::Add,
                 lhs: Lit::Int(7).into(),
-                rhs: Expr::FnCall {
-                    fun: Expr::Ident("sin".into()).into(),
+                rhs: ExprKind::FnCall {
+                    fun: ExprKind::Ident("sin".into()).into(),
                     args: vec![Lit::Float(3.0).into()]
                 }
                 .into()
@@ -275,15 +369,19 @@ fn parse_var_expresssions() {
 
     let expr = parse_expr("let mut y: Int = 7");
     assert_eq!(
-        expr,
-        Expr::Let {
+        expr.kind,
+        ExprKind::Let {
             binding: Binding {
                 mutable: true,
                 name: "y".into(),
-                type_annotation: Some(Type::Ident {
-                    name: "Int".into(),
-                    generics: vec![]
-                })
+                type_annotation: Some(
+                    TypeKind::Ident {
+                        name: "Int".into(),
+                        generics: vec![]
+                    }
+                    .into()
+                ),
+                span: Span::default(),
             },
             value: Lit::Int(7).into()
         }
@@ -291,14 +389,14 @@ fn parse_var_expresssions() {
 
     let expr = parse_expr("y = 3 + 7 * 0.5");
     assert_eq!(
-        expr,
-        Expr::BinaryOp {
+        expr.kind,
+        ExprKind::BinaryOp {
             op: Bop::Assign,
-            lhs: Expr::Ident("y".into()).into(),
-            rhs: Expr::BinaryOp {
+            lhs: ExprKind::Ident("y".into()).into(),
+            rhs: ExprKind::BinaryOp {
                 op: Bop::Add,
                 lhs: Lit::Int(3).into(),
-                rhs: Expr::BinaryOp {
+                rhs: ExprKind::BinaryOp {
                     op: Bop::Mul,
                     lhs: Lit::Int(7).into(),
                     rhs: Lit::Float(0.5).into()
@@ -308,6 +406,17 @@ fn parse_var_expresssions() {
             .into()
         }
     );
+
+    // `>=` is split so its `>` can close the generics.
+    let expr = parse_expr("let z: Option<Box<Int>>= none");
+    let ExprKind::Let { binding, value } = expr.kind else {
+        panic!("expected let");
+    };
+    assert_eq!(
+        binding.type_annotation.unwrap().to_string(),
+        "Option<Box<Int>>"
+    );
+    assert_eq!(value.kind, ExprKind::Ident("none".into()));
 }
 
 #[test]
@@ -324,73 +433,269 @@ fn parse_block_expressions() {
     }",
     );
     assert_eq!(
-        expr,
-        Expr::Block {
+        expr.kind,
+        ExprKind::Block {
             exprs: vec![
-                Expr::Let {
+                ExprKind::Let {
                     binding: Binding {
                         mutable: true,
                         name: "y".into(),
-                        type_annotation: None
+                        type_annotation: None,
+                        span: Span::default(),
                     },
                     value: Lit::Int(5).into()
-                },
-                Expr::BinaryOp {
+                }
+                .into(),
+                ExprKind::BinaryOp {
                     op: Bop::Sub,
-                    lhs: Expr::BinaryOp {
+                    lhs: ExprKind::BinaryOp {
                         op: Bop::Add,
                         lhs: Lit::Int(3).into(),
                         rhs: Lit::Int(1).into()
                     }
                     .into(),
                     rhs: Lit::Int(2).into()
-                },
-                Expr::BinaryOp {
+                }
+                .into(),
+                ExprKind::BinaryOp {
                     op: Bop::Assign,
-                    lhs: Expr::Ident("y".into()).into(),
+                    lhs: ExprKind::Ident("y".into()).into(),
                     rhs: Lit::Int(1).into()
-                },
-                Expr::If {
-                    cond: Expr::BinaryOp {
+                }
+                .into(),
+                ExprKind::If {
+                    cond: ExprKind::BinaryOp {
                         op: Bop::Lt,
-                        lhs: Expr::Ident("y".into()).into(),
+                        lhs: ExprKind::Ident("y".into()).into(),
                         rhs: Lit::Int(3).into()
                     }
                     .into(),
-                    th: Expr::Block {
+                    th: ExprKind::Block {
                         exprs: vec![
-                            Expr::Let {
+                            ExprKind::Let {
                                 binding: Binding {
                                     mutable: false,
                                     name: "a".into(),
-                                    type_annotation: None
+                                    type_annotation: None,
+                                    span: Span::default(),
                                 },
                                 value: Lit::Int(5).into()
-                            },
-                            Expr::Ident("a".to_string())
+                            }
+                            .into(),
+                            ExprKind::Ident("a".to_string()).into()
                         ],
                         trailing: true
                     }
                     .into(),
                     el: Some(Lit::Int(32).into())
                 }
+                .into()
             ],
             trailing: false
         }
     );
 }
 
+#[test]
+fn parse_struct_and_enum_literals() {
+    let field = |name: &str, value: Expr| FieldInit {
+        name: name.into(),
+        value,
+        span: Span::default(),
+    };
+
+    let expr = parse_expr("Point { x: 1, y }");
+    assert_eq!(
+        expr,
+        Lit::Struct {
+            name: "Point".into(),
+            variant: None,
+            fields: vec![
+                field("x", Lit::Int(1).into()),
+                field("y", ExprKind::Ident("y".into()).into())
+            ]
+        }
+        .into()
+    );
+
+    let expr = parse_expr("Shape::Rect { w: 2.0, h: 1.5 }");
+    assert_eq!(
+        expr,
+        Lit::Struct {
+            name: "Shape".into(),
+            variant: Some("Rect".into()),
+            fields: vec![
+                field("w", Lit::Float(2.0).into()),
+                field("h", Lit::Float(1.5).into())
+            ]
+        }
+        .into()
+    );
+
+    let expr = parse_expr("Shape::Circle(1.0)");
+    assert_eq!(
+        expr.kind,
+        ExprKind::FnCall {
+            fun: ExprKind::Path {
+                enum_name: "Shape".into(),
+                variant: "Circle".into()
+            }
+            .into(),
+            args: vec![Lit::Float(1.0).into()]
+        }
+    );
+
+    let expr = parse_expr("Shape::Empty");
+    assert_eq!(
+        expr.kind,
+        ExprKind::Path {
+            enum_name: "Shape".into(),
+            variant: "Empty".into()
+        }
+    );
+
+    // A struct literal can't directly precede the arms of a match, but is
+    // allowed again inside parentheses and in `if` conditions.
+    let expr = parse_expr("match p { _ -> if (p == Point { x: 1 }) { x } else Point {} }");
+    let ExprKind::Match { scrutinee, arms } = expr.kind else {
+        panic!("expected match");
+    };
+    assert_eq!(scrutinee.kind, ExprKind::Ident("p".into()));
+    let ExprKind::If { cond, th, el } = &arms[0].body.kind else {
+        panic!("expected if");
+    };
+    assert!(matches!(
+        &cond.kind,
+        ExprKind::BinaryOp { rhs, .. } if matches!(rhs.kind, ExprKind::Literal(Lit::Struct { .. }))
+    ));
+    assert!(matches!(th.kind, ExprKind::Block { .. }));
+    assert!(matches!(
+        el.as_ref().unwrap().kind,
+        ExprKind::Literal(Lit::Struct { .. })
+    ));
+
+    let expr = parse_expr("match (Point { x: 1 }) { Point { x } -> x }");
+    assert!(matches!(expr.kind, ExprKind::Match { .. }));
+}
+
+#[test]
+fn parse_match_expressions() {
+    let expr = parse_expr(
+        r#"
+        match shape.kind {
+            Shape::Circle(r) -> r,
+            Shape::Rect { w, h: 0 } -> w,
+            Shape::Empty -> 0,
+            Point { x, mut y } -> y,
+            (_, -1, "s") -> 1,
+            [a, 'c', true] -> a,
+        }
+    "#,
+    );
+
+    let binding = |name: &str| PatternKind::Binding {
+        mutable: false,
+        name: name.into(),
+    };
+    let field = |name: &str, pattern: PatternKind| FieldPattern {
+        name: name.into(),
+        pattern: pattern.into(),
+        span: Span::default(),
+    };
+    let arm = |pattern: PatternKind, body: ExprKind| MatchArm {
+        pattern: pattern.into(),
+        body: body.into(),
+        span: Span::default(),
+    };
+
+    assert_eq!(
+        expr.kind,
+        ExprKind::Match {
+            scrutinee: ExprKind::FieldAccess {
+                base: ExprKind::Ident("shape".into()).into(),
+                field: "kind".into()
+            }
+            .into(),
+            arms: vec![
+                arm(
+                    PatternKind::Variant {
+                        enum_name: "Shape".into(),
+                        variant: VariantPattern::Tuple("Circle".into(), vec![binding("r").into()])
+                    },
+                    ExprKind::Ident("r".into())
+                ),
+                arm(
+                    PatternKind::Variant {
+                        enum_name: "Shape".into(),
+                        variant: VariantPattern::Struct(
+                            "Rect".into(),
+                            vec![
+                                field("w", binding("w")),
+                                field("h", PatternKind::Literal(Lit::Int(0)))
+                            ]
+                        )
+                    },
+                    ExprKind::Ident("w".into())
+                ),
+                arm(
+                    PatternKind::Variant {
+                        enum_name: "Shape".into(),
+                        variant: VariantPattern::Unit("Empty".into())
+                    },
+                    ExprKind::Literal(Lit::Int(0))
+                ),
+                arm(
+                    PatternKind::Struct {
+                        name: "Point".into(),
+                        fields: vec![
+                            field("x", binding("x")),
+                            field(
+                                "y",
+                                PatternKind::Binding {
+                                    mutable: true,
+                                    name: "y".into()
+                                }
+                            )
+                        ]
+                    },
+                    ExprKind::Ident("y".into())
+                ),
+                arm(
+                    PatternKind::Tuple(vec![
+                        PatternKind::Wildcard.into(),
+                        PatternKind::Literal(Lit::Int(-1)).into(),
+                        PatternKind::Literal(Lit::Str("s".into())).into()
+                    ]),
+                    ExprKind::Literal(Lit::Int(1))
+                ),
+                arm(
+                    PatternKind::Array(vec![
+                        binding("a").into(),
+                        PatternKind::Literal(Lit::Char('c')).into(),
+                        PatternKind::Literal(Lit::Bool(true)).into()
+                    ]),
+                    ExprKind::Ident("a".into())
+                ),
+            ]
+        }
+    );
+
+    let expr = parse_expr("match x { _ -> 1 } + 2");
+    assert!(matches!(expr.kind, ExprKind::BinaryOp { op: Bop::Add, .. }));
+}
+
 #[test]
 fn parse_const_items() {
     let item = parse_item(r#"const HELLO_WORLD: Str = "Hello, World!""#);
     assert_eq!(
-        item,
-        Item::Const {
+        item.kind,
+        ItemKind::Const {
             ident: "HELLO_WORLD".into(),
-            ty: Type::Ident {
+            ty: TypeKind::Ident {
                 name: "Str".into(),
                 generics: vec![]
-            },
+            }
+            .into(),
             value: Lit::Str("Hello, World!".into()).into()
         }
     );
@@ -407,30 +712,42 @@ fn parse_struct_items() {
     "#,
     );
     assert_eq!(
-        item,
-        Item::Struct {
+        item.kind,
+        ItemKind::Struct {
             name: "Foo".into(),
             generic_params: vec!["T".into(), "U".into()],
             fields: vec![
                 Field {
                     name: "x".into(),
-                    ty: Type::Ident {
+                    ty: TypeKind::Ident {
                         name: "Str".into(),
                         generics: vec![]
                     }
+                    .into(),
+                    docs: Vec::new(),
+                    span: Span::default(),
                 },
                 Field {
                     name: "bar".into(),
-                    ty: Type::Ident {
+                    ty: TypeKind::Ident {
                         name: "Bar".into(),
-                        generics: vec![Type::Ident {
-                            name: "Baz".into(),
-                            generics: vec![Type::Ident {
-                                name: "T".into(),
-                                generics: vec![]
-                            }]
-                        }]
+                        generics: vec![
+                            TypeKind::Ident {
+                                name: "Baz".into(),
+                                generics: vec![
+                                    TypeKind::Ident {
+                                        name: "T".into(),
+                                        generics: vec![]
+                                    }
+                                    .into()
+                                ]
+                            }
+                            .into()
+                        ]
                     }
+                    .into(),
+                    docs: Vec::new(),
+                    span: Span::default(),
                 }
             ]
         }
@@ -449,38 +766,49 @@ fn parse_enum_items() {
     "#,
     );
     assert_eq!(
-        item,
-        Item::Enum {
+        item.kind,
+        ItemKind::Enum {
             name: "Foo".into(),
             generic_params: vec![],
             variants: vec![
-                Variant::Unit("X".into()),
-                Variant::Tuple(
+                VariantKind::Unit("X".into()).into(),
+                VariantKind::Tuple(
                     "Y".into(),
-                    vec![Type::Ident {
-                        name: "Bar".into(),
-                        generics: vec![]
-                    }]
-                ),
-                Variant::Struct(
+                    vec![
+                        TypeKind::Ident {
+                            name: "Bar".into(),
+                            generics: vec![]
+                        }
+                        .into()
+                    ]
+                )
+                .into(),
+                VariantKind::Struct(
                     "Z".into(),
                     vec![
                         Field {
                             name: "baz".into(),
-                            ty: Type::Ident {
+                            ty: TypeKind::Ident {
                                 name: "Baz".into(),
                                 generics: vec![]
                             }
+                            .into(),
+                            docs: Vec::new(),
+                            span: Span::default(),
                         },
                         Field {
                             name: "fizz".into(),
-                            ty: Type::Ident {
+                            ty: TypeKind::Ident {
                                 name: "Buzz".into(),
                                 generics: vec![]
                             }
+                            .into(),
+                            docs: Vec::new(),
+                            span: Span::default(),
                         }
                     ]
-                ),
+                )
+                .into(),
             ]
         }
     )
@@ -494,30 +822,36 @@ fn parse_function_items() {
     "#,
     );
     assert_eq!(
-        item,
-        Item::Function {
+        item.kind,
+        ItemKind::Function {
             name: "foo".into(),
             params: vec![
                 Binding {
                     mutable: true,
                     name: "a".into(),
-                    type_annotation: None
+                    type_annotation: None,
+                    span: Span::default(),
                 },
                 Binding {
                     mutable: false,
                     name: "b".into(),
-                    type_annotation: Some(Type::Ident {
-                        name: "Int".into(),
-                        generics: vec![]
-                    })
+                    type_annotation: Some(
+                        TypeKind::Ident {
+                            name: "Int".into(),
+                            generics: vec![]
+                        }
+                        .into()
+                    ),
+                    span: Span::default(),
                 }
             ],
             return_type: None,
-            body: Expr::BinaryOp {
+            body: ExprKind::BinaryOp {
                 op: Bop::Add,
-                lhs: Expr::Ident("a".into()).into(),
-                rhs: Expr::Ident("b".into()).into()
+                lhs: ExprKind::Ident("a".into()).into(),
+                rhs: ExprKind::Ident("b".into()).into()
             }
+            .into()
         }
     )
 }
@@ -543,105 +877,129 @@ fn parse_file() {
     );
 
     assert_eq!(
-        items[0],
-        Item::Function {
+        items[0].kind,
+        ItemKind::Function {
             name: "wow_we_did_it".into(),
             params: vec![
                 Binding {
                     mutable: true,
                     name: "x".into(),
-                    type_annotation: None
+                    type_annotation: None,
+                    span: Span::default(),
                 },
                 Binding {
                     mutable: false,
                     name: "bar".into(),
-                    type_annotation: Some(Type::Ident {
-                        name: "Bar".into(),
-                        generics: vec![
-                            Type::Ident {
-                                name: "Baz".into(),
-                                generics: vec![Type::Ident {
-                                    name: "T".into(),
+                    type_annotation: Some(
+                        TypeKind::Ident {
+                            name: "Bar".into(),
+                            generics: vec![
+                                TypeKind::Ident {
+                                    name: "Baz".into(),
+                                    generics: vec![
+                                        TypeKind::Ident {
+                                            name: "T".into(),
+                                            generics: vec![],
+                                        }
+                                        .into()
+                                    ],
+                                }
+                                .into(),
+                                TypeKind::Ident {
+                                    name: "U".into(),
                                     generics: vec![],
-                                }],
-                            },
-                            Type::Ident {
-                                name: "U".into(),
-                                generics: vec![],
-                            }
-                        ],
-                    })
+                                }
+                                .into()
+                            ],
+                        }
+                        .into()
+                    ),
+                    span: Span::default(),
                 }
             ],
-            return_type: Some(Type::Fn {
-                params: vec![Type::Ident {
-                    name: "Int".into(),
-                    generics: vec![]
-                }],
-                result: Type::Ident {
-                    name: "Int".into(),
-                    generics: vec![]
+            return_type: Some(
+                TypeKind::Fn {
+                    params: vec![
+                        TypeKind::Ident {
+                            name: "Int".into(),
+                            generics: vec![]
+                        }
+                        .into()
+                    ],
+                    result: TypeKind::Ident {
+                        name: "Int".into(),
+                        generics: vec![]
+                    }
+                    .into()
                 }
                 .into()
-            }),
-            body: Expr::Block {
+            ),
+            body: ExprKind::Block {
                 exprs: vec![
-                    Expr::Let {
+                    ExprKind::Let {
                         binding: Binding {
                             mutable: true,
                             name: "x".into(),
-                            type_annotation: Some(Type::Tuple(vec![
-                                Type::Ident {
-                                    name: "Float".into(),
-                                    generics: vec![]
-                                },
-                                Type::Ident {
-                                    name: "T".into(),
-                                    generics: vec![]
-                                }
-                            ]))
+                            type_annotation: Some(
+                                TypeKind::Tuple(vec![
+                                    TypeKind::Ident {
+                                        name: "Float".into(),
+                                        generics: vec![]
+                                    }
+                                    .into(),
+                                    TypeKind::Ident {
+                                        name: "T".into(),
+                                        generics: vec![]
+                                    }
+                                    .into()
+                                ])
+                                .into()
+                            ),
+                            span: Span::default(),
                         },
-                        value: Expr::BinaryOp {
+                        value: ExprKind::BinaryOp {
                             op: Bop::Add,
-                            lhs: Expr::UnaryOp {
+                            lhs: ExprKind::UnaryOp {
                                 op: Unop::Neg,
                                 expr: Lit::Float(7.0).into()
                             }
                             .into(),
-                            rhs: Expr::FnCall {
-                                fun: Expr::Ident("sin".into()).into(),
-                                args: vec![Expr::Ident("y".into())]
+                            rhs: ExprKind::FnCall {
+                                fun: ExprKind::Ident("sin".into()).into(),
+                                args: vec![ExprKind::Ident("y".into()).into()]
                             }
                             .into()
                         }
                         .into()
-                    },
-                    Expr::BinaryOp {
+                    }
+                    .into(),
+                    ExprKind::BinaryOp {
                         op: Bop::Assign,
-                        lhs: Expr::Ident("x".into()).into(),
-                        rhs: Expr::If {
-                            cond: Expr::BinaryOp {
+                        lhs: ExprKind::Ident("x".into()).into(),
+                        rhs: ExprKind::If {
+                            cond: ExprKind::BinaryOp {
                                 op: Bop::Lt,
-                                lhs: Expr::Ident("bar".into()).into(),
+                                lhs: ExprKind::Ident("bar".into()).into(),
                                 rhs: Lit::Int(3).into()
                             }
                             .into(),
-                            th: Expr::Block {
+                            th: ExprKind::Block {
                                 exprs: vec![
-                                    Expr::Let {
+                                    ExprKind::Let {
                                         binding: Binding {
                                             mutable: false,
                                             name: "baz".into(),
-                                            type_annotation: None
+                                            type_annotation: None,
+                                            span: Span::default(),
                                         },
-                                        value: Expr::BinaryOp {
+                                        value: ExprKind::BinaryOp {
                                             op: Bop::Add,
-                                            lhs: Expr::FieldAccess {
-                                                base: Expr::Ident("bar".into()).into(),
+                                            lhs: ExprKind::FieldAccess {
+                                                base: ExprKind::Ident("bar".into()).into(),
                                                 field: "value".into()
                                             }
                                             .into(),
-                                            rhs: Expr::BinaryOp {
+                                            rhs: ExprKind::BinaryOp {
                                                 op: Bop::Mul,
                                                 lhs: Lit::Int(2).into(),
                                                 rhs: Lit::Int(4).into()
@@ -649,26 +1007,28 @@ fn parse_file() {
                                             .into()
                                         }
                                         .into()
-                                    },
-                                    Expr::BinaryOp {
+                                    }
+                                    .into(),
+                                    ExprKind::BinaryOp {
                                         op: Bop::Add,
-                                        lhs: Expr::Ident("x".into()).into(),
+                                        lhs: ExprKind::Ident("x".into()).into(),
                                         rhs: Lit::Int(1).into()
                                     }
+                                    .into()
                                 ],
                                 trailing: false
                             }
                             .into(),
                             el: Some(
-                                Expr::If {
-                                    cond: Expr::BinaryOp {
+                                ExprKind::If {
+                                    cond: ExprKind::BinaryOp {
                                         op: Bop::Leq,
-                                        lhs: Expr::Ident("bar".into()).into(),
+                                        lhs: ExprKind::Ident("bar".into()).into(),
                                         rhs: Lit::Int(2).into()
                                     }
                                     .into(),
-                                    th: Expr::FnCall {
-                                        fun: Expr::Ident("fizz".into()).into(),
+                                    th: ExprKind::FnCall {
+                                        fun: ExprKind::Ident("fizz".into()).into(),
                                         args: vec![Lit::Int(3).into(), Lit::Float(5.1).into()]
                                     }
                                     .into(),
@@ -678,49 +1038,246 @@ fn parse_file() {
                             )
                         }
                         .into()
-                    },
+                    }
+                    .into(),
                 ],
                 trailing: true
             }
+            .into()
         }
     );
 
     assert_eq!(
-        items[1],
-        Item::Struct {
+        items[1].kind,
+        ItemKind::Struct {
             name: "Foo".into(),
             generic_params: vec!["T".into(), "U".into(),],
             fields: vec![
                 Field {
                     name: "x".into(),
-                    ty: Type::Ident {
+                    ty: TypeKind::Ident {
                         name: "Str".into(),
                         generics: vec![],
-                    },
+                    }
+                    .into(),
+                    docs: Vec::new(),
+                    span: Span::default(),
                 },
                 Field {
                     name: "bar".into(),
-                    ty: Type::Ident {
+                    ty: TypeKind::Ident {
                         name: "Bar".into(),
                         generics: vec![
-                            Type::Ident {
+                            TypeKind::Ident {
                                 name: "Baz".into(),
-                                generics: vec![Type::Ident {
-                                    name: "T".into(),
-                                    generics: vec![],
-                                }],
-                            },
-                            Type::Array(
-                                Type::Ident {
+                                generics: vec![
+                                    TypeKind::Ident {
+                                        name: "T".into(),
+                                        generics: vec![],
+                                    }
+                                    .into()
+                                ],
+                            }
+                            .into(),
+                            TypeKind::Array(
+                                TypeKind::Ident {
                                     name: "U".into(),
                                     generics: vec![],
                                 }
                                 .into()
                             )
+                            .into()
                         ],
-                    },
+                    }
+                    .into(),
+                    docs: Vec::new(),
+                    span: Span::default(),
                 }
             ]
         }
     );
 }
+
+#[test]
+fn parse_interpolated_strings() {
+    let expr = parse_expr(r#""{a + b}, not {"{c}"}!""#);
+    assert_eq!(
+        expr,
+        ExprKind::Interpolated(vec![
+            StringPart::Expr(
+                ExprKind::BinaryOp {
+                    op: Bop::Add,
+                    lhs: ExprKind::Ident("a".into()).into(),
+                    rhs: ExprKind::Ident("b".into()).into(),
+                }
+                .into()
+            ),
+            StringPart::Text(", not ".into()),
+            StringPart::Expr(
+                ExprKind::Interpolated(vec![StringPart::Expr(ExprKind::Ident("c".into()).into())])
+                    .into()
+            ),
+            StringPart::Text("!".into()),
+        ])
+        .into()
+    );
+
+    let mut parser = Parser::new(r#""{1 2}""#);
+    assert_eq!(
+        parser.expression().unwrap_err().to_string(),
+        "unexpected token `int literal` at end of expression"
+    );
+    let mut parser = Parser::new(r#""{1; 2}""#);
+    assert_eq!(
+        parser.expression().unwrap_err().to_string(),
+        "expected token }, found token ;"
+    );
+
+    // Text that can't be lexed is reported once, and the rest of the string
+    // is still parsed as part of it.
+    let mut parser = Parser::new(r#"f("a {x} \q {y} b", "{z}\q", "\q{w}")"#);
+    let expr = parser.expression().unwrap();
+    assert_eq!(
+        parser
+            .take_errors()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        [r"invalid escape sequence `\q`"; 3]
+    );
+    let ExprKind::FnCall { args, .. } = expr.kind else {
+        panic!("expected a call");
+    };
+    assert!(args.iter().all(|arg| arg.kind == ExprKind::Error));
+}
+
+#[test]
+fn recover_from_errors() {
+    let mut parser = Parser::new(
+        r#"
+        fn broken( -> 1
+        fn ok() -> {
+            let x = ;
+            x + 1;
+            (y ]
+        }
+        const = 3
+        struct Foo { x: Int }
+    "#,
+    );
+    let (ast, errors) = parser.file();
+
+    assert_eq!(errors.len(), 4, "{errors:?}");
+    assert_eq!(ast.len(), 4);
+    assert_eq!(ast[0].kind, ItemKind::Error);
+    assert_eq!(ast[2].kind, ItemKind::Error);
+    assert!(matches!(ast[3].kind, ItemKind::Struct { .. }));
+
+    let ItemKind::Function { body, .. } = &ast[1].kind else {
+        panic!("expected function");
+    };
+    let ExprKind::Block { exprs, trailing } = &body.kind else {
+        panic!("expected block");
+    };
+    assert_eq!(exprs.len(), 3);
+    assert_eq!(exprs[0].kind, ExprKind::Error);
+    assert!(matches!(exprs[1].kind, ExprKind::BinaryOp { .. }));
+    assert_eq!(exprs[2].kind, ExprKind::Error);
+    assert!(trailing);
+}
+
+#[test]
+fn recover_from_lex_errors() {
+    let mut parser = Parser::new("fn f() -> { let x = 99999999999999999999 $ + 1; '' }");
+    let (ast, errors) = parser.file();
+
+    assert_eq!(
+        errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            "integer literal is too large",
+            "unexpected characters `$`",
+            "char literal must contain exactly one character",
+        ]
+    );
+    assert!(matches!(ast[0].kind, ItemKind::Function { .. }));
+}
+
+#[test]
+fn report_unterminated_comments_once() {
+    let mut parser = Parser::new("fn f() -> 1 /* never closed\nfn g() -> 2");
+    let (ast, errors) = parser.file();
+
+    assert_eq!(
+        errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        ["unterminated block comment"]
+    );
+    assert_eq!(ast.len(), 1);
+}
+
+#[test]
+fn attach_doc_comments() {
+    let ast = parse_ast(
+        r#"
+        //! The file's docs, which nothing keeps.
+
+        /// The answer.
+        const ANSWER: Int = 42
+
+        /// Adds one.
+        ///
+        /// Works on any `Int`.
+        fn succ(n) -> {
+            //! Never overflows.
+            /// Not for anything.
+            n + 1
+        }
+
+        // An ordinary comment.
+        struct Point {
+            //! A point.
+            /// Across.
+            x: Int,
+            y: Int,
+        }
+
+        /// A shape.
+        enum Shape {
+            /// Round.
+            Circle(Int),
+            /// Four-sided.
+            Square { /// Along each side.
+            side: Int },
+        }
+
+        fn undocumented() -> { 1 }
+    "#,
+    );
+
+    let docs: Vec<_> = ast.iter().map(|item| item.docs.clone()).collect();
+    assert_eq!(
+        docs,
+        [
+            vec!["The answer."],
+            vec!["Adds one.", "", "Works on any `Int`.", "Never overflows."],
+            vec!["A point."],
+            vec!["A shape."],
+            vec![],
+        ]
+    );
+
+    let ItemKind::Struct { fields, .. } = &ast[2].kind else {
+        panic!("expected a struct");
+    };
+    assert_eq!(fields[0].docs, ["Across."]);
+    assert!(fields[1].docs.is_empty());
+
+    let ItemKind::Enum { variants, .. } = &ast[3].kind else {
+        panic!("expected an enum");
+    };
+    assert_eq!(variants[0].docs, ["Round."]);
+    assert_eq!(variants[1].docs, ["Four-sided."]);
+    let VariantKind::Struct(_, fields) = &variants[1].kind else {
+        panic!("expected a struct variant");
+    };
+    assert_eq!(fields[0].docs, ["Along each side."]);
+}
@@ -0,0 +1,341 @@
+//! Name resolution: works out which definition every identifier refers to.
+//!
+//! Items are visible everywhere, in any order. Function and lambda parameters
+//! are visible in their bodies, match arm bindings in their arm, and a `let`
+//! from the rest of its block onwards, but not in its own value, so that
+//! `let x = x + 1` refers to the `x` from before.
+
+#[cfg(test)]
+mod test;
+
+use std::collections::{HashMap, hash_map::Entry};
+
+use crate::{
+    diagnostics::Diagnostic,
+    natives::Natives,
+    parser::ast::{
+        Ast, Binding, Expr, ExprKind, FieldPattern, ItemKind, MatchArm, Pattern, PatternKind,
+        VariantPattern,
+    },
+    span::Span,
+};
+
+/// Identifies a definition, and stays the same however many places use it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DefId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Builtin,
+    Function,
+    Const,
+    Struct,
+    Enum,
+    /// A function or lambda parameter.
+    Param,
+    /// A `let` or pattern binding.
+    Local,
+}
+
+impl DefKind {
+    fn is_value(self) -> bool {
+        !matches!(self, DefKind::Struct | DefKind::Enum)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Def {
+    pub name: String,
+    pub kind: DefKind,
+    pub mutable: bool,
+    /// Where the definition is, or an empty span for builtins. Items don't
+    /// track the span of their name, so this covers the whole item.
+    pub span: Span,
+}
+
+/// What every name in a program refers to.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    defs: Vec<Def>,
+    /// The definition each `ExprKind::Ident` refers to, by span.
+    uses: HashMap<Span, DefId>,
+    /// The definition each parameter, `let` and pattern binding introduces,
+    /// by span.
+    bindings: HashMap<Span, DefId>,
+}
+
+impl Resolution {
+    pub fn def(&self, id: DefId) -> &Def {
+        &self.defs[id.0 as usize]
+    }
+
+    /// The definition the identifier at `span` refers to, if it was found.
+    pub fn use_at(&self, span: Span) -> Option<DefId> {
+        self.uses.get(&span).copied()
+    }
+
+    /// The definition introduced by the binding at `span`.
+    pub fn binding_at(&self, span: Span) -> Option<DefId> {
+        self.bindings.get(&span).copied()
+    }
+
+    fn define(&mut self, name: &str, kind: DefKind, mutable: bool, span: Span) -> DefId {
+        self.defs.push(Def {
+            name: name.into(),
+            kind,
+            mutable,
+            span,
+        });
+        DefId(self.defs.len() as u32 - 1)
+    }
+}
+
+/// Resolves every identifier in the desugared `ast`, which can call
+/// `natives`, returning the results along with any errors and warnings.
+pub fn resolve(ast: &Ast, natives: &Natives) -> (Resolution, Vec<Diagnostic>) {
+    let mut resolver = Resolver::default();
+    resolver.items(ast, natives);
+    (resolver.resolution, resolver.diagnostics)
+}
+
+#[derive(Default)]
+struct Resolver {
+    resolution: Resolution,
+    builtins: HashMap<String, DefId>,
+    items: HashMap<String, DefId>,
+    /// Variables in scope, innermost last.
+    locals: Vec<DefId>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn items(&mut self, ast: &Ast, natives: &Natives) {
+        for name in natives.names() {
+            let id = self
+                .resolution
+                .define(name, DefKind::Builtin, false, Span::default());
+            self.builtins.insert(name.into(), id);
+        }
+
+        for item in ast {
+            let (name, kind) = match &item.kind {
+                ItemKind::Const { ident, .. } => (ident, DefKind::Const),
+                ItemKind::Function { name, .. } => (name, DefKind::Function),
+                ItemKind::Struct { name, .. } => (name, DefKind::Struct),
+                ItemKind::Enum { name, .. } => (name, DefKind::Enum),
+                ItemKind::Error => continue,
+            };
+
+            match self.items.entry(name.clone()) {
+                Entry::Occupied(entry) => {
+                    let previous = self.resolution.def(*entry.get()).span;
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!("the name `{name}` is defined more than once"),
+                            item.span,
+                        )
+                        .with_label(format!("`{name}` redefined here"))
+                        .with_secondary(previous, format!("previous definition of `{name}` here")),
+                    );
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(self.resolution.define(name, kind, false, item.span));
+                }
+            }
+        }
+
+        for item in ast {
+            match &item.kind {
+                ItemKind::Const { value, .. } => self.expr(value),
+                ItemKind::Function { params, body, .. } => {
+                    self.params(params);
+                    self.expr(body);
+                    self.locals.clear();
+                }
+                ItemKind::Struct { .. } | ItemKind::Enum { .. } | ItemKind::Error => {}
+            }
+        }
+    }
+
+    fn params(&mut self, params: &[Binding]) {
+        let group = self.locals.len();
+        for param in params {
+            self.declare(
+                &param.name,
+                DefKind::Param,
+                param.mutable,
+                param.span,
+                Some((group, "parameter list")),
+            );
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) => self.ident(name, expr.span),
+            ExprKind::Let { binding, value } => {
+                // A `let` outside a block has nothing to be visible in.
+                let scope = self.locals.len();
+                self.let_binding(binding, value);
+                self.locals.truncate(scope);
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                let scope = self.locals.len();
+                self.params(params);
+                self.expr(body);
+                self.locals.truncate(scope);
+            }
+            ExprKind::Block { exprs, .. } => {
+                let scope = self.locals.len();
+                for expr in exprs {
+                    match &expr.kind {
+                        ExprKind::Let { binding, value } => self.let_binding(binding, value),
+                        _ => self.expr(expr),
+                    }
+                }
+                self.locals.truncate(scope);
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee);
+                for MatchArm { pattern, body, .. } in arms {
+                    let scope = self.locals.len();
+                    self.pattern(pattern, scope);
+                    self.expr(body);
+                    self.locals.truncate(scope);
+                }
+            }
+            _ => {
+                for child in expr.children() {
+                    self.expr(child);
+                }
+            }
+        }
+    }
+
+    fn ident(&mut self, name: &str, span: Span) {
+        if let Some(id) = self.lookup(name) {
+            self.resolution.uses.insert(span, id);
+            return;
+        }
+
+        let diagnostic = match self.items.get(name) {
+            Some(_) => Diagnostic::error(format!("`{name}` is a type, not a value"), span)
+                .with_label("not a value"),
+            None => Diagnostic::error(format!("cannot find value `{name}` in this scope"), span)
+                .with_label("not found in this scope"),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The value `name` refers to here: a local, an item or a builtin, in
+    /// that order.
+    fn lookup(&self, name: &str) -> Option<DefId> {
+        self.locals
+            .iter()
+            .rev()
+            .copied()
+            .find(|id| self.resolution.def(*id).name == name)
+            .or_else(|| {
+                self.items
+                    .get(name)
+                    .copied()
+                    .filter(|id| self.resolution.def(*id).kind.is_value())
+            })
+            .or_else(|| self.builtins.get(name).copied())
+    }
+
+    /// Resolves a `let`'s value, then brings its binding into scope.
+    fn let_binding(&mut self, binding: &Binding, value: &Expr) {
+        self.expr(value);
+        self.declare(
+            &binding.name,
+            DefKind::Local,
+            binding.mutable,
+            binding.span,
+            None,
+        );
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, group: usize) {
+        match &pattern.kind {
+            PatternKind::Binding { mutable, name } => self.declare(
+                name,
+                DefKind::Local,
+                *mutable,
+                pattern.span,
+                Some((group, "pattern")),
+            ),
+            PatternKind::Tuple(patterns)
+            | PatternKind::Array(patterns)
+            | PatternKind::Variant {
+                variant: VariantPattern::Tuple(_, patterns),
+                ..
+            } => {
+                for pattern in patterns {
+                    self.pattern(pattern, group);
+                }
+            }
+            PatternKind::Struct { fields, .. }
+            | PatternKind::Variant {
+                variant: VariantPattern::Struct(_, fields),
+                ..
+            } => {
+                for FieldPattern { pattern, .. } in fields {
+                    self.pattern(pattern, group);
+                }
+            }
+            PatternKind::Wildcard
+            | PatternKind::Literal(_)
+            | PatternKind::Variant {
+                variant: VariantPattern::Unit(_),
+                ..
+            } => {}
+        }
+    }
+
+    /// Brings a new variable into scope.
+    ///
+    /// If it's part of a parameter list or pattern, `group` holds the index of
+    /// the first variable that list bound and what to call the list, since
+    /// reusing one of their names is an error rather than shadowing.
+    fn declare(
+        &mut self,
+        name: &str,
+        kind: DefKind,
+        mutable: bool,
+        span: Span,
+        group: Option<(usize, &str)>,
+    ) {
+        let previous = group.and_then(|(start, context)| {
+            self.locals[start..]
+                .iter()
+                .find(|id| self.resolution.def(**id).name == name)
+                .map(|id| (self.resolution.def(*id).span, context))
+        });
+        if let Some((previous, context)) = previous {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!("`{name}` is bound more than once in the same {context}"),
+                    span,
+                )
+                .with_label("bound again here")
+                .with_secondary(previous, "first bound here"),
+            );
+        } else if let Some(shadowed) = self.lookup(name)
+            && !name.starts_with('_')
+        {
+            let shadowed = self.resolution.def(shadowed);
+            if shadowed.kind != DefKind::Builtin {
+                self.diagnostics.push(
+                    Diagnostic::warning(format!("`{name}` shadows an earlier definition"), span)
+                        .with_label(format!("shadows an earlier `{name}`"))
+                        .with_secondary(shadowed.span, format!("the shadowed `{name}`")),
+                );
+            }
+        }
+
+        let id = self.resolution.define(name, kind, mutable, span);
+        self.resolution.bindings.insert(span, id);
+        self.locals.push(id);
+    }
+}
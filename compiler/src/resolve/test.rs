@@ -0,0 +1,232 @@
+use super::{DefKind, Resolution, resolve};
+use crate::{
+    desugar::desugar,
+    diagnostics::{Diagnostic, Severity},
+    natives::Natives,
+    parser::Parser,
+    span::Span,
+};
+
+fn resolve_source(input: &str) -> (Resolution, Vec<Diagnostic>) {
+    let (mut ast, errors) = Parser::new(input).file();
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(desugar(&mut ast).is_empty());
+    resolve(&ast, &Natives::builtins())
+}
+
+/// The span of the `n`th occurrence of `needle` in `input`.
+fn nth(input: &str, needle: &str, n: usize) -> Span {
+    let start = input
+        .match_indices(needle)
+        .nth(n)
+        .unwrap_or_else(|| panic!("no occurrence {n} of {needle:?}"))
+        .0;
+    Span::new(Default::default(), start, start + needle.len())
+}
+
+/// The kind of definition used at `use_span`, and where it is.
+fn target(resolution: &Resolution, use_span: Span) -> (DefKind, Span) {
+    let id = resolution
+        .use_at(use_span)
+        .unwrap_or_else(|| panic!("nothing resolved at {use_span:?}"));
+    let def = resolution.def(id);
+    (def.kind, def.span)
+}
+
+#[test]
+fn resolve_scopes() {
+    let input = "
+        fn f(x, y) -> {
+            let z = x + y;
+            let x = |y| -> y + z;
+            match x(1) {
+                n -> n + twelve,
+            }
+        }
+        const twelve: Int = 12
+    ";
+    let (resolution, diagnostics) = resolve_source(input);
+    assert!(diagnostics.iter().all(|diag| !diag.is_error()));
+
+    let param_x = nth(input, "x", 0);
+    let param_y = nth(input, "y", 0);
+    let local_z = nth(input, "z", 0);
+    assert_eq!(
+        target(&resolution, nth(input, "x", 1)),
+        (DefKind::Param, param_x)
+    );
+    assert_eq!(
+        target(&resolution, nth(input, "y", 1)),
+        (DefKind::Param, param_y)
+    );
+
+    // The lambda's own parameter shadows the function's.
+    let lambda_y = nth(input, "y", 2);
+    assert_eq!(
+        target(&resolution, nth(input, "y", 3)),
+        (DefKind::Param, lambda_y)
+    );
+    assert_eq!(
+        target(&resolution, nth(input, "z", 1)),
+        (DefKind::Local, local_z)
+    );
+
+    // `x(1)` comes after the second `let x`.
+    let local_x = nth(input, "x", 2);
+    assert_eq!(
+        target(&resolution, nth(input, "x", 3)),
+        (DefKind::Local, local_x)
+    );
+
+    let arm_n = nth(input, "n -> ", 0);
+    let arm_n = Span::new(arm_n.file, arm_n.start, arm_n.start + 1);
+    let use_n = nth(input, "n + ", 0);
+    let use_n = Span::new(use_n.file, use_n.start, use_n.start + 1);
+    assert_eq!(target(&resolution, use_n), (DefKind::Local, arm_n));
+    assert_eq!(
+        target(&resolution, nth(input, "twelve", 0)).0,
+        DefKind::Const
+    );
+
+    // Every use of a definition shares its id.
+    assert_eq!(
+        resolution.binding_at(local_z),
+        resolution.use_at(nth(input, "z", 1))
+    );
+}
+
+#[test]
+fn let_value_sees_earlier_binding() {
+    let input = "fn f(x) -> { let x = x + 1; x }";
+    let (resolution, _) = resolve_source(input);
+
+    let param_x = nth(input, "x", 0);
+    let local_x = nth(input, "x", 1);
+    assert_eq!(
+        target(&resolution, nth(input, "x", 2)),
+        (DefKind::Param, param_x)
+    );
+    assert_eq!(
+        target(&resolution, nth(input, "x", 3)),
+        (DefKind::Local, local_x)
+    );
+}
+
+#[test]
+fn items_and_builtins() {
+    let input = "
+        fn main() -> len(helper(1))
+        fn helper(x) -> print(x)
+        fn print(x) -> x
+    ";
+    let (resolution, diagnostics) = resolve_source(input);
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+    assert_eq!(
+        target(&resolution, nth(input, "len", 0)).0,
+        DefKind::Builtin
+    );
+    assert_eq!(
+        target(&resolution, nth(input, "helper", 0)).0,
+        DefKind::Function
+    );
+    // Items shadow builtins.
+    assert_eq!(
+        target(&resolution, nth(input, "print", 0)).0,
+        DefKind::Function
+    );
+}
+
+#[test]
+fn report_undefined_names() {
+    let input = "
+        struct Point { x: Int }
+        fn f() -> { let a = 1; b + a }
+        fn g() -> { { let inner = 1; }; inner }
+        fn h() -> Point
+    ";
+    let (_, diagnostics) = resolve_source(input);
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| (diag.message.as_str(), diag.primary.span))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("cannot find value `b` in this scope", nth(input, "b", 0)),
+            (
+                "cannot find value `inner` in this scope",
+                nth(input, "inner", 1)
+            ),
+            ("`Point` is a type, not a value", nth(input, "Point", 1)),
+        ]
+    );
+}
+
+#[test]
+fn report_duplicates() {
+    let input = "
+        fn f(a, a) -> a
+        const f: Int = 1
+        fn g(x) -> match x {
+            (y, y) -> y,
+        }
+    ";
+    let (_, diagnostics) = resolve_source(input);
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| (diag.message.as_str(), diag.secondary[0].message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "the name `f` is defined more than once",
+                "previous definition of `f` here"
+            ),
+            (
+                "`a` is bound more than once in the same parameter list",
+                "first bound here"
+            ),
+            (
+                "`y` is bound more than once in the same pattern",
+                "first bound here"
+            ),
+        ]
+    );
+    assert_eq!(diagnostics[1].primary.span, nth(input, "a", 1));
+    assert_eq!(diagnostics[1].secondary[0].span, nth(input, "a", 0));
+}
+
+#[test]
+fn warn_about_shadowing() {
+    let input = "
+        fn outer(x) -> {
+            let x = 1;
+            let g = |_ignored, outer| -> outer;
+            let _x = 2;
+            let _x = 3;
+            x
+        }
+    ";
+    let (_, diagnostics) = resolve_source(input);
+    assert!(
+        diagnostics
+            .iter()
+            .all(|diag| diag.severity == Severity::Warning)
+    );
+
+    let shadowed: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| (diag.primary.span, diag.secondary[0].span))
+        .collect();
+    let fn_start = nth(input, "fn", 0).start;
+    let fn_item = Span::new(Default::default(), fn_start, input.trim_end().len());
+    assert_eq!(
+        shadowed,
+        [
+            (nth(input, "x", 1), nth(input, "x", 0)),
+            (nth(input, "outer", 1), fn_item),
+        ]
+    );
+}
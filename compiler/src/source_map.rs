@@ -0,0 +1,167 @@
+use std::{fmt::Display, fs, io, path::Path};
+
+use crate::span::FileId;
+
+/// A 1-based line and column pair. Columns count `char`s, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Display for LineCol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+pub struct SourceFile {
+    name: String,
+    source: String,
+    /// Byte offset of the first character of every line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+
+        Self {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of the 1-based `line`, without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+
+    /// Converts a byte offset into a line/column pair. Offsets past the end of
+    /// the file are clamped to the end.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+
+        LineCol {
+            line: line + 1,
+            col: self.source[line_start..]
+                .char_indices()
+                .take_while(|(pos, _)| line_start + pos < offset)
+                .count()
+                + 1,
+        }
+    }
+
+    /// Converts a line/column pair back into a byte offset. Returns `None` if the
+    /// position lies outside the file.
+    pub fn offset(&self, position: LineCol) -> Option<usize> {
+        let line = self.line(position.line)?;
+        let line_start = self.line_starts[position.line - 1];
+
+        let col = position.col.checked_sub(1)?;
+        line.char_indices()
+            .map(|(pos, _)| pos)
+            .chain(std::iter::once(line.len()))
+            .nth(col)
+            .map(|pos| line_start + pos)
+    }
+}
+
+/// Owns every source file loaded during a compilation and hands out the
+/// `FileId`s that spans refer to.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        id
+    }
+
+    /// Reads the file at `path` and adds it to the map.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        Ok(self.add(path.display().to_string(), source))
+    }
+
+    /// # Panics
+    /// If `id` was not handed out by this map.
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn line_col(&self, id: FileId, offset: usize) -> LineCol {
+        self.get(id).line_col(offset)
+    }
+
+    pub fn offset(&self, id: FileId, position: LineCol) -> Option<usize> {
+        self.get(id).offset(position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LineCol, SourceMap};
+
+    #[test]
+    fn line_col_round_trip() {
+        let mut map = SourceMap::new();
+        map.add("a.ptn", "fn a() -> 1\n");
+        let file = map.add("b.ptn", "let x = 'é';\r\n\nfoo");
+        let source = map.get(file);
+
+        assert_eq!(source.name(), "b.ptn");
+        assert_eq!(source.line_count(), 3);
+        assert_eq!(source.line(1), Some("let x = 'é';"));
+        assert_eq!(source.line(2), Some(""));
+        assert_eq!(source.line(3), Some("foo"));
+        assert_eq!(source.line(4), None);
+
+        let positions = [
+            (0, LineCol { line: 1, col: 1 }),
+            (11, LineCol { line: 1, col: 11 }),
+            (12, LineCol { line: 1, col: 12 }),
+            (15, LineCol { line: 2, col: 1 }),
+            (16, LineCol { line: 3, col: 1 }),
+            (19, LineCol { line: 3, col: 4 }),
+        ];
+        for (offset, position) in positions {
+            assert_eq!(map.line_col(file, offset), position);
+            assert_eq!(map.offset(file, position), Some(offset));
+        }
+
+        assert_eq!(map.offset(file, LineCol { line: 3, col: 5 }), None);
+        assert_eq!(map.offset(file, LineCol { line: 0, col: 1 }), None);
+    }
+}
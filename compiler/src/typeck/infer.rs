@@ -0,0 +1,628 @@
+use super::{
+    Checker, TypeDefKind, VariantDef,
+    ty::{Class, Scheme, Ty},
+};
+use crate::{
+    diagnostics::Diagnostic,
+    parser::ast::{
+        Binding, Bop, Expr, ExprKind, FieldInit, FieldPattern, Lit, MatchArm, Pattern, PatternKind,
+        Unop, VariantPattern,
+    },
+    span::Span,
+};
+
+impl Checker {
+    pub(super) fn expr(&mut self, expr: &Expr) -> Ty {
+        let ty = self.expr_kind(expr);
+        self.nodes.insert(expr.span, ty.clone());
+        ty
+    }
+
+    fn expr_kind(&mut self, expr: &Expr) -> Ty {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(lit) => self.literal(lit, span),
+            ExprKind::Ident(name) => self.ident(name, span),
+            ExprKind::Path { enum_name, variant } => self.path(enum_name, variant, span),
+            ExprKind::FnCall { fun, args } => {
+                let fun_ty = self.expr(fun);
+                let arg_types: Vec<_> = args.iter().map(|arg| self.expr(arg)).collect();
+
+                match self.table.shallow_resolve(&fun_ty) {
+                    Ty::Fn(params, _) if params.len() != args.len() => self.error(
+                        format!(
+                            "this function takes {} argument{}, but {} {} supplied",
+                            params.len(),
+                            if params.len() == 1 { "" } else { "s" },
+                            args.len(),
+                            if args.len() == 1 { "was" } else { "were" },
+                        ),
+                        span,
+                        "wrong number of arguments",
+                    ),
+                    // Check each argument on its own, to point right at any mismatch.
+                    Ty::Fn(params, result) => {
+                        for ((param, arg), ty) in params.iter().zip(args).zip(&arg_types) {
+                            self.expect(param, ty, arg.span);
+                        }
+                        *result
+                    }
+                    _ => {
+                        let result = self.table.fresh(self.level, None);
+                        let expected = Ty::Fn(arg_types, Box::new(result.clone()));
+                        self.expect(&expected, &fun_ty, fun.span);
+                        result
+                    }
+                }
+            }
+            ExprKind::BinaryOp { op, lhs, rhs } => self.binary_op(*op, lhs, rhs),
+            ExprKind::UnaryOp { op, expr } => {
+                let ty = self.expr(expr);
+                let class = match op {
+                    Unop::Neg => Class::NUM,
+                    Unop::Not => Class::BITS,
+                };
+                self.constrain(&ty, class, expr.span);
+                ty
+            }
+            ExprKind::Index { arr, index } => {
+                let elem = self.table.fresh(self.level, None);
+                let arr_ty = self.expr(arr);
+                self.expect(&Ty::Array(Box::new(elem.clone())), &arr_ty, arr.span);
+                let index_ty = self.expr(index);
+                self.expect(&Ty::int(), &index_ty, index.span);
+                elem
+            }
+            ExprKind::FieldAccess { base, field } => {
+                let base_ty = self.expr(base);
+                self.field(&base_ty, field, span)
+            }
+            ExprKind::If { cond, th, el } => {
+                let cond_ty = self.expr(cond);
+                self.expect(&Ty::bool(), &cond_ty, cond.span);
+
+                let th_ty = self.expr(th);
+                match el {
+                    Some(el) => {
+                        let el_ty = self.expr(el);
+                        self.expect(&th_ty, &el_ty, el.span);
+                        th_ty
+                    }
+                    // Without an `else` there's nothing to produce when the
+                    // condition is false.
+                    None => {
+                        self.expect(&Ty::unit(), &th_ty, th.span);
+                        Ty::unit()
+                    }
+                }
+            }
+            // A `let` outside of a block binds nothing that can be used.
+            ExprKind::Let { binding, value } => {
+                self.let_binding(binding, value);
+                self.locals.pop();
+                Ty::unit()
+            }
+            ExprKind::Lambda {
+                params,
+                return_type,
+                body,
+            } => {
+                let scope = self.locals.len();
+                let param_types: Vec<_> = params
+                    .iter()
+                    .map(|param| {
+                        let ty = self.binding_type(param.type_annotation.as_ref());
+                        self.nodes.insert(param.span, ty.clone());
+                        self.locals
+                            .push((param.name.clone(), Scheme::mono(ty.clone())));
+                        ty
+                    })
+                    .collect();
+                let body_ty = self.expr(body);
+                self.locals.truncate(scope);
+
+                let result = match return_type {
+                    Some(annotation) => {
+                        let ty = self.annotation(annotation, &[]);
+                        self.expect_annotation(&ty, &body_ty, body.span, annotation.span);
+                        ty
+                    }
+                    None => body_ty,
+                };
+                Ty::Fn(param_types, Box::new(result))
+            }
+            ExprKind::Block { exprs, trailing } => {
+                let scope = self.locals.len();
+                let mut ty = Ty::unit();
+                for expr in exprs {
+                    ty = match &expr.kind {
+                        ExprKind::Let { binding, value } => {
+                            self.let_binding(binding, value);
+                            self.nodes.insert(expr.span, Ty::unit());
+                            Ty::unit()
+                        }
+                        _ => self.expr(expr),
+                    };
+                }
+                self.locals.truncate(scope);
+
+                if *trailing { ty } else { Ty::unit() }
+            }
+            ExprKind::Match { scrutinee, arms } => self.match_(scrutinee, arms),
+            // Every value has a string form, so the parts can be of any type.
+            ExprKind::Interpolated(_) => {
+                for part in expr.children() {
+                    self.expr(part);
+                }
+                Ty::str()
+            }
+            ExprKind::Placeholder | ExprKind::Error => Ty::Error,
+        }
+    }
+
+    fn literal(&mut self, lit: &Lit, span: Span) -> Ty {
+        match lit {
+            Lit::Int(_) => Ty::int(),
+            Lit::Float(_) => Ty::float(),
+            Lit::Str(_) => Ty::str(),
+            Lit::Char(_) => Ty::char(),
+            Lit::Bool(_) => Ty::bool(),
+            Lit::Array(exprs) => {
+                let elem = self.table.fresh(self.level, None);
+                for expr in exprs {
+                    let ty = self.expr(expr);
+                    self.expect(&elem, &ty, expr.span);
+                }
+                Ty::Array(Box::new(elem))
+            }
+            Lit::Tuple(exprs) => Ty::Tuple(exprs.iter().map(|expr| self.expr(expr)).collect()),
+            Lit::Struct {
+                name,
+                variant,
+                fields,
+            } => {
+                let Some((ty, generics)) = self.instantiate_def(name, span) else {
+                    return Ty::Error;
+                };
+
+                let def = &self.type_defs[name].kind;
+                let declared = match (def, variant) {
+                    (TypeDefKind::Struct(fields), None) => fields,
+                    (TypeDefKind::Enum(variants), Some(variant)) => {
+                        match variants.iter().find(|(name, _)| name == variant) {
+                            Some((_, VariantDef::Struct(fields))) => fields,
+                            Some(_) => {
+                                return self.error(
+                                    format!("`{name}::{variant}` is not a struct variant"),
+                                    span,
+                                    "doesn't have named fields",
+                                );
+                            }
+                            None => {
+                                return self.error(
+                                    format!("`{name}` has no variant `{variant}`"),
+                                    span,
+                                    "unknown variant",
+                                );
+                            }
+                        }
+                    }
+                    (TypeDefKind::Struct(_), Some(_)) => {
+                        return self.error(
+                            format!("`{name}` is a struct, not an enum"),
+                            span,
+                            "not an enum",
+                        );
+                    }
+                    (TypeDefKind::Enum(_), None) => {
+                        return self.error(
+                            format!("`{name}` is an enum, so it needs a variant"),
+                            span,
+                            "expected `Enum::Variant { .. }`",
+                        );
+                    }
+                };
+                let declared: Vec<_> = declared
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), ty.substitute(&generics)))
+                    .collect();
+
+                let struct_name = match variant {
+                    Some(variant) => format!("{name}::{variant}"),
+                    None => name.clone(),
+                };
+                self.field_inits(&struct_name, &declared, fields, span);
+                ty
+            }
+        }
+    }
+
+    fn field_inits(
+        &mut self,
+        struct_name: &str,
+        declared: &[(String, Ty)],
+        inits: &[FieldInit],
+        span: Span,
+    ) {
+        for (i, init) in inits.iter().enumerate() {
+            let found = self.expr(&init.value);
+            if let Some(first) = inits[..i].iter().find(|other| other.name == init.name) {
+                self.duplicate_field(&init.name, "specified", init.span, first.span);
+            }
+            match declared.iter().find(|(name, _)| *name == init.name) {
+                Some((_, expected)) => self.expect(expected, &found, init.value.span),
+                None => {
+                    self.error(
+                        format!("`{struct_name}` has no field named `{}`", init.name),
+                        init.span,
+                        "unknown field",
+                    );
+                }
+            }
+        }
+
+        let missing: Vec<_> = declared
+            .iter()
+            .filter(|(name, _)| !inits.iter().any(|init| init.name == *name))
+            .map(|(name, _)| format!("`{name}`"))
+            .collect();
+        if !missing.is_empty() {
+            self.error(
+                format!("missing fields {} in `{struct_name}`", missing.join(", ")),
+                span,
+                "missing fields",
+            );
+        }
+    }
+
+    /// A fresh instance of a struct or enum type, along with the variables
+    /// standing in for its generics.
+    fn instantiate_def(&mut self, name: &str, span: Span) -> Option<(Ty, Vec<Ty>)> {
+        let Some(def) = self.type_defs.get(name) else {
+            self.error(format!("cannot find type `{name}`"), span, "not defined");
+            return None;
+        };
+
+        let generics: Vec<_> = (0..def.generics)
+            .map(|_| self.table.fresh(self.level, None))
+            .collect();
+        Some((Ty::Named(name.into(), generics.clone()), generics))
+    }
+
+    fn ident(&mut self, name: &str, span: Span) -> Ty {
+        let scheme = self
+            .locals
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, scheme)| scheme)
+            .or_else(|| self.items.get(name))
+            .or_else(|| self.natives.get(name))
+            .cloned();
+
+        match scheme {
+            Some(scheme) => self.table.instantiate(&scheme, self.level),
+            None if self.type_defs.contains_key(name) => self.error(
+                format!("`{name}` is a type, not a value"),
+                span,
+                "not a value",
+            ),
+            None => self.error(format!("cannot find value `{name}`"), span, "not found"),
+        }
+    }
+
+    fn path(&mut self, enum_name: &str, variant: &str, span: Span) -> Ty {
+        let Some((ty, generics)) = self.instantiate_def(enum_name, span) else {
+            return Ty::Error;
+        };
+
+        let TypeDefKind::Enum(variants) = &self.type_defs[enum_name].kind else {
+            return self.error(
+                format!("`{enum_name}` is a struct, not an enum"),
+                span,
+                "not an enum",
+            );
+        };
+        match variants.iter().find(|(name, _)| name == variant) {
+            Some((_, VariantDef::Unit)) => ty,
+            Some((_, VariantDef::Tuple(types))) => Ty::Fn(
+                types.iter().map(|ty| ty.substitute(&generics)).collect(),
+                Box::new(ty),
+            ),
+            Some((_, VariantDef::Struct(_))) => self.error(
+                format!("`{enum_name}::{variant}` is a struct variant, so it needs fields"),
+                span,
+                "expected `{ .. }` after this",
+            ),
+            None => self.error(
+                format!("`{enum_name}` has no variant `{variant}`"),
+                span,
+                "unknown variant",
+            ),
+        }
+    }
+
+    /// Looks up a field of a struct or a numbered field of a tuple.
+    ///
+    /// If the base's type isn't known yet, a field name that only one struct
+    /// has decides it.
+    fn field(&mut self, base: &Ty, field: &str, span: Span) -> Ty {
+        let base = self.table.shallow_resolve(base);
+        match &base {
+            Ty::Named(name, generics) => {
+                if let Some(TypeDefKind::Struct(fields)) =
+                    self.type_defs.get(name).map(|def| &def.kind)
+                    && let Some((_, ty)) = fields.iter().find(|(name, _)| name == field)
+                {
+                    return ty.substitute(generics);
+                }
+            }
+            Ty::Tuple(types) => {
+                if let Some(ty) = field
+                    .strip_prefix('_')
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| types.get(index))
+                {
+                    return ty.clone();
+                }
+            }
+            Ty::Var(_) => {
+                let mut owners = self.type_defs.iter().filter(|(_, def)| {
+                    matches!(&def.kind, TypeDefKind::Struct(fields)
+                        if fields.iter().any(|(name, _)| name == field))
+                });
+                if let (Some((name, _)), None) = (owners.next(), owners.next()) {
+                    let name = name.clone();
+                    let (ty, _) = self
+                        .instantiate_def(&name, span)
+                        .expect("the struct was just found");
+                    self.expect(&ty, &base, span);
+                    return self.field(&ty, field, span);
+                }
+                return self.error(
+                    format!("the type of this value must be known to access `{field}`"),
+                    span,
+                    "type annotations needed",
+                );
+            }
+            Ty::Error => return Ty::Error,
+            _ => {}
+        }
+
+        let base = self.table.resolve(&base);
+        self.error(
+            format!("no field `{field}` on type `{base}`"),
+            span,
+            "unknown field",
+        )
+    }
+
+    fn binary_op(&mut self, op: Bop, lhs: &Expr, rhs: &Expr) -> Ty {
+        let lhs_ty = self.expr(lhs);
+        let rhs_ty = self.expr(rhs);
+
+        let operand = |class| (Some(class), None);
+        let (class, result) = match op {
+            Bop::Assign => {
+                self.expect(&lhs_ty, &rhs_ty, rhs.span);
+                return Ty::unit();
+            }
+            Bop::And | Bop::Or => {
+                self.expect(&Ty::bool(), &lhs_ty, lhs.span);
+                self.expect(&Ty::bool(), &rhs_ty, rhs.span);
+                return Ty::bool();
+            }
+            Bop::Add => operand(Class::ADD),
+            Bop::Sub | Bop::Mul | Bop::Div | Bop::Exp => operand(Class::NUM),
+            Bop::Xor | Bop::BOr | Bop::BAnd => operand(Class::BITS),
+            Bop::Gt | Bop::Lt | Bop::Geq | Bop::Leq => (Some(Class::ORD), Some(Ty::bool())),
+            Bop::Eqq | Bop::Neq => (None, Some(Ty::bool())),
+            Bop::Pipeline | Bop::ComposeRight | Bop::ComposeLeft => {
+                unreachable!("removed by desugaring")
+            }
+        };
+
+        self.expect(&lhs_ty, &rhs_ty, rhs.span);
+        if let Some(class) = class {
+            self.constrain(&lhs_ty, class, lhs.span);
+        }
+        result.unwrap_or(lhs_ty)
+    }
+
+    /// Restricts `ty` to the types in `class`.
+    fn constrain(&mut self, ty: &Ty, class: Class, span: Span) {
+        let var = self.table.fresh(self.level, Some(class));
+        self.expect(&var, ty, span);
+    }
+
+    /// Checks the value of a `let` and brings its binding into scope.
+    ///
+    /// Only immutable lambdas are generalized: generalizing anything else
+    /// could let the same variable hold values of different types.
+    fn let_binding(&mut self, binding: &Binding, value: &Expr) {
+        let generalize = !binding.mutable && matches!(value.kind, ExprKind::Lambda { .. });
+
+        if generalize {
+            self.level += 1;
+        }
+        let mut ty = self.expr(value);
+        if generalize {
+            self.level -= 1;
+        }
+
+        if let Some(annotation) = &binding.type_annotation {
+            let expected = self.annotation(annotation, &[]);
+            self.expect_annotation(&expected, &ty, value.span, annotation.span);
+            ty = expected;
+        }
+        self.nodes.insert(binding.span, ty.clone());
+
+        let scheme = if generalize {
+            self.table.generalize(&ty, self.level)
+        } else {
+            Scheme::mono(ty)
+        };
+        self.locals.push((binding.name.clone(), scheme));
+    }
+
+    fn match_(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Ty {
+        let scrutinee_ty = self.expr(scrutinee);
+        let result = self.table.fresh(self.level, None);
+        for arm in arms {
+            let scope = self.locals.len();
+            self.pattern(&arm.pattern, &scrutinee_ty);
+            let body_ty = self.expr(&arm.body);
+            self.expect(&result, &body_ty, arm.body.span);
+            self.locals.truncate(scope);
+        }
+        result
+    }
+
+    /// Checks `pattern` against the type of the value it matches, bringing
+    /// its bindings into scope.
+    fn pattern(&mut self, pattern: &Pattern, expected: &Ty) {
+        let span = pattern.span;
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Binding { name, .. } => {
+                self.nodes.insert(span, expected.clone());
+                self.locals
+                    .push((name.clone(), Scheme::mono(expected.clone())));
+            }
+            PatternKind::Literal(lit) => {
+                let ty = self.literal(lit, span);
+                self.expect(expected, &ty, span);
+            }
+            PatternKind::Tuple(patterns) => {
+                let types: Vec<_> = patterns
+                    .iter()
+                    .map(|_| self.table.fresh(self.level, None))
+                    .collect();
+                self.expect(expected, &Ty::Tuple(types.clone()), span);
+                for (pattern, ty) in patterns.iter().zip(&types) {
+                    self.pattern(pattern, ty);
+                }
+            }
+            PatternKind::Array(patterns) => {
+                let elem = self.table.fresh(self.level, None);
+                self.expect(expected, &Ty::Array(Box::new(elem.clone())), span);
+                for pattern in patterns {
+                    self.pattern(pattern, &elem);
+                }
+            }
+            PatternKind::Variant { enum_name, variant } => {
+                let Some((ty, generics)) = self.instantiate_def(enum_name, span) else {
+                    return;
+                };
+                self.expect(expected, &ty, span);
+
+                let TypeDefKind::Enum(variants) = &self.type_defs[enum_name].kind else {
+                    self.error(
+                        format!("`{enum_name}` is a struct, not an enum"),
+                        span,
+                        "not an enum",
+                    );
+                    return;
+                };
+                let name = match variant {
+                    VariantPattern::Unit(name)
+                    | VariantPattern::Tuple(name, _)
+                    | VariantPattern::Struct(name, _) => name,
+                };
+                let Some((_, def)) = variants.iter().find(|(variant, _)| variant == name) else {
+                    self.error(
+                        format!("`{enum_name}` has no variant `{name}`"),
+                        span,
+                        "unknown variant",
+                    );
+                    return;
+                };
+
+                match (variant, def) {
+                    (VariantPattern::Unit(_), VariantDef::Unit) => {}
+                    (VariantPattern::Tuple(_, patterns), VariantDef::Tuple(types))
+                        if patterns.len() == types.len() =>
+                    {
+                        let types: Vec<_> =
+                            types.iter().map(|ty| ty.substitute(&generics)).collect();
+                        for (pattern, ty) in patterns.iter().zip(&types) {
+                            self.pattern(pattern, ty);
+                        }
+                    }
+                    (VariantPattern::Struct(_, patterns), VariantDef::Struct(fields)) => {
+                        let fields: Vec<_> = fields
+                            .iter()
+                            .map(|(name, ty)| (name.clone(), ty.substitute(&generics)))
+                            .collect();
+                        self.field_patterns(&format!("{enum_name}::{name}"), &fields, patterns);
+                    }
+                    _ => {
+                        self.error(
+                            format!(
+                                "this pattern doesn't match the shape of `{enum_name}::{name}`"
+                            ),
+                            span,
+                            "wrong kind of variant pattern",
+                        );
+                    }
+                }
+            }
+            PatternKind::Struct { name, fields } => {
+                let Some((ty, generics)) = self.instantiate_def(name, span) else {
+                    return;
+                };
+                self.expect(expected, &ty, span);
+
+                let TypeDefKind::Struct(declared) = &self.type_defs[name].kind else {
+                    self.error(
+                        format!("`{name}` is an enum, so it needs a variant"),
+                        span,
+                        "expected `Enum::Variant { .. }`",
+                    );
+                    return;
+                };
+                let declared: Vec<_> = declared
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), ty.substitute(&generics)))
+                    .collect();
+                self.field_patterns(name, &declared, fields);
+            }
+        }
+    }
+
+    fn field_patterns(
+        &mut self,
+        struct_name: &str,
+        declared: &[(String, Ty)],
+        patterns: &[FieldPattern],
+    ) {
+        for (i, pattern) in patterns.iter().enumerate() {
+            if let Some(first) = patterns[..i]
+                .iter()
+                .find(|other| other.name == pattern.name)
+            {
+                self.duplicate_field(&pattern.name, "matched", pattern.span, first.span);
+            }
+            match declared.iter().find(|(name, _)| *name == pattern.name) {
+                Some((_, ty)) => self.pattern(&pattern.pattern, ty),
+                None => {
+                    self.error(
+                        format!("`{struct_name}` has no field named `{}`", pattern.name),
+                        pattern.span,
+                        "unknown field",
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reports a field that's `verb` more than once in a struct literal or
+    /// pattern.
+    fn duplicate_field(&mut self, name: &str, verb: &str, span: Span, first: Span) {
+        self.diagnostics.push(
+            Diagnostic::error(format!("field `{name}` is {verb} more than once"), span)
+                .with_label("used again here")
+                .with_secondary(first, "first used here"),
+        );
+    }
+}
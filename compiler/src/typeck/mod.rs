@@ -0,0 +1,486 @@
+//! Hindley–Milner type inference over the desugared AST.
+//!
+//! Functions are inferred a strongly connected component at a time, so that
+//! each is generalized before anything that calls it is checked. Overloaded
+//! operators like `+` restrict their operands to a `Class` of primitives
+//! instead of picking one, so `fn sum(a, b) -> a + b` works on `Int`, `Float`
+//! and `Str` alike.
+
+mod infer;
+#[cfg(test)]
+mod test;
+mod ty;
+mod unify;
+
+use std::collections::HashMap;
+
+pub use ty::{Class, Scheme, Ty, TyVar};
+
+use crate::{
+    diagnostics::Diagnostic,
+    natives::Natives,
+    parser::ast::{Ast, Expr, ExprKind, Field, Item, ItemKind, Type, TypeKind, VariantKind},
+    span::Span,
+};
+use unify::{Table, UnifyError};
+
+/// The types inferred for a program.
+#[derive(Debug, Default)]
+pub struct Types {
+    /// The type of every function and const item.
+    pub items: HashMap<String, Scheme>,
+    /// The type of every expression and binding, by span.
+    nodes: HashMap<Span, Ty>,
+}
+
+impl Types {
+    /// The type of the expression or binding at `span`.
+    pub fn node(&self, span: Span) -> Option<&Ty> {
+        self.nodes.get(&span)
+    }
+}
+
+/// Infers the types in `ast`, which can call `natives`, returning them along
+/// with any type errors.
+pub fn check(ast: &Ast, natives: &Natives) -> (Types, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        natives: natives.clone(),
+        ..Checker::default()
+    };
+    checker.items(ast);
+
+    let nodes = checker
+        .nodes
+        .iter()
+        .map(|(span, ty)| (*span, checker.table.resolve(ty)))
+        .collect();
+    let types = Types {
+        items: checker.items,
+        nodes,
+    };
+    (types, checker.diagnostics)
+}
+
+/// A struct's fields or an enum's variants, in terms of its generic
+/// parameters `Ty::Gen(0)`, `Ty::Gen(1)`, ...
+#[derive(Debug, Default)]
+struct TypeDef {
+    generics: usize,
+    kind: TypeDefKind,
+}
+
+#[derive(Debug)]
+enum TypeDefKind {
+    Struct(Vec<(String, Ty)>),
+    Enum(Vec<(String, VariantDef)>),
+}
+
+impl Default for TypeDefKind {
+    fn default() -> Self {
+        TypeDefKind::Struct(Vec::new())
+    }
+}
+
+#[derive(Debug)]
+enum VariantDef {
+    Unit,
+    Tuple(Vec<Ty>),
+    Struct(Vec<(String, Ty)>),
+}
+
+#[derive(Default)]
+struct Checker {
+    table: Table,
+    /// How many generalizable scopes deep inference currently is.
+    level: u32,
+    type_defs: HashMap<String, TypeDef>,
+    items: HashMap<String, Scheme>,
+    natives: Natives,
+    /// Variables in scope, innermost last.
+    locals: Vec<(String, Scheme)>,
+    nodes: HashMap<Span, Ty>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn items(&mut self, ast: &Ast) {
+        self.type_defs(ast);
+
+        // Consts always have a declared type, so they can be used before
+        // their values are checked.
+        for item in ast {
+            if let ItemKind::Const { ident, ty, .. } = &item.kind {
+                let ty = self.annotation(ty, &[]);
+                self.items.insert(ident.clone(), Scheme::mono(ty));
+            }
+        }
+
+        let functions: Vec<&Item> = ast
+            .iter()
+            .filter(|item| matches!(item.kind, ItemKind::Function { .. }))
+            .collect();
+        for component in components(&functions) {
+            self.functions(&component);
+        }
+
+        for item in ast {
+            if let ItemKind::Const { ident, ty, value } = &item.kind {
+                self.level += 1;
+                let found = self.expr(value);
+                self.level -= 1;
+                let expected = self.items[ident].ty.clone();
+                self.expect_annotation(&expected, &found, value.span, ty.span);
+            }
+        }
+    }
+
+    /// Collects every struct and enum, so that types can refer to ones
+    /// defined later in the file.
+    fn type_defs(&mut self, ast: &Ast) {
+        for item in ast {
+            if let ItemKind::Struct {
+                name,
+                generic_params,
+                ..
+            }
+            | ItemKind::Enum {
+                name,
+                generic_params,
+                ..
+            } = &item.kind
+            {
+                self.type_defs.insert(
+                    name.clone(),
+                    TypeDef {
+                        generics: generic_params.len(),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        for item in ast {
+            let kind = match &item.kind {
+                ItemKind::Struct {
+                    generic_params,
+                    fields,
+                    ..
+                } => TypeDefKind::Struct(self.fields(fields, generic_params)),
+                ItemKind::Enum {
+                    generic_params,
+                    variants,
+                    ..
+                } => TypeDefKind::Enum(
+                    variants
+                        .iter()
+                        .map(|variant| match &variant.kind {
+                            VariantKind::Unit(name) => (name.clone(), VariantDef::Unit),
+                            VariantKind::Tuple(name, types) => (
+                                name.clone(),
+                                VariantDef::Tuple(
+                                    types
+                                        .iter()
+                                        .map(|ty| self.annotation(ty, generic_params))
+                                        .collect(),
+                                ),
+                            ),
+                            VariantKind::Struct(name, fields) => (
+                                name.clone(),
+                                VariantDef::Struct(self.fields(fields, generic_params)),
+                            ),
+                        })
+                        .collect(),
+                ),
+                _ => continue,
+            };
+
+            let (ItemKind::Struct { name, .. } | ItemKind::Enum { name, .. }) = &item.kind else {
+                unreachable!()
+            };
+            if let Some(def) = self.type_defs.get_mut(name) {
+                def.kind = kind;
+            }
+        }
+    }
+
+    fn fields(&mut self, fields: &[Field], generic_params: &[String]) -> Vec<(String, Ty)> {
+        fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.clone(),
+                    self.annotation(&field.ty, generic_params),
+                )
+            })
+            .collect()
+    }
+
+    /// Infers a group of mutually recursive functions together, then
+    /// generalizes them all.
+    fn functions(&mut self, functions: &[&Item]) {
+        self.level += 1;
+
+        let mut signatures = Vec::new();
+        for item in functions {
+            let ItemKind::Function {
+                name,
+                params,
+                return_type,
+                ..
+            } = &item.kind
+            else {
+                unreachable!("only functions are grouped")
+            };
+
+            let params: Vec<_> = params
+                .iter()
+                .map(|param| self.binding_type(param.type_annotation.as_ref()))
+                .collect();
+            let result = self.binding_type(return_type.as_ref());
+            let ty = Ty::Fn(params.clone(), Box::new(result.clone()));
+            self.items.insert(name.clone(), Scheme::mono(ty));
+            signatures.push((params, result));
+        }
+
+        for (item, (param_types, result)) in functions.iter().zip(signatures) {
+            let ItemKind::Function {
+                params,
+                return_type,
+                body,
+                ..
+            } = &item.kind
+            else {
+                unreachable!()
+            };
+
+            let scope = self.locals.len();
+            for (param, ty) in params.iter().zip(param_types) {
+                self.nodes.insert(param.span, ty.clone());
+                self.locals.push((param.name.clone(), Scheme::mono(ty)));
+            }
+            let found = self.expr(body);
+            self.locals.truncate(scope);
+
+            match return_type {
+                Some(annotation) => {
+                    self.expect_annotation(&result, &found, body.span, annotation.span)
+                }
+                None => self.expect(&result, &found, body.span),
+            }
+        }
+
+        self.level -= 1;
+        for item in functions {
+            let ItemKind::Function { name, .. } = &item.kind else {
+                unreachable!()
+            };
+            let scheme = self.table.generalize(&self.items[name].ty, self.level);
+            self.items.insert(name.clone(), scheme);
+        }
+    }
+
+    /// The type of a binding, or a fresh variable if it isn't annotated.
+    fn binding_type(&mut self, annotation: Option<&Type>) -> Ty {
+        match annotation {
+            Some(annotation) => self.annotation(annotation, &[]),
+            None => self.table.fresh(self.level, None),
+        }
+    }
+
+    /// Converts a written type, where `generic_params` are in scope.
+    fn annotation(&mut self, ty: &Type, generic_params: &[String]) -> Ty {
+        let all = |this: &mut Self, types: &[Type]| {
+            types
+                .iter()
+                .map(|ty| this.annotation(ty, generic_params))
+                .collect::<Vec<_>>()
+        };
+
+        match &ty.kind {
+            TypeKind::Ident { name, generics } => {
+                if let Some(n) = generic_params.iter().position(|param| param == name)
+                    && generics.is_empty()
+                {
+                    return Ty::Gen(n as u32);
+                }
+
+                let expected = match self.type_defs.get(name) {
+                    Some(def) => def.generics,
+                    None if ["Int", "Float", "Str", "Char", "Bool"].contains(&name.as_str()) => 0,
+                    None => {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("cannot find type `{name}`"), ty.span)
+                                .with_label("not defined"),
+                        );
+                        return Ty::Error;
+                    }
+                };
+                if generics.len() != expected {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "`{name}` takes {expected} generic argument{}, but {} {} supplied",
+                                if expected == 1 { "" } else { "s" },
+                                generics.len(),
+                                if generics.len() == 1 { "was" } else { "were" },
+                            ),
+                            ty.span,
+                        )
+                        .with_label("wrong number of generics"),
+                    );
+                    return Ty::Error;
+                }
+
+                Ty::Named(name.clone(), all(self, generics))
+            }
+            TypeKind::Array(elem) => Ty::Array(Box::new(self.annotation(elem, generic_params))),
+            TypeKind::Tuple(types) => Ty::Tuple(all(self, types)),
+            TypeKind::Fn { params, result } => Ty::Fn(
+                all(self, params),
+                Box::new(self.annotation(result, generic_params)),
+            ),
+        }
+    }
+
+    /// Unifies the type an expression was found to have with the one its
+    /// context expects, reporting a mismatch at `span`.
+    fn expect(&mut self, expected: &Ty, found: &Ty, span: Span) {
+        if let Err(err) = self.table.unify(expected, found) {
+            let diagnostic = self.mismatch(err, expected, found, span);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Like `expect`, but also points at the annotation the expected type came from.
+    fn expect_annotation(&mut self, expected: &Ty, found: &Ty, span: Span, annotation: Span) {
+        if let Err(err) = self.table.unify(expected, found) {
+            let diagnostic = self
+                .mismatch(err, expected, found, span)
+                .with_secondary(annotation, "expected because of this annotation");
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn mismatch(&self, err: UnifyError, expected: &Ty, found: &Ty, span: Span) -> Diagnostic {
+        let expected = self.table.resolve(expected);
+        let found = self.table.resolve(found);
+        match err {
+            UnifyError::Mismatch => Diagnostic::error("mismatched types", span)
+                .with_label(format!("expected `{expected}`, found `{found}`")),
+            UnifyError::Infinite => Diagnostic::error("mismatched types", span)
+                .with_label(format!(
+                    "expected `{expected}`, found `{found}`, which contains it"
+                ))
+                .with_note("a value can't contain itself"),
+            UnifyError::NotInClass(class, ty) => Diagnostic::error("mismatched types", span)
+                .with_label(format!("expected {class}, found `{ty}`")),
+        }
+    }
+
+    fn error(&mut self, message: impl Into<String>, span: Span, label: &str) -> Ty {
+        self.diagnostics
+            .push(Diagnostic::error(message, span).with_label(label));
+        Ty::Error
+    }
+}
+
+/// Groups `functions` into strongly connected components of the call graph,
+/// with every component after the ones it calls into.
+fn components<'a>(functions: &[&'a Item]) -> Vec<Vec<&'a Item>> {
+    fn calls(expr: &Expr, names: &HashMap<&str, usize>, out: &mut Vec<usize>) {
+        if let ExprKind::Ident(name) = &expr.kind
+            && let Some(&i) = names.get(name.as_str())
+        {
+            out.push(i);
+        }
+        for child in expr.children() {
+            calls(child, names, out);
+        }
+    }
+
+    let names: HashMap<&str, usize> = functions
+        .iter()
+        .enumerate()
+        .map(|(i, item)| match &item.kind {
+            ItemKind::Function { name, .. } => (name.as_str(), i),
+            _ => unreachable!(),
+        })
+        .collect();
+    let edges: Vec<Vec<usize>> = functions
+        .iter()
+        .map(|item| {
+            let ItemKind::Function { body, .. } = &item.kind else {
+                unreachable!()
+            };
+            let mut out = Vec::new();
+            calls(body, &names, &mut out);
+            out
+        })
+        .collect();
+
+    // Tarjan's algorithm, which finds components callees first.
+    struct Tarjan<'e> {
+        edges: &'e [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, v: usize) {
+            self.index[v] = Some(self.next);
+            self.low[v] = self.next;
+            self.next += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+
+            for &w in &self.edges[v] {
+                match self.index[w] {
+                    None => {
+                        self.visit(w);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    }
+                    Some(index) if self.on_stack[w] => self.low[v] = self.low[v].min(index),
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[v]) == self.index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        edges: &edges,
+        index: vec![None; functions.len()],
+        low: vec![0; functions.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; functions.len()],
+        next: 0,
+        components: Vec::new(),
+    };
+    for v in 0..functions.len() {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+
+    tarjan
+        .components
+        .into_iter()
+        .map(|component| component.into_iter().map(|i| functions[i]).collect())
+        .collect()
+}
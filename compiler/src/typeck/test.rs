@@ -0,0 +1,188 @@
+use super::{Types, check};
+use crate::{desugar::desugar, natives::Natives, parser::Parser};
+
+fn check_source(input: &str) -> (Types, Vec<String>) {
+    let (mut ast, errors) = Parser::new(input).file();
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(desugar(&mut ast).is_empty());
+
+    let (types, diagnostics) = check(&ast, &Natives::builtins());
+    let messages = diagnostics
+        .into_iter()
+        .map(|diag| format!("{}: {}", diag.message, diag.primary.message))
+        .collect();
+    (types, messages)
+}
+
+/// The type of `item` in `input`, which must check without errors.
+fn item_type(input: &str, item: &str) -> String {
+    let (types, errors) = check_source(input);
+    assert!(errors.is_empty(), "{errors:?}");
+    types.items[item].to_string()
+}
+
+fn errors(input: &str) -> Vec<String> {
+    check_source(input).1
+}
+
+#[test]
+fn infer_functions() {
+    let source = r#"
+        fn sum(a, b) -> a + b
+        fn sum_annotated(a: Int, b: Int): Int -> a + b
+        fn id(x) -> x
+        fn apply(f, x) -> f(x)
+        fn len_plus(xs, n) -> len(xs) + n
+        fn both() -> (sum(1, 2), sum("a", "b"), id(id)(true))
+        const sum_lambda: fn(Int, Int): Int = |a, b| -> a + b
+        const inc: fn(Int): Int = sum(1, _)
+    "#;
+    let expectations = [
+        ("sum", "fn('a, 'a): 'a where 'a: Int | Float | Str"),
+        ("sum_annotated", "fn(Int, Int): Int"),
+        ("id", "fn('a): 'a"),
+        ("apply", "fn(fn('a): 'b, 'a): 'b"),
+        ("len_plus", "fn('a, Int): Int"),
+        ("both", "fn(): (Int, Str, Bool)"),
+        ("sum_lambda", "fn(Int, Int): Int"),
+        ("inc", "fn(Int): Int"),
+    ];
+    for (item, expected) in expectations {
+        assert_eq!(item_type(source, item), expected, "type of `{item}`");
+    }
+
+    // The type of a tuple field can't be found without knowing the tuple.
+    assert_eq!(
+        errors("fn first(pair) -> pair._0"),
+        ["the type of this value must be known to access `_0`: type annotations needed"]
+    );
+}
+
+#[test]
+fn infer_recursive_functions() {
+    let source = "
+        fn is_even(n) -> if (n == 0) true else is_odd(n - 1)
+        fn is_odd(n) -> if (n == 0) false else is_even(n - 1)
+        fn fact(n) -> if (n < 2) 1 else n * fact(n - 1)
+    ";
+    assert_eq!(item_type(source, "is_even"), "fn(Int): Bool");
+    assert_eq!(item_type(source, "is_odd"), "fn(Int): Bool");
+    assert_eq!(item_type(source, "fact"), "fn(Int): Int");
+}
+
+#[test]
+fn let_polymorphism() {
+    assert_eq!(
+        item_type(
+            r#"fn main() -> { let id = |x| -> x; (id(1), id("a")) }"#,
+            "main"
+        ),
+        "fn(): (Int, Str)"
+    );
+
+    // Mutable bindings aren't generalized.
+    assert_eq!(
+        errors(r#"fn main() -> { let mut id = |x| -> x; (id(1), id("a")) }"#),
+        ["mismatched types: expected `Int`, found `Str`"]
+    );
+}
+
+#[test]
+fn infer_structs_and_enums() {
+    let source = "
+        struct Point { x: Int, y: Int }
+        enum Option<T> {
+            Some(T),
+            None,
+        }
+        struct Wrapper<T> { value: T }
+
+        fn get_x(p) -> p.x
+        fn unwrap_or(option, default) -> match option {
+            Option::Some(x) -> x,
+            Option::None -> default,
+        }
+        fn wrap(value) -> Wrapper { value }
+        fn some() -> Option::Some
+    ";
+    assert_eq!(item_type(source, "get_x"), "fn(Point): Int");
+    assert_eq!(item_type(source, "unwrap_or"), "fn(Option<'a>, 'a): 'a");
+    assert_eq!(item_type(source, "wrap"), "fn('a): Wrapper<'a>");
+    assert_eq!(item_type(source, "some"), "fn(): fn('a): Option<'a>");
+}
+
+#[test]
+fn report_type_errors() {
+    assert_eq!(
+        errors(r#"fn f() -> 1 + "a""#),
+        ["mismatched types: expected `Int`, found `Str`"]
+    );
+    assert_eq!(
+        errors("fn f() -> true + false"),
+        ["mismatched types: expected `Int`, `Float` or `Str`, found `Bool`"]
+    );
+    assert_eq!(
+        errors("fn f(x) -> x(x)"),
+        ["mismatched types: expected `fn('a): 'b`, found `'a`, which contains it"]
+    );
+    assert_eq!(
+        errors("fn f(a, b) -> a fn g() -> f(1)"),
+        ["this function takes 2 arguments, but 1 was supplied: wrong number of arguments"]
+    );
+    assert_eq!(
+        errors("fn f() -> if (1) 2 else 3"),
+        ["mismatched types: expected `Bool`, found `Int`"]
+    );
+    assert_eq!(errors("fn f() -> x"), ["cannot find value `x`: not found"]);
+    assert_eq!(
+        errors("struct P { x: Int } fn f() -> P { x: 1, y: 2 }"),
+        ["`P` has no field named `y`: unknown field"]
+    );
+    assert_eq!(
+        errors("struct P<T> { x: T } fn f(p: P<Int, Int>) -> p"),
+        ["`P` takes 1 generic argument, but 2 were supplied: wrong number of generics"]
+    );
+    assert_eq!(
+        errors("struct P { x: Int } fn f() -> P { x: 1, x: 2 }"),
+        ["field `x` is specified more than once: used again here"]
+    );
+    assert_eq!(
+        errors("struct P { x: Int } fn f(p) -> match p { P { x: 1, x } -> x }"),
+        ["field `x` is matched more than once: used again here"]
+    );
+    assert_eq!(
+        errors("fn f(x: Foo) -> x"),
+        ["cannot find type `Foo`: not defined"]
+    );
+}
+
+#[test]
+fn report_mismatched_annotations() {
+    let (mut ast, _) = Parser::new("const sum_lambda: fn(Int, Int): Int = |a, b| -> a == b").file();
+    desugar(&mut ast);
+    let (_, diagnostics) = check(&ast, &Natives::builtins());
+
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic.message, "mismatched types");
+    assert_eq!(
+        diagnostic.primary.message,
+        "expected `fn(Int, Int): Int`, found `fn(Int, Int): Bool`"
+    );
+    assert_eq!(
+        diagnostic.secondary[0].message,
+        "expected because of this annotation"
+    );
+    assert_eq!(
+        (
+            diagnostic.secondary[0].span.start,
+            diagnostic.secondary[0].span.end
+        ),
+        (18, 35)
+    );
+
+    assert_eq!(
+        errors("fn f(a: Int): Str -> a"),
+        ["mismatched types: expected `Str`, found `Int`"]
+    );
+}
@@ -0,0 +1,227 @@
+use std::fmt::{self, Display};
+
+/// An inference variable, standing for a type that isn't known yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TyVar(pub(super) u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Var(TyVar),
+    /// The `n`th variable quantified by a `Scheme`, or the `n`th generic
+    /// parameter of a struct or enum definition.
+    Gen(u32),
+    /// A primitive like `Int`, or a struct or enum applied to its generics.
+    Named(String, Vec<Ty>),
+    Array(Box<Ty>),
+    /// The empty tuple is the unit type.
+    Tuple(Vec<Ty>),
+    Fn(Vec<Ty>, Box<Ty>),
+    /// Stands in for the type of anything that already failed to check, and
+    /// unifies with everything so the failure isn't reported again.
+    Error,
+}
+
+impl Ty {
+    pub fn named(name: &str) -> Ty {
+        Ty::Named(name.into(), Vec::new())
+    }
+
+    pub fn int() -> Ty {
+        Ty::named("Int")
+    }
+
+    pub fn float() -> Ty {
+        Ty::named("Float")
+    }
+
+    pub fn str() -> Ty {
+        Ty::named("Str")
+    }
+
+    pub fn char() -> Ty {
+        Ty::named("Char")
+    }
+
+    pub fn bool() -> Ty {
+        Ty::named("Bool")
+    }
+
+    pub fn unit() -> Ty {
+        Ty::Tuple(Vec::new())
+    }
+
+    /// Replaces every `Gen(n)` with `generics[n]`.
+    pub(super) fn substitute(&self, generics: &[Ty]) -> Ty {
+        let all = |types: &[Ty]| types.iter().map(|ty| ty.substitute(generics)).collect();
+        match self {
+            Ty::Gen(n) => generics[*n as usize].clone(),
+            Ty::Named(name, args) => Ty::Named(name.clone(), all(args)),
+            Ty::Array(elem) => Ty::Array(Box::new(elem.substitute(generics))),
+            Ty::Tuple(types) => Ty::Tuple(all(types)),
+            Ty::Fn(params, result) => Ty::Fn(all(params), Box::new(result.substitute(generics))),
+            Ty::Var(_) | Ty::Error => self.clone(),
+        }
+    }
+}
+
+/// The set of primitive types an overloaded operator accepts, like `Int` and
+/// `Float` for `-`. Variables constrained to a class can only be unified with
+/// a type in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Class(u8);
+
+const PRIMITIVES: [&str; 5] = ["Int", "Float", "Str", "Char", "Bool"];
+
+impl Class {
+    pub const NUM: Class = Class(0b00011);
+    pub const ADD: Class = Class(0b00111);
+    pub const ORD: Class = Class(0b01111);
+    pub const BITS: Class = Class(0b10001);
+
+    pub fn contains(self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Named(name, args) if args.is_empty() => PRIMITIVES
+                .iter()
+                .position(|primitive| primitive == name)
+                .is_some_and(|i| self.0 & (1 << i) != 0),
+            _ => false,
+        }
+    }
+
+    /// The types in both classes, if there are any.
+    pub fn intersect(self, other: Class) -> Option<Class> {
+        let both = self.0 & other.0;
+        (both != 0).then_some(Class(both))
+    }
+
+    fn members(self) -> impl Iterator<Item = &'static str> {
+        PRIMITIVES
+            .into_iter()
+            .enumerate()
+            .filter(move |(i, _)| self.0 & (1 << i) != 0)
+            .map(|(_, name)| name)
+    }
+}
+
+/// Lists the types, like "`Int`, `Float` or `Str`".
+impl Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let members: Vec<_> = self.members().map(|name| format!("`{name}`")).collect();
+        match members.split_last() {
+            Some((last, [])) => write!(f, "{last}"),
+            Some((last, rest)) => write!(f, "{} or {last}", rest.join(", ")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A possibly polymorphic type, like `fn('a): 'a` for the identity function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheme {
+    /// The class each quantified variable is restricted to, if any, indexed
+    /// by the `n` in `Ty::Gen(n)`.
+    pub vars: Vec<Option<Class>>,
+    pub ty: Ty,
+}
+
+impl Scheme {
+    pub fn mono(ty: Ty) -> Scheme {
+        Scheme {
+            vars: Vec::new(),
+            ty,
+        }
+    }
+}
+
+/// Names variables `'a`, `'b`, ... in the order they're first printed.
+#[derive(Default)]
+struct Names(Vec<Ty>);
+
+impl Names {
+    fn name(&mut self, var: &Ty) -> String {
+        let i = match self.0.iter().position(|seen| seen == var) {
+            Some(i) => i,
+            None => {
+                self.0.push(var.clone());
+                self.0.len() - 1
+            }
+        };
+
+        let letter = (b'a' + (i % 26) as u8) as char;
+        match i / 26 {
+            0 => format!("'{letter}"),
+            n => format!("'{letter}{n}"),
+        }
+    }
+
+    fn write(&mut self, f: &mut fmt::Formatter<'_>, ty: &Ty) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, names: &mut Self, types: &[Ty]| {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                names.write(f, ty)?;
+            }
+            Ok(())
+        };
+
+        match ty {
+            Ty::Var(_) | Ty::Gen(_) => write!(f, "{}", self.name(ty)),
+            Ty::Named(name, args) if args.is_empty() => write!(f, "{name}"),
+            Ty::Named(name, args) => {
+                write!(f, "{name}<")?;
+                list(f, self, args)?;
+                write!(f, ">")
+            }
+            Ty::Array(elem) => {
+                write!(f, "[")?;
+                self.write(f, elem)?;
+                write!(f, "]")
+            }
+            Ty::Tuple(types) => {
+                write!(f, "(")?;
+                list(f, self, types)?;
+                write!(f, ")")
+            }
+            Ty::Fn(params, result) => {
+                write!(f, "fn(")?;
+                list(f, self, params)?;
+                write!(f, "): ")?;
+                self.write(f, result)
+            }
+            Ty::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+/// Written the way types are in source, with variables named `'a`, `'b`, ...
+impl Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Names::default().write(f, self)
+    }
+}
+
+/// Like `Ty`, followed by the classes of any restricted variables, as in
+/// `fn('a, 'a): 'a where 'a: Int | Float | Str`.
+impl Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Names::default();
+        names.write(f, &self.ty)?;
+
+        let mut separator = " where ";
+        for var in names.0.clone() {
+            let Ty::Gen(n) = var else { continue };
+            if let Some(Some(class)) = self.vars.get(n as usize) {
+                let members: Vec<_> = class.members().collect();
+                write!(
+                    f,
+                    "{separator}{}: {}",
+                    names.name(&var),
+                    members.join(" | ")
+                )?;
+                separator = ", ";
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use super::ty::{Class, Scheme, Ty, TyVar};
+
+#[derive(Debug, Clone)]
+enum VarState {
+    Bound(Ty),
+    Unbound {
+        /// How many generalizable `let`s deep the variable was created, so
+        /// that generalizing a `let` only quantifies the variables that don't
+        /// also appear in its surroundings.
+        level: u32,
+        class: Option<Class>,
+    },
+}
+
+/// Why two types couldn't be unified.
+#[derive(Debug)]
+pub(super) enum UnifyError {
+    Mismatch,
+    /// A variable would have to contain itself.
+    Infinite,
+    /// A type isn't in the class a variable is restricted to.
+    NotInClass(Class, Ty),
+}
+
+/// The substitution built up by unification.
+#[derive(Default)]
+pub(super) struct Table {
+    vars: Vec<VarState>,
+}
+
+impl Table {
+    pub fn fresh(&mut self, level: u32, class: Option<Class>) -> Ty {
+        self.vars.push(VarState::Unbound { level, class });
+        Ty::Var(TyVar(self.vars.len() as u32 - 1))
+    }
+
+    /// Follows bound variables until reaching a type that isn't one.
+    pub fn shallow_resolve(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+        while let Ty::Var(var) = ty {
+            match &self.vars[var.0 as usize] {
+                VarState::Bound(bound) => ty = bound.clone(),
+                VarState::Unbound { .. } => break,
+            }
+        }
+        ty
+    }
+
+    /// Substitutes every bound variable in `ty`.
+    pub fn resolve(&self, ty: &Ty) -> Ty {
+        let all = |types: &[Ty]| types.iter().map(|ty| self.resolve(ty)).collect();
+        match self.shallow_resolve(ty) {
+            Ty::Named(name, args) => Ty::Named(name, all(&args)),
+            Ty::Array(elem) => Ty::Array(Box::new(self.resolve(&elem))),
+            Ty::Tuple(types) => Ty::Tuple(all(&types)),
+            Ty::Fn(params, result) => Ty::Fn(all(&params), Box::new(self.resolve(&result))),
+            ty @ (Ty::Var(_) | Ty::Gen(_) | Ty::Error) => ty,
+        }
+    }
+
+    pub fn unify(&mut self, a: &Ty, b: &Ty) -> Result<(), UnifyError> {
+        let a = self.shallow_resolve(a);
+        let b = self.shallow_resolve(b);
+
+        match (&a, &b) {
+            (Ty::Error, _) | (_, Ty::Error) => Ok(()),
+            (Ty::Var(a), Ty::Var(b)) if a == b => Ok(()),
+            (Ty::Var(a), Ty::Var(b)) => {
+                let (
+                    VarState::Unbound {
+                        level: a_level,
+                        class: a_class,
+                    },
+                    VarState::Unbound {
+                        level: b_level,
+                        class: b_class,
+                    },
+                ) = (&self.vars[a.0 as usize], &self.vars[b.0 as usize])
+                else {
+                    unreachable!("resolved variables are unbound")
+                };
+
+                let class = match (a_class, b_class) {
+                    (Some(a_class), Some(b_class)) => {
+                        Some(a_class.intersect(*b_class).ok_or(UnifyError::Mismatch)?)
+                    }
+                    (class, None) | (None, class) => *class,
+                };
+                self.vars[b.0 as usize] = VarState::Unbound {
+                    level: (*a_level).min(*b_level),
+                    class,
+                };
+                self.vars[a.0 as usize] = VarState::Bound(Ty::Var(*b));
+                Ok(())
+            }
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => self.bind(*var, ty),
+            (Ty::Named(a_name, a_args), Ty::Named(b_name, b_args))
+                if a_name == b_name && a_args.len() == b_args.len() =>
+            {
+                self.unify_all(a_args, b_args)
+            }
+            (Ty::Array(a), Ty::Array(b)) => self.unify(a, b),
+            (Ty::Tuple(a), Ty::Tuple(b)) if a.len() == b.len() => self.unify_all(a, b),
+            (Ty::Fn(a_params, a_result), Ty::Fn(b_params, b_result))
+                if a_params.len() == b_params.len() =>
+            {
+                self.unify_all(a_params, b_params)?;
+                self.unify(a_result, b_result)
+            }
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    fn unify_all(&mut self, a: &[Ty], b: &[Ty]) -> Result<(), UnifyError> {
+        a.iter().zip(b).try_for_each(|(a, b)| self.unify(a, b))
+    }
+
+    fn bind(&mut self, var: TyVar, ty: &Ty) -> Result<(), UnifyError> {
+        let VarState::Unbound { level, class } = self.vars[var.0 as usize] else {
+            unreachable!("resolved variables are unbound")
+        };
+
+        if let Some(class) = class
+            && !class.contains(&self.resolve(ty))
+        {
+            return Err(UnifyError::NotInClass(class, self.resolve(ty)));
+        }
+        self.occurs(var, level, ty)?;
+
+        self.vars[var.0 as usize] = VarState::Bound(ty.clone());
+        Ok(())
+    }
+
+    /// Checks that `var` doesn't appear in `ty`, and lowers the level of the
+    /// variables in `ty` to `level`, since they're now reachable from `var`.
+    fn occurs(&mut self, var: TyVar, level: u32, ty: &Ty) -> Result<(), UnifyError> {
+        match self.shallow_resolve(ty) {
+            Ty::Var(other) if other == var => Err(UnifyError::Infinite),
+            Ty::Var(other) => {
+                if let VarState::Unbound {
+                    level: other_level, ..
+                } = &mut self.vars[other.0 as usize]
+                {
+                    *other_level = (*other_level).min(level);
+                }
+                Ok(())
+            }
+            Ty::Named(_, types) | Ty::Tuple(types) => {
+                types.iter().try_for_each(|ty| self.occurs(var, level, ty))
+            }
+            Ty::Array(elem) => self.occurs(var, level, &elem),
+            Ty::Fn(params, result) => {
+                params
+                    .iter()
+                    .try_for_each(|ty| self.occurs(var, level, ty))?;
+                self.occurs(var, level, &result)
+            }
+            Ty::Gen(_) | Ty::Error => Ok(()),
+        }
+    }
+
+    /// Quantifies the variables in `ty` that were created deeper than `level`.
+    pub fn generalize(&self, ty: &Ty, level: u32) -> Scheme {
+        fn walk(
+            table: &Table,
+            ty: &Ty,
+            level: u32,
+            quantified: &mut Vec<(TyVar, Option<Class>)>,
+        ) -> Ty {
+            let mut all = |types: &[Ty]| {
+                types
+                    .iter()
+                    .map(|ty| walk(table, ty, level, quantified))
+                    .collect()
+            };
+            match table.shallow_resolve(ty) {
+                Ty::Var(var) => match table.vars[var.0 as usize] {
+                    VarState::Unbound {
+                        level: var_level,
+                        class,
+                    } if var_level > level => {
+                        let n = match quantified.iter().position(|(seen, _)| *seen == var) {
+                            Some(n) => n,
+                            None => {
+                                quantified.push((var, class));
+                                quantified.len() - 1
+                            }
+                        };
+                        Ty::Gen(n as u32)
+                    }
+                    _ => Ty::Var(var),
+                },
+                Ty::Named(name, args) => Ty::Named(name, all(&args)),
+                Ty::Array(elem) => Ty::Array(Box::new(walk(table, &elem, level, quantified))),
+                Ty::Tuple(types) => Ty::Tuple(all(&types)),
+                Ty::Fn(params, result) => {
+                    let params = all(&params);
+                    Ty::Fn(params, Box::new(walk(table, &result, level, quantified)))
+                }
+                ty @ (Ty::Gen(_) | Ty::Error) => ty,
+            }
+        }
+
+        let mut quantified = Vec::new();
+        let ty = walk(self, ty, level, &mut quantified);
+        Scheme {
+            vars: quantified.into_iter().map(|(_, class)| class).collect(),
+            ty,
+        }
+    }
+
+    /// Replaces the quantified variables in `scheme` with fresh ones.
+    pub fn instantiate(&mut self, scheme: &Scheme, level: u32) -> Ty {
+        let vars: Vec<_> = scheme
+            .vars
+            .iter()
+            .map(|class| self.fresh(level, *class))
+            .collect();
+        scheme.ty.substitute(&vars)
+    }
+}
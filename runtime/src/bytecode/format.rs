@@ -0,0 +1,433 @@
+//! The binary encoding of modules.
+//!
+//! A module starts with `MAGIC` and the format's version as a little-endian
+//! `u16`. Integers after that which count or index things are LEB128
+//! varints, and strings are a varint length followed by UTF-8. Each
+//! instruction is an opcode byte, followed by its operand if it has one, and
+//! each function's line table is a list of runs of instructions sharing a
+//! line.
+
+use std::{error::Error, fmt};
+
+use super::{Capture, Constant, Fields, Function, Module, Op, Shape};
+
+/// The first bytes of every module file.
+pub const MAGIC: [u8; 4] = *b"PTNC";
+
+/// Bumped whenever the encoding changes, so that old modules are rejected
+/// rather than misread.
+pub const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The data doesn't start with `MAGIC`.
+    NotAModule,
+    /// The module was written in a different version of the format.
+    Version(u16),
+    Malformed(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotAModule => write!(f, "not a compiled patinac module"),
+            DecodeError::Version(version) => write!(
+                f,
+                "the module was compiled for format version {version}, but this runtime only \
+                 supports version {VERSION}; recompile it with a matching compiler"
+            ),
+            DecodeError::Malformed(message) => write!(f, "invalid module: {message}"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+type DecodeResult<T> = Result<T, DecodeError>;
+
+impl Module {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+
+        w.bytes(&MAGIC);
+        w.bytes(&VERSION.to_le_bytes());
+        w.str(&self.source);
+        w.list(&self.constants, |w, constant| match constant {
+            Constant::Int(int) => {
+                w.byte(0);
+                w.bytes(&int.to_le_bytes());
+            }
+            Constant::Float(float) => {
+                w.byte(1);
+                w.bytes(&float.to_le_bytes());
+            }
+            Constant::Str(string) => {
+                w.byte(2);
+                w.str(string);
+            }
+            Constant::Char(char) => {
+                w.byte(3);
+                w.uint(*char as u32);
+            }
+        });
+        w.list(&self.shapes, |w, shape| {
+            w.str(&shape.name);
+            match &shape.fields {
+                Fields::Unit => w.byte(0),
+                Fields::Tuple(len) => {
+                    w.byte(1);
+                    w.uint(*len);
+                }
+                Fields::Named(names) => {
+                    w.byte(2);
+                    w.list(names, |w, name| w.str(name));
+                }
+            }
+        });
+        w.list(&self.natives, |w, name| w.str(name));
+        w.list(&self.globals, |w, name| w.str(name));
+        w.list(&self.functions, |w, function| {
+            w.str(&function.name);
+            w.byte(function.arity);
+            w.list(&function.captures, |w, capture| match capture {
+                Capture::Local(slot) => {
+                    w.byte(0);
+                    w.uint(*slot as u32);
+                }
+                Capture::Upvalue(index) => {
+                    w.byte(1);
+                    w.uint(*index as u32);
+                }
+            });
+            w.list(&function.code, |w, op| {
+                let (opcode, operand) = op.encode();
+                w.byte(opcode);
+                if let Some(operand) = operand {
+                    w.uint(operand);
+                }
+            });
+            w.list(&runs(&function.lines), |w, (len, line)| {
+                w.uint(*len);
+                w.uint(*line);
+            });
+        });
+        w.uint(self.entry);
+        w.uint(self.init);
+
+        w.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> DecodeResult<Module> {
+        let mut r = Reader { bytes, pos: 0 };
+
+        if r.array().ok() != Some(MAGIC) {
+            return Err(DecodeError::NotAModule);
+        }
+        let version = u16::from_le_bytes(r.array()?);
+        if version != VERSION {
+            return Err(DecodeError::Version(version));
+        }
+
+        let source = r.str()?;
+        let constants = r.list(|r| {
+            Ok(match r.byte()? {
+                0 => Constant::Int(i64::from_le_bytes(r.array()?)),
+                1 => Constant::Float(f64::from_le_bytes(r.array()?)),
+                2 => Constant::Str(r.str()?),
+                3 => Constant::Char(
+                    char::from_u32(r.uint()?).ok_or_else(|| r.error("invalid char constant"))?,
+                ),
+                tag => return Err(r.error(format!("unknown constant tag {tag}"))),
+            })
+        })?;
+        let shapes = r.list(|r| {
+            let name = r.str()?;
+            let fields = match r.byte()? {
+                0 => Fields::Unit,
+                1 => Fields::Tuple(r.uint()?),
+                2 => Fields::Named(r.list(Reader::str)?),
+                tag => return Err(r.error(format!("unknown field layout {tag}"))),
+            };
+            Ok(Shape { name, fields })
+        })?;
+        let natives = r.list(Reader::str)?;
+        let globals = r.list(Reader::str)?;
+        let functions = r.list(|r| {
+            let name = r.str()?;
+            let arity = r.byte()?;
+            let captures = r.list(|r| {
+                let tag = r.byte()?;
+                let index = r.operand()?;
+                match tag {
+                    0 => Ok(Capture::Local(index)),
+                    1 => Ok(Capture::Upvalue(index)),
+                    tag => Err(r.error(format!("unknown capture kind {tag}"))),
+                }
+            })?;
+            let code = r.list(Reader::op)?;
+            let mismatched =
+                |r: &Reader| r.error(format!("`{name}` has the wrong number of lines"));
+            let mut lines = Vec::new();
+            for (len, line) in r.list(|r| Ok((r.uint()?, r.uint()?)))? {
+                if lines.len() + len as usize > code.len() {
+                    return Err(mismatched(r));
+                }
+                lines.extend(std::iter::repeat_n(line, len as usize));
+            }
+            if !lines.is_empty() && lines.len() != code.len() {
+                return Err(mismatched(r));
+            }
+            Ok(Function {
+                name,
+                arity,
+                captures,
+                code,
+                lines,
+            })
+        })?;
+        let entry = r.uint()?;
+        let init = r.uint()?;
+
+        if r.pos != bytes.len() {
+            return Err(r.error("unexpected data after the end of the module"));
+        }
+        Ok(Module {
+            constants,
+            shapes,
+            natives,
+            globals,
+            functions,
+            entry,
+            init,
+            source,
+        })
+    }
+}
+
+/// Splits `lines` into runs of the same line, as `(length, line)` pairs.
+fn runs(lines: &[u32]) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match runs.last_mut() {
+            Some((len, last)) if *last == line => *len += 1,
+            _ => runs.push((1, line)),
+        }
+    }
+    runs
+}
+
+impl Op {
+    /// The instruction's opcode, and its operand if it has one.
+    fn encode(self) -> (u8, Option<u32>) {
+        match self {
+            Op::Const(n) => (0, Some(n)),
+            Op::Unit => (1, None),
+            Op::True => (2, None),
+            Op::False => (3, None),
+            Op::Pop => (4, None),
+            Op::Dup => (5, None),
+            Op::GetLocal(n) => (6, Some(n.into())),
+            Op::SetLocal(n) => (7, Some(n.into())),
+            Op::GetUpvalue(n) => (8, Some(n.into())),
+            Op::SetUpvalue(n) => (9, Some(n.into())),
+            Op::GetGlobal(n) => (10, Some(n)),
+            Op::SetGlobal(n) => (11, Some(n)),
+            Op::Native(n) => (12, Some(n)),
+            Op::Closure(n) => (13, Some(n)),
+            Op::EndScope(n) => (14, Some(n.into())),
+            Op::Call(n) => (15, Some(n.into())),
+            Op::Return => (16, None),
+            Op::Jump(n) => (17, Some(n)),
+            Op::JumpIfFalse(n) => (18, Some(n)),
+            Op::Add => (19, None),
+            Op::Sub => (20, None),
+            Op::Mul => (21, None),
+            Op::Div => (22, None),
+            Op::Exp => (23, None),
+            Op::BitAnd => (24, None),
+            Op::BitOr => (25, None),
+            Op::BitXor => (26, None),
+            Op::Eq => (27, None),
+            Op::Neq => (28, None),
+            Op::Lt => (29, None),
+            Op::Gt => (30, None),
+            Op::Leq => (31, None),
+            Op::Geq => (32, None),
+            Op::Neg => (33, None),
+            Op::Not => (34, None),
+            Op::Array(n) => (35, Some(n)),
+            Op::Tuple(n) => (36, Some(n)),
+            Op::Record(n) => (37, Some(n)),
+            Op::Index => (38, None),
+            Op::SetIndex => (39, None),
+            Op::GetField(n) => (40, Some(n)),
+            Op::SetField(n) => (41, Some(n)),
+            Op::Element(n) => (42, Some(n)),
+            Op::Len => (43, None),
+            Op::IsShape(n) => (44, Some(n)),
+            Op::NoMatch => (45, None),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.0.push(byte);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn uint(&mut self, mut n: u32) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.byte(byte);
+                return;
+            }
+            self.byte(byte | 0x80);
+        }
+    }
+
+    fn str(&mut self, string: &str) {
+        self.uint(string.len() as u32);
+        self.bytes(string.as_bytes());
+    }
+
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T)) {
+        self.uint(items.len() as u32);
+        for it in items {
+            item(self, it);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl fmt::Display) -> DecodeError {
+        DecodeError::Malformed(format!("{message} at byte {}", self.pos))
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| self.error("unexpected end of module"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn array<const N: usize>(&mut self) -> DecodeResult<[u8; N]> {
+        let mut array = [0; N];
+        for byte in &mut array {
+            *byte = self.byte()?;
+        }
+        Ok(array)
+    }
+
+    fn uint(&mut self) -> DecodeResult<u32> {
+        let mut n: u32 = 0;
+        for shift in (0..32).step_by(7) {
+            let byte = self.byte()?;
+            n |= u32::from(byte & 0x7f)
+                .checked_shl(shift)
+                .filter(|bits| bits >> shift == u32::from(byte & 0x7f))
+                .ok_or_else(|| self.error("integer too large"))?;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(self.error("integer too large"))
+    }
+
+    /// A varint that has to fit in a smaller integer type.
+    fn operand<T: TryFrom<u32>>(&mut self) -> DecodeResult<T> {
+        let n = self.uint()?;
+        T::try_from(n).map_err(|_| self.error(format!("operand {n} out of range")))
+    }
+
+    fn str(&mut self) -> DecodeResult<String> {
+        let len = self.uint()? as usize;
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| self.error("unexpected end of module"))?;
+        self.pos += len;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> DecodeResult<T>,
+    ) -> DecodeResult<Vec<T>> {
+        let len = self.uint()?;
+        // Don't trust the length for the allocation, in case it's corrupt.
+        let mut items = Vec::with_capacity((len as usize).min(1024));
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn op(&mut self) -> DecodeResult<Op> {
+        let opcode = self.byte()?;
+        Ok(match opcode {
+            0 => Op::Const(self.uint()?),
+            1 => Op::Unit,
+            2 => Op::True,
+            3 => Op::False,
+            4 => Op::Pop,
+            5 => Op::Dup,
+            6 => Op::GetLocal(self.operand()?),
+            7 => Op::SetLocal(self.operand()?),
+            8 => Op::GetUpvalue(self.operand()?),
+            9 => Op::SetUpvalue(self.operand()?),
+            10 => Op::GetGlobal(self.uint()?),
+            11 => Op::SetGlobal(self.uint()?),
+            12 => Op::Native(self.uint()?),
+            13 => Op::Closure(self.uint()?),
+            14 => Op::EndScope(self.operand()?),
+            15 => Op::Call(self.operand()?),
+            16 => Op::Return,
+            17 => Op::Jump(self.uint()?),
+            18 => Op::JumpIfFalse(self.uint()?),
+            19 => Op::Add,
+            20 => Op::Sub,
+            21 => Op::Mul,
+            22 => Op::Div,
+            23 => Op::Exp,
+            24 => Op::BitAnd,
+            25 => Op::BitOr,
+            26 => Op::BitXor,
+            27 => Op::Eq,
+            28 => Op::Neq,
+            29 => Op::Lt,
+            30 => Op::Gt,
+            31 => Op::Leq,
+            32 => Op::Geq,
+            33 => Op::Neg,
+            34 => Op::Not,
+            35 => Op::Array(self.uint()?),
+            36 => Op::Tuple(self.uint()?),
+            37 => Op::Record(self.uint()?),
+            38 => Op::Index,
+            39 => Op::SetIndex,
+            40 => Op::GetField(self.uint()?),
+            41 => Op::SetField(self.uint()?),
+            42 => Op::Element(self.uint()?),
+            43 => Op::Len,
+            44 => Op::IsShape(self.uint()?),
+            45 => Op::NoMatch,
+            opcode => return Err(self.error(format!("unknown opcode {opcode}"))),
+        })
+    }
+}
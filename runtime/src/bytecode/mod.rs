@@ -0,0 +1,169 @@
+//! The instruction set, and the modules that hold compiled programs.
+
+mod format;
+
+pub use format::{DecodeError, MAGIC, VERSION};
+
+/// A single VM instruction. Operands index into the tables of the module or
+/// function being run, or count the values an instruction works on.
+///
+/// Instructions that consume values pop them, with the last operand on top.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Pushes `constants[n]`.
+    Const(u32),
+    Unit,
+    True,
+    False,
+    Pop,
+    Dup,
+
+    /// Pushes the `n`th local of the current call, counting parameters first.
+    GetLocal(u16),
+    /// Pops a value into the `n`th local.
+    SetLocal(u16),
+    /// Pushes the value of the current closure's `n`th upvalue.
+    GetUpvalue(u16),
+    SetUpvalue(u16),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    /// Pushes the native function `natives[n]`.
+    Native(u32),
+    /// Pushes a closure over `functions[n]`, capturing what its `captures` say.
+    Closure(u32),
+    /// Keeps the value on top of the stack, but pops the `n` locals under it,
+    /// closing any upvalues that captured them.
+    EndScope(u16),
+
+    /// Calls the function under `n` arguments.
+    Call(u8),
+    Return,
+    /// Continues at instruction `n` of the current function.
+    Jump(u32),
+    /// Pops a `Bool`, and jumps to instruction `n` if it's false.
+    JumpIfFalse(u32),
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Exp,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Leq,
+    Geq,
+    Neg,
+    Not,
+
+    /// Collects `n` values into an array.
+    Array(u32),
+    /// Collects `n` values into a tuple.
+    Tuple(u32),
+    /// Collects the fields of `shapes[n]`, in order, into a struct or variant.
+    Record(u32),
+    /// Pops an index and an array, and pushes the element.
+    Index,
+    /// Pops a value, an index and an array, and pushes a copy of the array
+    /// with the element replaced.
+    SetIndex,
+    /// Pushes the field named by the `Str` constant `n`. Tuple fields are
+    /// named `_0`, `_1`, ...
+    GetField(u32),
+    /// Pops a value and a tuple or record, and pushes a copy with the field
+    /// named by the `Str` constant `n` replaced.
+    SetField(u32),
+    /// Pushes the `n`th element of an array, tuple or record.
+    Element(u32),
+    /// Pushes the number of elements in an array or tuple, or characters in
+    /// a string.
+    Len,
+    /// Pops a value, and pushes whether it's a record of `shapes[n]`.
+    IsShape(u32),
+    /// Pops the value a `match` was on, and stops with an error since none
+    /// of its arms matched.
+    NoMatch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Char(char),
+}
+
+/// The layout of a struct or enum variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    /// `Struct` or `Enum::Variant`.
+    pub name: String,
+    pub fields: Fields,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fields {
+    Unit,
+    Tuple(u32),
+    Named(Vec<String>),
+}
+
+impl Fields {
+    pub fn len(&self) -> usize {
+        match self {
+            Fields::Unit => 0,
+            Fields::Tuple(len) => *len as usize,
+            Fields::Named(names) => names.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Where a closure gets one of its upvalues from when it's created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    /// A local of the function creating the closure.
+    Local(u16),
+    /// One of the creating closure's own upvalues.
+    Upvalue(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub arity: u8,
+    pub captures: Vec<Capture>,
+    pub code: Vec<Op>,
+    /// The source line each instruction came from, or 0 if it's unknown.
+    /// Either empty or as long as `code`.
+    pub lines: Vec<u32>,
+}
+
+/// A compiled program.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Module {
+    pub constants: Vec<Constant>,
+    pub shapes: Vec<Shape>,
+    /// The names of the native functions the module uses, looked up when
+    /// it's loaded.
+    pub natives: Vec<String>,
+    /// The names of the module's global variables, which start out as `()`.
+    pub globals: Vec<String>,
+    pub functions: Vec<Function>,
+    /// The function taking no arguments that runs the program, and returns
+    /// its result.
+    pub entry: u32,
+    /// The function taking no arguments that sets the globals up, for hosts
+    /// calling into the module without running the whole program. The entry
+    /// point calls it first.
+    pub init: u32,
+    /// The file the module was compiled from, for error messages.
+    pub source: String,
+}
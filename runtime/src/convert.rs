@@ -0,0 +1,361 @@
+//! Conversions between Rust values and patinac ones, for hosts calling into
+//! the VM and for the natives they register.
+
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+use crate::{heap::Object, value::Value, vm::NativeFn, vm::Vm};
+
+/// A patinac type, as far as a host can name one from Rust.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Unit,
+    Bool,
+    Int,
+    Float,
+    Char,
+    Str,
+    Array(Box<Type>),
+    Tuple(Vec<Type>),
+    /// Any type at all, for values passed through as a raw [`Value`].
+    Any,
+}
+
+/// The types of a native function's parameters and result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub result: Type,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unit => write!(f, "()"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Char => write!(f, "Char"),
+            Type::Str => write!(f, "Str"),
+            Type::Array(element) => write!(f, "[{element}]"),
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, ")")
+            }
+            Type::Any => write!(f, "_"),
+        }
+    }
+}
+
+/// A Rust type that patinac values can be converted to.
+pub trait FromValue: Sized {
+    fn ty() -> Type;
+
+    /// Fails with a message if `value` isn't of [`FromValue::ty`].
+    fn from_value(vm: &Vm, value: Value) -> Result<Self, String>;
+}
+
+/// A Rust type that can be converted to a patinac value.
+pub trait IntoValue {
+    fn ty() -> Type;
+
+    fn into_value(self, vm: &mut Vm) -> Value;
+}
+
+fn mismatch<T: FromValue>(vm: &Vm, value: Value) -> String {
+    format!("expected {}, found {}", T::ty(), vm.type_name(value))
+}
+
+/// Passes values through untouched. Objects in a `Value` are only kept
+/// alive while the program can reach them, so one returned to the host
+/// shouldn't be held onto across calls.
+impl FromValue for Value {
+    fn ty() -> Type {
+        Type::Any
+    }
+
+    fn from_value(_: &Vm, value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn ty() -> Type {
+        Type::Any
+    }
+
+    fn into_value(self, _: &mut Vm) -> Value {
+        self
+    }
+}
+
+macro_rules! scalars {
+    ($($rust:ty => $variant:ident),* $(,)?) => {$(
+        impl FromValue for $rust {
+            fn ty() -> Type {
+                Type::$variant
+            }
+
+            fn from_value(vm: &Vm, value: Value) -> Result<Self, String> {
+                match value {
+                    Value::$variant(value) => Ok(value),
+                    _ => Err(mismatch::<Self>(vm, value)),
+                }
+            }
+        }
+
+        impl IntoValue for $rust {
+            fn ty() -> Type {
+                Type::$variant
+            }
+
+            fn into_value(self, _: &mut Vm) -> Value {
+                Value::$variant(self)
+            }
+        }
+    )*};
+}
+
+scalars! {
+    bool => Bool,
+    i64 => Int,
+    f64 => Float,
+    char => Char,
+}
+
+impl FromValue for () {
+    fn ty() -> Type {
+        Type::Unit
+    }
+
+    fn from_value(vm: &Vm, value: Value) -> Result<Self, String> {
+        match value {
+            Value::Unit => Ok(()),
+            _ => Err(mismatch::<Self>(vm, value)),
+        }
+    }
+}
+
+impl IntoValue for () {
+    fn ty() -> Type {
+        Type::Unit
+    }
+
+    fn into_value(self, _: &mut Vm) -> Value {
+        Value::Unit
+    }
+}
+
+impl FromValue for String {
+    fn ty() -> Type {
+        Type::Str
+    }
+
+    fn from_value(vm: &Vm, value: Value) -> Result<Self, String> {
+        vm.as_str(value)
+            .map(Into::into)
+            .ok_or_else(|| mismatch::<Self>(vm, value))
+    }
+}
+
+impl IntoValue for String {
+    fn ty() -> Type {
+        Type::Str
+    }
+
+    fn into_value(self, vm: &mut Vm) -> Value {
+        vm.alloc(Object::Str(self.into()))
+    }
+}
+
+impl IntoValue for &str {
+    fn ty() -> Type {
+        Type::Str
+    }
+
+    fn into_value(self, vm: &mut Vm) -> Value {
+        vm.alloc(Object::Str(self.into()))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn ty() -> Type {
+        Type::Array(Box::new(T::ty()))
+    }
+
+    fn from_value(vm: &Vm, value: Value) -> Result<Self, String> {
+        match value {
+            Value::Obj(obj) if let Object::Array(values) = vm.heap.get(obj) => values
+                .iter()
+                .map(|value| T::from_value(vm, *value))
+                .collect(),
+            _ => Err(mismatch::<Self>(vm, value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn ty() -> Type {
+        Type::Array(Box::new(T::ty()))
+    }
+
+    fn into_value(self, vm: &mut Vm) -> Value {
+        let start = vm.pinned.len();
+        for element in self {
+            let value = element.into_value(vm);
+            vm.pinned.push(value);
+        }
+        let values = vm.pinned.split_off(start);
+        vm.alloc(Object::Array(values))
+    }
+}
+
+macro_rules! tuples {
+    ($(($($element:ident $index:tt),+)),* $(,)?) => {$(
+        impl<$($element: FromValue),+> FromValue for ($($element,)+) {
+            fn ty() -> Type {
+                Type::Tuple(vec![$($element::ty()),+])
+            }
+
+            fn from_value(vm: &Vm, value: Value) -> Result<Self, String> {
+                match value {
+                    Value::Obj(obj) if let Object::Tuple(values) = vm.heap.get(obj)
+                        && values.len() == [$($index),+].len() =>
+                    {
+                        Ok(($($element::from_value(vm, values[$index])?,)+))
+                    }
+                    _ => Err(mismatch::<Self>(vm, value)),
+                }
+            }
+        }
+
+        impl<$($element: IntoValue),+> IntoValue for ($($element,)+) {
+            fn ty() -> Type {
+                Type::Tuple(vec![$($element::ty()),+])
+            }
+
+            fn into_value(self, vm: &mut Vm) -> Value {
+                let start = vm.pinned.len();
+                $(
+                    let value = self.$index.into_value(vm);
+                    vm.pinned.push(value);
+                )+
+                let values = vm.pinned.split_off(start);
+                vm.alloc(Object::Tuple(values))
+            }
+        }
+
+        impl<$($element: IntoValue),+> IntoArgs for ($($element,)+) {
+            fn into_args(self, vm: &mut Vm) -> Vec<Value> {
+                // Each argument stays pinned while the rest are converted.
+                let start = vm.pinned.len();
+                $(
+                    let value = self.$index.into_value(vm);
+                    vm.pinned.push(value);
+                )+
+                vm.pinned.split_off(start)
+            }
+        }
+    )*};
+}
+
+/// The arguments to a patinac function called from Rust: a tuple of
+/// [`IntoValue`]s, or `()` for none.
+pub trait IntoArgs {
+    fn into_args(self, vm: &mut Vm) -> Vec<Value>;
+}
+
+impl IntoArgs for () {
+    fn into_args(self, _: &mut Vm) -> Vec<Value> {
+        Vec::new()
+    }
+}
+
+tuples! {
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+}
+
+/// What a native returns: a value, or a `Result` whose error stops the
+/// program with a runtime error.
+pub trait NativeResult {
+    fn ty() -> Type;
+
+    fn into_result(self, vm: &mut Vm) -> Result<Value, String>;
+}
+
+impl<T: IntoValue> NativeResult for T {
+    fn ty() -> Type {
+        T::ty()
+    }
+
+    fn into_result(self, vm: &mut Vm) -> Result<Value, String> {
+        Ok(self.into_value(vm))
+    }
+}
+
+impl<T: IntoValue, E: Display> NativeResult for Result<T, E> {
+    fn ty() -> Type {
+        T::ty()
+    }
+
+    fn into_result(self, vm: &mut Vm) -> Result<Value, String> {
+        self.map(|value| value.into_value(vm))
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// A Rust closure that can be registered as a native function, taking up
+/// to four [`FromValue`] arguments and returning a [`NativeResult`].
+pub trait IntoNative<'a, Args> {
+    fn signature() -> Signature;
+
+    fn into_native(self) -> NativeFn<'a>;
+}
+
+macro_rules! natives {
+    ($(($($param:ident $arg:ident),*)),* $(,)?) => {$(
+        impl<'a, F, R, $($param),*> IntoNative<'a, ($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R + 'a,
+            R: NativeResult,
+            $($param: FromValue,)*
+        {
+            fn signature() -> Signature {
+                Signature {
+                    params: vec![$($param::ty()),*],
+                    result: R::ty(),
+                }
+            }
+
+            fn into_native(self) -> NativeFn<'a> {
+                Rc::new(move |vm, args| {
+                    // The VM has already checked there are the right number.
+                    let [$($arg),*] = args else {
+                        unreachable!("natives are called with their arity")
+                    };
+                    $(let $arg = $param::from_value(vm, *$arg)?;)*
+                    self($($arg),*).into_result(vm)
+                })
+            }
+        }
+    )*};
+}
+
+natives! {
+    (),
+    (A a),
+    (A a, B b),
+    (A a, B b, C c),
+    (A a, B b, C c, D d),
+}
@@ -0,0 +1,236 @@
+use crate::value::Value;
+
+/// A handle to an object on the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Str(Box<str>),
+    Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// A struct or enum variant, laid out as `shapes[shape]` says.
+    Record {
+        shape: u32,
+        fields: Vec<Value>,
+    },
+    Closure(Closure),
+    Upvalue(Upvalue),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub function: u32,
+    /// Each one points at an `Object::Upvalue`.
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A variable captured by a closure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Upvalue {
+    /// The variable is still on the stack, in this slot.
+    Open(usize),
+    /// The variable's scope ended, so the upvalue holds it from now on.
+    Closed(Value),
+}
+
+/// When the heap collects garbage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GcConfig {
+    /// How many bytes can be allocated before the first collection, and the
+    /// least the heap grows to before any later one.
+    pub initial_threshold: usize,
+    /// How much bigger than what survived the last collection the heap can
+    /// grow before the next one.
+    pub growth_factor: f64,
+    /// Collect before every allocation, so that anything the VM forgets to
+    /// keep reachable is freed straight away.
+    pub stress: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            initial_threshold: 1024 * 1024,
+            growth_factor: 2.0,
+            stress: false,
+        }
+    }
+}
+
+/// How much the heap holds, and how often it's been collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    /// The objects that haven't been freed yet.
+    pub objects: usize,
+    /// Roughly how much memory those objects take up.
+    pub bytes: usize,
+}
+
+/// Every object the VM has allocated. Slots are reused once freed.
+///
+/// Objects are freed by a mark and sweep collector. The VM marks its roots,
+/// then `sweep` marks everything they lead to and frees the rest.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    marked: Vec<bool>,
+    free: Vec<u32>,
+    /// Marked objects whose children haven't been marked yet.
+    gray: Vec<ObjRef>,
+    config: GcConfig,
+    /// The estimated size of every object that hasn't been freed.
+    bytes: usize,
+    /// `bytes` at which to collect next.
+    threshold: usize,
+    collections: usize,
+}
+
+impl Heap {
+    pub fn new(config: GcConfig) -> Self {
+        Self {
+            objects: Vec::new(),
+            marked: Vec::new(),
+            free: Vec::new(),
+            gray: Vec::new(),
+            config,
+            bytes: 0,
+            threshold: config.initial_threshold,
+            collections: 0,
+        }
+    }
+
+    /// Changes when collections happen, starting with the next allocation.
+    pub fn configure(&mut self, config: GcConfig) {
+        self.config = config;
+        self.threshold = config.initial_threshold;
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        self.bytes += size(&object);
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(object);
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marked.push(false);
+                ObjRef(self.objects.len() as u32 - 1)
+            }
+        }
+    }
+
+    pub fn get(&self, obj: ObjRef) -> &Object {
+        self.objects[obj.0 as usize]
+            .as_ref()
+            .expect("live objects are never freed")
+    }
+
+    pub fn get_mut(&mut self, obj: ObjRef) -> &mut Object {
+        self.objects[obj.0 as usize]
+            .as_mut()
+            .expect("live objects are never freed")
+    }
+
+    /// Whether it's time to collect, before allocating anything else.
+    pub fn should_collect(&self) -> bool {
+        self.config.stress || self.bytes >= self.threshold
+    }
+
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            collections: self.collections,
+            objects: self.objects.len() - self.free.len(),
+            bytes: self.bytes,
+        }
+    }
+
+    /// Keeps `value` alive through the next sweep.
+    pub fn mark(&mut self, value: Value) {
+        if let Value::Obj(obj) = value {
+            self.mark_object(obj);
+        }
+    }
+
+    pub fn mark_object(&mut self, obj: ObjRef) {
+        mark(&mut self.marked, &mut self.gray, obj);
+    }
+
+    /// Marks everything `object` refers to, for objects that are reachable
+    /// but not on the heap yet.
+    pub fn mark_children(&mut self, object: &Object) {
+        children(object, |child| {
+            mark(&mut self.marked, &mut self.gray, child)
+        });
+    }
+
+    /// Marks everything reachable from the objects marked so far, then frees
+    /// every object that wasn't marked.
+    pub fn sweep(&mut self) {
+        while let Some(obj) = self.gray.pop() {
+            let object = self.objects[obj.0 as usize]
+                .as_ref()
+                .expect("only live objects are marked");
+            children(object, |child| {
+                mark(&mut self.marked, &mut self.gray, child)
+            });
+        }
+
+        for (index, (object, marked)) in self.objects.iter_mut().zip(&mut self.marked).enumerate() {
+            if *marked {
+                *marked = false;
+            } else if let Some(object) = object.take() {
+                self.bytes -= size(&object);
+                self.free.push(index as u32);
+            }
+        }
+
+        self.collections += 1;
+        self.threshold = ((self.bytes as f64 * self.config.growth_factor) as usize)
+            .max(self.config.initial_threshold);
+    }
+}
+
+fn mark(marked: &mut [bool], gray: &mut Vec<ObjRef>, obj: ObjRef) {
+    let marked = &mut marked[obj.0 as usize];
+    if !*marked {
+        *marked = true;
+        gray.push(obj);
+    }
+}
+
+/// Calls `f` with every object `object` refers to directly.
+fn children(object: &Object, mut f: impl FnMut(ObjRef)) {
+    let values = match object {
+        Object::Str(_) => &[][..],
+        Object::Array(values) | Object::Tuple(values) | Object::Record { fields: values, .. } => {
+            values
+        }
+        Object::Closure(closure) => {
+            closure.upvalues.iter().copied().for_each(f);
+            return;
+        }
+        Object::Upvalue(Upvalue::Open(_)) => &[],
+        Object::Upvalue(Upvalue::Closed(value)) => std::slice::from_ref(value),
+    };
+    for value in values {
+        if let Value::Obj(obj) = value {
+            f(*obj);
+        }
+    }
+}
+
+/// Roughly how much memory `object` takes up, counting what it owns.
+fn size(object: &Object) -> usize {
+    let owned = match object {
+        Object::Str(string) => string.len(),
+        Object::Array(values) | Object::Tuple(values) | Object::Record { fields: values, .. } => {
+            values.capacity() * size_of::<Value>()
+        }
+        Object::Closure(closure) => closure.upvalues.capacity() * size_of::<ObjRef>(),
+        Object::Upvalue(_) => 0,
+    };
+    size_of::<Object>() + owned
+}
@@ -0,0 +1,10 @@
+pub mod bytecode;
+pub mod convert;
+mod heap;
+mod natives;
+mod value;
+mod vm;
+
+pub use heap::{GcConfig, GcStats};
+pub use value::Value;
+pub use vm::{Builder, NativeFn, RuntimeError, TraceFrame, Vm};
@@ -1,3 +1,42 @@
-fn main() -> anyhow::Result<()> {
-    Ok(())
+use std::{env, fs, io, process::ExitCode};
+
+use anyhow::{Context, bail};
+use runtime::{GcConfig, Value, Vm, bytecode::Module};
+
+const USAGE: &str = "usage: runtime [--gc-threshold <bytes>] [--gc-stress] <module>";
+
+fn main() -> anyhow::Result<ExitCode> {
+    let mut gc = GcConfig::default();
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gc-stress" => gc.stress = true,
+            "--gc-threshold" => {
+                let bytes = args.next().context(USAGE)?;
+                gc.initial_threshold = bytes
+                    .parse()
+                    .with_context(|| format!("invalid threshold `{bytes}`"))?;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => bail!(USAGE),
+        }
+    }
+    let Some(path) = path else {
+        bail!(USAGE);
+    };
+    let bytes = fs::read(&path).with_context(|| format!("failed to read `{path}`"))?;
+    let module = Module::decode(&bytes).with_context(|| format!("cannot load `{path}`"))?;
+
+    let stdout = io::stdout().lock();
+    let result = Vm::new(module, stdout).and_then(|vm| vm.with_gc(gc).run());
+    match result {
+        // Exit codes only go up to 255, so wrap like a process would.
+        Ok(Value::Int(code)) => Ok(ExitCode::from(code as u8)),
+        Ok(_) => Ok(ExitCode::SUCCESS),
+        Err(err) => {
+            eprintln!("error: {err}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
 }
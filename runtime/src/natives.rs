@@ -0,0 +1,44 @@
+use crate::{heap::Object, value::Value, vm::Vm};
+
+pub(crate) type BuiltinFn = fn(&mut Vm, &[Value]) -> Result<Value, String>;
+
+/// The functions every VM provides, matching the interpreter's builtins.
+pub(crate) static BUILTINS: [(&str, u8, BuiltinFn); 4] = [
+    ("print", 1, |vm, args| {
+        let text = vm.display(args[0]);
+        vm.write(&text)
+    }),
+    ("println", 1, |vm, args| {
+        let text = vm.display(args[0]) + "\n";
+        vm.write(&text)
+    }),
+    ("len", 1, |vm, args| {
+        let len = match args[0] {
+            Value::Obj(obj) => match vm.heap.get(obj) {
+                Object::Array(values) => Some(values.len()),
+                Object::Str(string) => Some(string.chars().count()),
+                _ => None,
+            },
+            _ => None,
+        };
+        len.map(|len| Value::Int(len as i64)).ok_or_else(|| {
+            format!(
+                "`len` expects an array or a string, found {}",
+                vm.type_name(args[0])
+            )
+        })
+    }),
+    ("to_string", 1, |vm, args| {
+        let text = vm.display(args[0]);
+        Ok(vm.alloc(Object::Str(text.into())))
+    }),
+];
+
+impl Vm<'_> {
+    fn write(&mut self, text: &str) -> Result<Value, String> {
+        self.out
+            .write_all(text.as_bytes())
+            .map_err(|err| format!("failed to write output: {err}"))?;
+        Ok(Value::Unit)
+    }
+}
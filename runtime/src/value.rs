@@ -0,0 +1,188 @@
+use std::fmt::Write;
+
+use crate::{
+    bytecode::Fields,
+    heap::{ObjRef, Object},
+    vm::Vm,
+};
+
+/// A value on the VM's stack. Anything bigger than a word lives on the heap.
+///
+/// Objects compare by identity here; `Vm::equals` compares them structurally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Char(char),
+    Obj(ObjRef),
+    /// A native function, by its index in the VM's table of them.
+    Native(u32),
+}
+
+impl Vm<'_> {
+    /// The name of the value's type, for error messages.
+    pub fn type_name(&self, value: Value) -> String {
+        match value {
+            Value::Unit => "()".into(),
+            Value::Bool(_) => "Bool".into(),
+            Value::Int(_) => "Int".into(),
+            Value::Float(_) => "Float".into(),
+            Value::Char(_) => "Char".into(),
+            Value::Native(_) => "function".into(),
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Object::Str(_) => "Str".into(),
+                Object::Array(_) => "array".into(),
+                Object::Tuple(_) => "tuple".into(),
+                Object::Record { shape, .. } => {
+                    let name = &self.module.shapes[*shape as usize].name;
+                    // Variants are named after their enum.
+                    name.split("::").next().unwrap_or(name).into()
+                }
+                Object::Closure(_) => "function".into(),
+                Object::Upvalue(_) => unreachable!("upvalues are never on the stack"),
+            },
+        }
+    }
+
+    /// The value as `print` shows it: strings and chars as their contents,
+    /// and everything else the way it would be written in source.
+    pub fn display(&self, value: Value) -> String {
+        if let Value::Char(char) = value {
+            return char.into();
+        }
+        if let Some(string) = self.as_str(value) {
+            return string.into();
+        }
+
+        let mut out = String::new();
+        self.write_value(&mut out, value);
+        out
+    }
+
+    /// The contents of `value`, if it's a string.
+    pub fn as_str(&self, value: Value) -> Option<&str> {
+        match value {
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Object::Str(string) => Some(string),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn write_value(&self, out: &mut String, value: Value) {
+        let list = |out: &mut String, values: &[Value]| {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                self.write_value(out, *value);
+            }
+        };
+
+        // Writing to a `String` can't fail.
+        let _ = match value {
+            Value::Unit => write!(out, "()"),
+            Value::Bool(bool) => write!(out, "{bool}"),
+            Value::Int(int) => write!(out, "{int}"),
+            Value::Float(float) => write!(out, "{float:?}"),
+            Value::Char(char) => write!(out, "{char:?}"),
+            Value::Native(native) => write!(out, "<fn {}>", self.natives[native as usize].name),
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Object::Str(string) => write!(out, "{string:?}"),
+                Object::Array(values) => {
+                    out.push('[');
+                    list(out, values);
+                    write!(out, "]")
+                }
+                Object::Tuple(values) => {
+                    out.push('(');
+                    list(out, values);
+                    if values.len() == 1 {
+                        out.push(',');
+                    }
+                    write!(out, ")")
+                }
+                Object::Record { shape, fields } => {
+                    let shape = &self.module.shapes[*shape as usize];
+                    out.push_str(&shape.name);
+                    match &shape.fields {
+                        Fields::Unit => Ok(()),
+                        Fields::Tuple(_) => {
+                            out.push('(');
+                            list(out, fields);
+                            write!(out, ")")
+                        }
+                        Fields::Named(names) => {
+                            out.push_str(" { ");
+                            for (i, (name, value)) in names.iter().zip(fields).enumerate() {
+                                if i > 0 {
+                                    out.push_str(", ");
+                                }
+                                out.push_str(name);
+                                out.push_str(": ");
+                                self.write_value(out, *value);
+                            }
+                            write!(out, " }}")
+                        }
+                    }
+                }
+                Object::Closure(closure) => {
+                    let name = &self.module.functions[closure.function as usize].name;
+                    // Lambdas are already named `<closure>`.
+                    if name.starts_with('<') {
+                        write!(out, "{name}")
+                    } else {
+                        write!(out, "<fn {name}>")
+                    }
+                }
+                Object::Upvalue(_) => unreachable!("upvalues are never on the stack"),
+            },
+        };
+    }
+
+    /// Structural equality. Returns `None` if a function is compared, since
+    /// those have no meaningful notion of equality.
+    pub fn equals(&self, lhs: Value, rhs: Value) -> Option<bool> {
+        let all = |lhs: &[Value], rhs: &[Value]| {
+            if lhs.len() != rhs.len() {
+                return Some(false);
+            }
+            for (lhs, rhs) in lhs.iter().zip(rhs) {
+                if !self.equals(*lhs, *rhs)? {
+                    return Some(false);
+                }
+            }
+            Some(true)
+        };
+
+        Some(match (lhs, rhs) {
+            (Value::Obj(lhs), Value::Obj(rhs)) => match (self.heap.get(lhs), self.heap.get(rhs)) {
+                (Object::Str(lhs), Object::Str(rhs)) => lhs == rhs,
+                (Object::Array(lhs), Object::Array(rhs))
+                | (Object::Tuple(lhs), Object::Tuple(rhs)) => all(lhs, rhs)?,
+                (
+                    Object::Record {
+                        shape: lhs_shape,
+                        fields: lhs,
+                    },
+                    Object::Record {
+                        shape: rhs_shape,
+                        fields: rhs,
+                    },
+                ) => lhs_shape == rhs_shape && all(lhs, rhs)?,
+                (Object::Closure(_), _) | (_, Object::Closure(_)) => return None,
+                _ => false,
+            },
+            (Value::Native(_), _) | (_, Value::Native(_)) => return None,
+            (Value::Obj(obj), _) | (_, Value::Obj(obj))
+                if matches!(self.heap.get(obj), Object::Closure(_)) =>
+            {
+                return None;
+            }
+            (lhs, rhs) => lhs == rhs,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    io::{self, Write},
+    rc::Rc,
+};
+
+use super::{Native, RuntimeError, Vm, validate};
+use crate::{
+    bytecode::Module,
+    convert::{IntoNative, Signature},
+    heap::{GcConfig, Heap},
+    natives::BUILTINS,
+    value::Value,
+};
+
+/// Sets up a [`Vm`] for a host that wants more than [`Vm::new`] gives it:
+/// natives of its own, somewhere else for output to go, or different
+/// garbage collection settings.
+///
+/// ```
+/// # use runtime::{Vm, bytecode::Module};
+/// # fn load(module: Module) -> Result<(), runtime::RuntimeError> {
+/// let mut vm = Vm::builder()
+///     .native("clamp", |n: i64, max: i64| n.min(max))
+///     .load(module)?;
+/// let total: i64 = vm.call("total", (vec![1, 2, 3],))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Builder<'a> {
+    out: Box<dyn Write + 'a>,
+    gc: GcConfig,
+    natives: Vec<Native<'a>>,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new() -> Self {
+        let natives = BUILTINS
+            .iter()
+            .map(|&(name, arity, fun)| Native {
+                name: name.into(),
+                arity,
+                fun: Rc::new(fun),
+                signature: None,
+            })
+            .collect();
+        Builder {
+            out: Box::new(io::stdout()),
+            gc: GcConfig::default(),
+            natives,
+        }
+    }
+
+    /// Where `print` and `println` write to, instead of stdout.
+    pub fn output(mut self, out: impl Write + 'a) -> Self {
+        self.out = Box::new(out);
+        self
+    }
+
+    pub fn gc(mut self, config: GcConfig) -> Self {
+        self.gc = config;
+        self
+    }
+
+    /// Makes `fun` callable from patinac as `name`, replacing any native of
+    /// the same name, builtins included. Its arguments are converted from
+    /// patinac values and its result back, failing the call if they're of
+    /// the wrong type.
+    pub fn native<Args, F: IntoNative<'a, Args>>(mut self, name: &str, fun: F) -> Self {
+        let signature = F::signature();
+        let native = Native {
+            name: name.into(),
+            arity: signature.params.len() as u8,
+            fun: fun.into_native(),
+            signature: Some(signature),
+        };
+        match self.natives.iter_mut().find(|native| native.name == name) {
+            Some(existing) => *existing = native,
+            None => self.natives.push(native),
+        }
+        self
+    }
+
+    /// The name and signature of each native added with
+    /// [`Builder::native`], which a compiler needs to check calls to them.
+    pub fn signatures(&self) -> impl Iterator<Item = (&str, &Signature)> {
+        self.natives.iter().filter_map(|native| {
+            native
+                .signature
+                .as_ref()
+                .map(|signature| (native.name.as_str(), signature))
+        })
+    }
+
+    /// Loads `module`, failing if it uses a native function the VM doesn't
+    /// have or refers to something that isn't there.
+    pub fn load(self, module: Module) -> Result<Vm<'a>, RuntimeError> {
+        let module_natives = module
+            .natives
+            .iter()
+            .map(|name| {
+                self.natives
+                    .iter()
+                    .position(|native| native.name == *name)
+                    .map(|i| i as u32)
+                    .ok_or_else(|| format!("unknown native function `{name}`"))
+            })
+            .collect::<Result<_, _>>()?;
+        validate(&module)?;
+
+        Ok(Vm {
+            globals: vec![Value::Unit; module.globals.len()],
+            initialized: false,
+            module,
+            heap: Heap::new(self.gc),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            natives: self.natives,
+            module_natives,
+            open_upvalues: Vec::new(),
+            pinned: Vec::new(),
+            out: self.out,
+        })
+    }
+}
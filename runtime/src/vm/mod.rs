@@ -0,0 +1,770 @@
+mod builder;
+mod ops;
+#[cfg(test)]
+mod test;
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io::{self, Write},
+    rc::Rc,
+};
+
+pub use builder::Builder;
+
+use crate::{
+    bytecode::{Capture, Constant, Function, Module, Op},
+    convert::{FromValue, IntoArgs, Signature},
+    heap::{Closure, GcConfig, GcStats, Heap, ObjRef, Object, Upvalue},
+    value::Value,
+};
+
+/// How deeply calls can nest before the program is assumed to be stuck
+/// recursing forever. Matches the interpreter's limit.
+const MAX_FRAMES: usize = 10_000;
+
+pub type NativeFn<'a> = Rc<dyn Fn(&mut Vm<'a>, &[Value]) -> Result<Value, String> + 'a>;
+
+pub(crate) struct Native<'a> {
+    pub name: String,
+    pub arity: u8,
+    pub fun: NativeFn<'a>,
+    /// `None` for builtins, which the compiler already knows the types of.
+    pub signature: Option<Signature>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The calls that were running, innermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+/// A function that was running when an error happened, and where it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    /// `None` if the module has no line info for the function.
+    pub line: Option<u32>,
+}
+
+/// Runs of identical frames, like those left by deep recursion, are shown
+/// once along with how many times they repeat.
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for run in self.trace.chunk_by(|a, b| a == b) {
+            let frame = &run[0];
+            write!(f, "\n    in `{}`", frame.function)?;
+            if let Some(line) = frame.line {
+                write!(f, " at line {line}")?;
+            }
+            if run.len() > 1 {
+                let more = run.len() - 1;
+                write!(
+                    f,
+                    "\n    ... repeated {more} more time{}",
+                    if more == 1 { "" } else { "s" }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {}
+
+type RunResult<T> = Result<T, String>;
+
+#[derive(Debug)]
+struct Frame {
+    closure: ObjRef,
+    function: u32,
+    ip: usize,
+    /// Where the frame's locals start on the stack.
+    base: usize,
+}
+
+pub struct Vm<'a> {
+    pub(crate) module: Module,
+    pub(crate) heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: Vec<Value>,
+    /// Whether the module's `init` function has run.
+    initialized: bool,
+    pub(crate) natives: Vec<Native<'a>>,
+    /// The index in `natives` of each of the module's natives.
+    module_natives: Vec<u32>,
+    /// The upvalues still pointing into the stack, ordered by slot.
+    open_upvalues: Vec<ObjRef>,
+    /// Values the host is converting, kept alive until they're on the stack
+    /// or inside another object.
+    pub(crate) pinned: Vec<Value>,
+    pub(crate) out: Box<dyn Write + 'a>,
+}
+
+impl<'a> Vm<'a> {
+    /// Loads `module`, failing if it uses a native function the VM doesn't
+    /// have or refers to something that isn't there. Use [`Vm::builder`]
+    /// to give it natives of its own.
+    pub fn new(module: Module, out: impl Write + 'a) -> Result<Self, RuntimeError> {
+        Vm::builder().output(out).load(module)
+    }
+
+    pub fn builder() -> Builder<'a> {
+        Builder::new()
+    }
+
+    /// Sets when the VM collects garbage, instead of the defaults.
+    pub fn with_gc(mut self, config: GcConfig) -> Self {
+        self.heap.configure(config);
+        self
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// Frees every object the program can no longer reach.
+    pub fn collect_garbage(&mut self) {
+        self.mark_roots();
+        self.heap.sweep();
+    }
+
+    /// Runs the module's entry point, returning its result.
+    pub fn run(&mut self) -> Result<Value, RuntimeError> {
+        // The entry point initializes the globals itself.
+        self.initialized = true;
+        let entry = self.alloc(Object::Closure(Closure {
+            function: self.module.entry,
+            upvalues: Vec::new(),
+        }));
+        self.call_value(entry, &[])
+    }
+
+    /// Calls the global function `name`, converting `args` to patinac
+    /// values and the result back. The globals are initialized first if
+    /// nothing has run yet.
+    pub fn call<R: FromValue>(
+        &mut self,
+        name: &str,
+        args: impl IntoArgs,
+    ) -> Result<R, RuntimeError> {
+        self.initialize()?;
+        let global = self
+            .module
+            .globals
+            .iter()
+            .position(|global| global == name)
+            .ok_or_else(|| RuntimeError::from(format!("no global named `{name}`")))?;
+
+        let args = args.into_args(self);
+        let result = self.call_value(self.globals[global], &args)?;
+        R::from_value(self, result)
+            .map_err(|message| format!("`{name}` returned the wrong type: {message}").into())
+    }
+
+    /// Runs the module's `init` function, unless it's already run.
+    fn initialize(&mut self) -> Result<(), RuntimeError> {
+        if self.initialized {
+            return Ok(());
+        }
+        self.initialized = true;
+        let init = self.alloc(Object::Closure(Closure {
+            function: self.module.init,
+            upvalues: Vec::new(),
+        }));
+        self.call_value(init, &[])?;
+        Ok(())
+    }
+
+    /// Calls `callee` with `args`, running until it returns.
+    pub fn call_value(&mut self, callee: Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        let depth = self.frames.len();
+        let height = self.stack.len();
+
+        self.stack.push(callee);
+        self.stack.extend_from_slice(args);
+        let result = self
+            .invoke(args.len() as u8)
+            .and_then(|()| self.execute(depth));
+
+        match result {
+            Ok(()) => Ok(self.pop()),
+            Err(message) => {
+                let trace = self.frames[depth..]
+                    .iter()
+                    .rev()
+                    .map(|frame| {
+                        let function = &self.module.functions[frame.function as usize];
+                        TraceFrame {
+                            function: function.name.clone(),
+                            // The frame has already moved past the instruction.
+                            line: function
+                                .lines
+                                .get(frame.ip - 1)
+                                .copied()
+                                .filter(|&line| line != 0),
+                        }
+                    })
+                    .collect();
+                self.frames.truncate(depth);
+                self.close_upvalues(height);
+                self.stack.truncate(height);
+                Err(RuntimeError { message, trace })
+            }
+        }
+    }
+
+    pub(crate) fn alloc(&mut self, object: Object) -> Value {
+        Value::Obj(self.alloc_object(object))
+    }
+
+    /// Allocates `object`, collecting garbage first if it's time to. Whatever
+    /// `object` refers to is kept, even if it's been popped off the stack.
+    fn alloc_object(&mut self, object: Object) -> ObjRef {
+        if self.heap.should_collect() {
+            self.mark_roots();
+            self.heap.mark_children(&object);
+            self.heap.sweep();
+        }
+        self.heap.alloc(object)
+    }
+
+    /// Marks everything the program can reach directly: the stack, globals,
+    /// the running closures and the upvalues still pointing into the stack.
+    fn mark_roots(&mut self) {
+        for value in self.stack.iter().chain(&self.globals).chain(&self.pinned) {
+            self.heap.mark(*value);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        for upvalue in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    /// Runs instructions until the frames above `depth` have all returned.
+    fn execute(&mut self, depth: usize) -> RunResult<()> {
+        while self.frames.len() > depth {
+            let frame = self.frames.last_mut().expect("a frame is running");
+            let code = &self.module.functions[frame.function as usize].code;
+            let op = *code
+                .get(frame.ip)
+                .ok_or("invalid module: function ended without returning")?;
+            frame.ip += 1;
+            let base = frame.base;
+
+            match op {
+                Op::Const(n) => {
+                    let value = match &self.module.constants[n as usize] {
+                        Constant::Int(int) => Value::Int(*int),
+                        Constant::Float(float) => Value::Float(*float),
+                        Constant::Char(char) => Value::Char(*char),
+                        Constant::Str(string) => {
+                            let string = string.as_str().into();
+                            self.alloc(Object::Str(string))
+                        }
+                    };
+                    self.push(value);
+                }
+                Op::Unit => self.push(Value::Unit),
+                Op::True => self.push(Value::Bool(true)),
+                Op::False => self.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Dup => {
+                    let value = *self.stack.last().expect("stack underflow");
+                    self.push(value);
+                }
+
+                Op::GetLocal(slot) => {
+                    let value = *self.local(base, slot)?;
+                    self.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.pop();
+                    *self.local(base, slot)? = value;
+                }
+                Op::GetUpvalue(n) => {
+                    let value = match *self.upvalue(n) {
+                        Upvalue::Open(slot) => self.stack[slot],
+                        Upvalue::Closed(value) => value,
+                    };
+                    self.push(value);
+                }
+                Op::SetUpvalue(n) => {
+                    let value = self.pop();
+                    match self.upvalue(n) {
+                        Upvalue::Open(slot) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                Op::GetGlobal(n) => self.push(self.globals[n as usize]),
+                Op::SetGlobal(n) => self.globals[n as usize] = self.pop(),
+                Op::Native(n) => self.push(Value::Native(self.module_natives[n as usize])),
+                Op::Closure(function) => {
+                    let captures = self.module.functions[function as usize].captures.clone();
+                    let upvalues = captures
+                        .into_iter()
+                        .map(|capture| match capture {
+                            Capture::Local(slot) => self.capture(base + slot as usize),
+                            Capture::Upvalue(n) => self.closure().upvalues[n as usize],
+                        })
+                        .collect();
+                    let closure = self.alloc(Object::Closure(Closure { function, upvalues }));
+                    self.push(closure);
+                }
+                Op::EndScope(n) => {
+                    let value = self.pop();
+                    let end = self.stack.len() - n as usize;
+                    self.close_upvalues(end);
+                    self.stack.truncate(end);
+                    self.push(value);
+                }
+
+                Op::Call(argc) => self.invoke(argc)?,
+                Op::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("a frame is running");
+                    self.close_upvalues(frame.base);
+                    // Pop the locals and the callee.
+                    self.stack.truncate(frame.base - 1);
+                    self.push(value);
+                }
+                Op::Jump(target) => self.jump(target),
+                Op::JumpIfFalse(target) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => self.jump(target),
+                    value => {
+                        return Err(format!("expected Bool, found {}", self.type_name(value)));
+                    }
+                },
+
+                Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::Exp
+                | Op::BitAnd
+                | Op::BitOr
+                | Op::BitXor
+                | Op::Eq
+                | Op::Neq
+                | Op::Lt
+                | Op::Gt
+                | Op::Leq
+                | Op::Geq => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let value = self.binary_op(op, lhs, rhs)?;
+                    self.push(value);
+                }
+                Op::Neg | Op::Not => {
+                    let value = self.pop();
+                    let value = self.unary_op(op, value)?;
+                    self.push(value);
+                }
+
+                Op::Array(len) => {
+                    let values = self.pop_n(len as usize);
+                    let array = self.alloc(Object::Array(values));
+                    self.push(array);
+                }
+                Op::Tuple(len) => {
+                    let values = self.pop_n(len as usize);
+                    let tuple = self.alloc(Object::Tuple(values));
+                    self.push(tuple);
+                }
+                Op::Record(shape) => {
+                    let len = self.module.shapes[shape as usize].fields.len();
+                    let fields = self.pop_n(len);
+                    let record = self.alloc(Object::Record { shape, fields });
+                    self.push(record);
+                }
+                Op::Index => {
+                    let index = self.pop();
+                    let array = self.pop();
+                    let value = self.index(array, index)?;
+                    self.push(value);
+                }
+                Op::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let array = self.pop();
+                    let array = self.set_index(array, index, value)?;
+                    self.push(array);
+                }
+                Op::GetField(name) => {
+                    let base = self.pop();
+                    let value = self.field(base, name, None)?;
+                    self.push(value);
+                }
+                Op::SetField(name) => {
+                    let value = self.pop();
+                    let base = self.pop();
+                    let base = self.field(base, name, Some(value))?;
+                    self.push(base);
+                }
+                Op::Element(n) => {
+                    let value = self.pop();
+                    let element = self.element(value, n)?;
+                    self.push(element);
+                }
+                Op::Len => {
+                    let value = self.pop();
+                    let len = self.len(value)?;
+                    self.push(Value::Int(len as i64));
+                }
+                Op::NoMatch => {
+                    let value = self.pop();
+                    return Err(format!("no match arm matches `{}`", self.display(value)));
+                }
+                Op::IsShape(shape) => {
+                    let value = self.pop();
+                    let is_shape = matches!(
+                        value,
+                        Value::Obj(obj) if matches!(
+                            self.heap.get(obj),
+                            Object::Record { shape: found, .. } if *found == shape
+                        )
+                    );
+                    self.push(Value::Bool(is_shape));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls the value under the top `argc` values on the stack. Natives run
+    /// straight away, anything else gets a new frame.
+    fn invoke(&mut self, argc: u8) -> RunResult<()> {
+        let callee_slot = self.stack.len() - argc as usize - 1;
+        let callee = self.stack[callee_slot];
+
+        let arity = match callee {
+            Value::Native(native) => self.natives[native as usize].arity,
+            Value::Obj(obj) if let Object::Closure(closure) = self.heap.get(obj) => {
+                self.module.functions[closure.function as usize].arity
+            }
+            value => return Err(format!("{} is not a function", self.type_name(value))),
+        };
+        if arity != argc {
+            return Err(format!(
+                "`{}` takes {arity} argument{}, but {argc} {} supplied",
+                self.display(callee),
+                if arity == 1 { "" } else { "s" },
+                if argc == 1 { "was" } else { "were" },
+            ));
+        }
+
+        match callee {
+            Value::Native(native) => {
+                // The arguments stay on the stack until the native returns,
+                // so that they survive any collections it causes.
+                let args = self.stack[callee_slot + 1..].to_vec();
+                let fun = Rc::clone(&self.natives[native as usize].fun);
+                let result = fun(self, &args)?;
+                self.stack.truncate(callee_slot);
+                self.push(result);
+            }
+            Value::Obj(closure) => {
+                if self.frames.len() == MAX_FRAMES {
+                    return Err(format!(
+                        "stack overflow: calls nested more than {MAX_FRAMES} deep"
+                    ));
+                }
+                let Object::Closure(Closure { function, .. }) = self.heap.get(closure) else {
+                    unreachable!("checked above")
+                };
+                self.frames.push(Frame {
+                    closure,
+                    function: *function,
+                    ip: 0,
+                    base: callee_slot + 1,
+                });
+            }
+            _ => unreachable!("checked above"),
+        }
+        Ok(())
+    }
+
+    fn local(&mut self, base: usize, slot: u16) -> RunResult<&mut Value> {
+        self.stack
+            .get_mut(base + slot as usize)
+            .ok_or_else(|| format!("invalid module: local {slot} doesn't exist"))
+    }
+
+    fn closure(&self) -> &Closure {
+        let frame = self.frames.last().expect("a frame is running");
+        let Object::Closure(closure) = self.heap.get(frame.closure) else {
+            unreachable!("frames always run closures")
+        };
+        closure
+    }
+
+    fn upvalue(&mut self, n: u16) -> &mut Upvalue {
+        let upvalue = self.closure().upvalues[n as usize];
+        let Object::Upvalue(upvalue) = self.heap.get_mut(upvalue) else {
+            unreachable!("closures only hold upvalues")
+        };
+        upvalue
+    }
+
+    /// The upvalue for the variable in `slot`, shared with any other closure
+    /// that captured it.
+    fn capture(&mut self, slot: usize) -> ObjRef {
+        let position = self.open_upvalues.partition_point(|upvalue| {
+            matches!(self.heap.get(*upvalue), Object::Upvalue(Upvalue::Open(open)) if *open < slot)
+        });
+        if let Some(&upvalue) = self.open_upvalues.get(position)
+            && matches!(self.heap.get(upvalue), Object::Upvalue(Upvalue::Open(open)) if *open == slot)
+        {
+            return upvalue;
+        }
+
+        let upvalue = self.alloc_object(Object::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, upvalue);
+        upvalue
+    }
+
+    /// Moves the variables from `slot` upwards off the stack and into the
+    /// upvalues that captured them.
+    fn close_upvalues(&mut self, slot: usize) {
+        while let Some(&upvalue) = self.open_upvalues.last() {
+            let Object::Upvalue(upvalue) = self.heap.get_mut(upvalue) else {
+                unreachable!("only upvalues are open")
+            };
+            let Upvalue::Open(open) = *upvalue else {
+                unreachable!("closed upvalues aren't kept")
+            };
+            if open < slot {
+                break;
+            }
+            *upvalue = Upvalue::Closed(self.stack[open]);
+            self.open_upvalues.pop();
+        }
+    }
+
+    fn jump(&mut self, target: u32) {
+        self.frames.last_mut().expect("a frame is running").ip = target as usize;
+    }
+
+    fn pop_n(&mut self, n: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - n)
+    }
+}
+
+/// Checks that every index in `module` points at something, and that every
+/// instruction finds the values, locals and upvalues it uses, so running it
+/// can't go out of bounds.
+fn validate(module: &Module) -> Result<(), String> {
+    let check = |what: &str, index: u32, len: usize| {
+        if (index as usize) < len {
+            Ok(())
+        } else {
+            Err(format!(
+                "invalid module: {what} {index} doesn't exist, there are only {len}"
+            ))
+        }
+    };
+
+    for (what, function) in [("entry", module.entry), ("init", module.init)] {
+        check(
+            &format!("{what} function"),
+            function,
+            module.functions.len(),
+        )?;
+        let function = &module.functions[function as usize];
+        if function.arity != 0 {
+            return Err(format!(
+                "invalid module: the {what} function can't take arguments"
+            ));
+        }
+        if !function.captures.is_empty() {
+            return Err(format!(
+                "invalid module: the {what} function can't capture variables"
+            ));
+        }
+    }
+    for function in &module.functions {
+        for op in &function.code {
+            match *op {
+                Op::Const(n) => check("constant", n, module.constants.len())?,
+                Op::GetGlobal(n) | Op::SetGlobal(n) => check("global", n, module.globals.len())?,
+                Op::Native(n) => check("native", n, module.natives.len())?,
+                Op::Closure(n) => check("function", n, module.functions.len())?,
+                Op::Record(n) | Op::IsShape(n) => check("shape", n, module.shapes.len())?,
+                Op::Jump(n) | Op::JumpIfFalse(n) => {
+                    check("instruction", n, function.code.len())?;
+                }
+                Op::GetField(n) | Op::SetField(n) => {
+                    check("constant", n, module.constants.len())?;
+                    if !matches!(module.constants[n as usize], Constant::Str(_)) {
+                        return Err(format!(
+                            "invalid module: field name {n} isn't a string constant"
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    for function in &module.functions {
+        check_stack(module, function)?;
+    }
+    Ok(())
+}
+
+/// Follows every path through `function`, tracking how many values are on
+/// its part of the stack. Paths that meet must agree on the height. Running
+/// off the end is left to the VM, which reports it when it happens.
+fn check_stack(module: &Module, function: &Function) -> Result<(), String> {
+    let name = &function.name;
+    let mut heights = vec![None; function.code.len()];
+    let mut pending = vec![(0, function.arity as usize)];
+    while let Some((ip, height)) = pending.pop() {
+        let Some(&op) = function.code.get(ip) else {
+            continue;
+        };
+        match heights[ip] {
+            Some(seen) if seen == height => continue,
+            Some(seen) => {
+                return Err(format!(
+                    "invalid module: instruction {ip} of `{name}` is reached with \
+                     {seen} and with {height} values on the stack"
+                ));
+            }
+            None => heights[ip] = Some(height),
+        }
+
+        let (pops, pushes) = match op {
+            Op::Const(_)
+            | Op::Unit
+            | Op::True
+            | Op::False
+            | Op::GetLocal(_)
+            | Op::GetUpvalue(_)
+            | Op::GetGlobal(_)
+            | Op::Native(_)
+            | Op::Closure(_) => (0, 1),
+            Op::Pop
+            | Op::SetLocal(_)
+            | Op::SetUpvalue(_)
+            | Op::SetGlobal(_)
+            | Op::Return
+            | Op::JumpIfFalse(_)
+            | Op::NoMatch => (1, 0),
+            Op::Dup => (1, 2),
+            Op::Jump(_) => (0, 0),
+            Op::EndScope(n) => (n as usize + 1, 1),
+            Op::Call(argc) => (argc as usize + 1, 1),
+            Op::Add
+            | Op::Sub
+            | Op::Mul
+            | Op::Div
+            | Op::Exp
+            | Op::BitAnd
+            | Op::BitOr
+            | Op::BitXor
+            | Op::Eq
+            | Op::Neq
+            | Op::Lt
+            | Op::Gt
+            | Op::Leq
+            | Op::Geq
+            | Op::Index
+            | Op::SetField(_) => (2, 1),
+            Op::Neg | Op::Not | Op::GetField(_) | Op::Element(_) | Op::Len | Op::IsShape(_) => {
+                (1, 1)
+            }
+            Op::Array(n) | Op::Tuple(n) => (n as usize, 1),
+            Op::Record(shape) => (module.shapes[shape as usize].fields.len(), 1),
+            Op::SetIndex => (3, 1),
+        };
+        if height < pops {
+            return Err(format!(
+                "invalid module: instruction {ip} of `{name}` pops {pops} value{}, \
+                 but there are only {height}",
+                if pops == 1 { "" } else { "s" },
+            ));
+        }
+
+        let local = |slot: u16, height: usize| {
+            if (slot as usize) < height {
+                Ok(())
+            } else {
+                Err(format!(
+                    "invalid module: local {slot} doesn't exist at instruction {ip} \
+                     of `{name}`, there are only {height}"
+                ))
+            }
+        };
+        let upvalue = |n: u16| {
+            if (n as usize) < function.captures.len() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "invalid module: upvalue {n} of `{name}` doesn't exist, there are only {}",
+                    function.captures.len()
+                ))
+            }
+        };
+        match op {
+            Op::GetLocal(slot) => local(slot, height)?,
+            Op::SetLocal(slot) => local(slot, height - 1)?,
+            Op::GetUpvalue(n) | Op::SetUpvalue(n) => upvalue(n)?,
+            Op::Closure(f) => {
+                for capture in &module.functions[f as usize].captures {
+                    match *capture {
+                        Capture::Local(slot) => local(slot, height)?,
+                        Capture::Upvalue(n) => upvalue(n)?,
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let height = height - pops + pushes;
+        match op {
+            Op::Return | Op::NoMatch => {}
+            Op::Jump(target) => pending.push((target as usize, height)),
+            Op::JumpIfFalse(target) => {
+                pending.push((target as usize, height));
+                pending.push((ip + 1, height));
+            }
+            _ => pending.push((ip + 1, height)),
+        }
+    }
+    Ok(())
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        err.to_string().into()
+    }
+}
+
+/// An error that didn't happen inside any call, so has no trace.
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError {
+            message,
+            trace: Vec::new(),
+        }
+    }
+}
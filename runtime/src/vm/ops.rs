@@ -0,0 +1,253 @@
+//! The instructions that work on values, rather than on the VM's own state.
+
+use std::cmp::Ordering;
+
+use super::{RunResult, Vm};
+use crate::{
+    bytecode::{Constant, Fields, Op},
+    heap::Object,
+    value::Value,
+};
+
+impl Vm<'_> {
+    pub(super) fn binary_op(&mut self, op: Op, lhs: Value, rhs: Value) -> RunResult<Value> {
+        let overflow = || "integer overflow".to_string();
+
+        Ok(match (op, lhs, rhs) {
+            (Op::Add, Value::Int(lhs), Value::Int(rhs)) => {
+                Value::Int(lhs.checked_add(rhs).ok_or_else(overflow)?)
+            }
+            (Op::Sub, Value::Int(lhs), Value::Int(rhs)) => {
+                Value::Int(lhs.checked_sub(rhs).ok_or_else(overflow)?)
+            }
+            (Op::Mul, Value::Int(lhs), Value::Int(rhs)) => {
+                Value::Int(lhs.checked_mul(rhs).ok_or_else(overflow)?)
+            }
+            (Op::Div, Value::Int(_), Value::Int(0)) => return Err("division by zero".into()),
+            (Op::Div, Value::Int(lhs), Value::Int(rhs)) => {
+                Value::Int(lhs.checked_div(rhs).ok_or_else(overflow)?)
+            }
+            (Op::Exp, Value::Int(lhs), Value::Int(rhs)) => {
+                let Ok(rhs) = u32::try_from(rhs) else {
+                    return Err(if rhs < 0 {
+                        "cannot raise an Int to a negative power".into()
+                    } else {
+                        overflow()
+                    });
+                };
+                Value::Int(lhs.checked_pow(rhs).ok_or_else(overflow)?)
+            }
+            (Op::Add, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs + rhs),
+            (Op::Sub, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs - rhs),
+            (Op::Mul, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs * rhs),
+            (Op::Div, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs / rhs),
+            (Op::Exp, Value::Float(lhs), Value::Float(rhs)) => Value::Float(lhs.powf(rhs)),
+            (Op::BitXor, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs ^ rhs),
+            (Op::BitOr, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs | rhs),
+            (Op::BitAnd, Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs & rhs),
+            (Op::BitXor, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs ^ rhs),
+            (Op::BitOr, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs | rhs),
+            (Op::BitAnd, Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs & rhs),
+            (Op::Eq | Op::Neq, lhs, rhs) => match self.equals(lhs, rhs) {
+                Some(equal) => Value::Bool(equal == (op == Op::Eq)),
+                None => return Err("functions can't be compared".into()),
+            },
+            (Op::Lt | Op::Gt | Op::Leq | Op::Geq, lhs, rhs) => {
+                let ordering = match (lhs, rhs) {
+                    (Value::Int(lhs), Value::Int(rhs)) => lhs.partial_cmp(&rhs),
+                    (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(&rhs),
+                    (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(&rhs),
+                    _ => match (self.as_str(lhs), self.as_str(rhs)) {
+                        (Some(lhs), Some(rhs)) => lhs.partial_cmp(rhs),
+                        _ => return Err(self.mismatched(op, lhs, rhs)),
+                    },
+                };
+                Value::Bool(matches!(
+                    (op, ordering),
+                    (Op::Lt, Some(Ordering::Less))
+                        | (Op::Gt, Some(Ordering::Greater))
+                        | (Op::Leq, Some(Ordering::Less | Ordering::Equal))
+                        | (Op::Geq, Some(Ordering::Greater | Ordering::Equal))
+                ))
+            }
+            (Op::Add, lhs, rhs) => match (self.as_str(lhs), self.as_str(rhs)) {
+                (Some(lhs), Some(rhs)) => {
+                    let string = format!("{lhs}{rhs}").into();
+                    self.alloc(Object::Str(string))
+                }
+                _ => return Err(self.mismatched(op, lhs, rhs)),
+            },
+            _ => return Err(self.mismatched(op, lhs, rhs)),
+        })
+    }
+
+    pub(super) fn unary_op(&self, op: Op, value: Value) -> RunResult<Value> {
+        Ok(match (op, value) {
+            (Op::Neg, Value::Int(int)) => Value::Int(int.checked_neg().ok_or("integer overflow")?),
+            (Op::Neg, Value::Float(float)) => Value::Float(-float),
+            (Op::Not, Value::Bool(bool)) => Value::Bool(!bool),
+            (Op::Not, Value::Int(int)) => Value::Int(!int),
+            (op, value) => {
+                return Err(format!(
+                    "cannot apply `{}` to {}",
+                    symbol(op),
+                    self.type_name(value)
+                ));
+            }
+        })
+    }
+
+    pub(super) fn index(&self, array: Value, index: Value) -> RunResult<Value> {
+        let values = self.array(array, index)?;
+        let i = array_index(values.len(), index)?;
+        Ok(values[i])
+    }
+
+    pub(super) fn set_index(
+        &mut self,
+        array: Value,
+        index: Value,
+        value: Value,
+    ) -> RunResult<Value> {
+        let mut values = self.array(array, index)?.to_vec();
+        let i = array_index(values.len(), index)?;
+        values[i] = value;
+        Ok(self.alloc(Object::Array(values)))
+    }
+
+    /// Gets the field named by the `Str` constant `name`, or if `value` is
+    /// given, makes a copy of `base` with the field replaced.
+    pub(super) fn field(
+        &mut self,
+        base: Value,
+        name: u32,
+        value: Option<Value>,
+    ) -> RunResult<Value> {
+        let Constant::Str(name) = &self.module.constants[name as usize] else {
+            unreachable!("checked when the module was loaded")
+        };
+
+        let object = match base {
+            Value::Obj(obj) => Some(self.heap.get(obj)),
+            _ => None,
+        };
+        let found = match object {
+            Some(Object::Tuple(values)) => name
+                .strip_prefix('_')
+                .and_then(|index| index.parse::<usize>().ok())
+                .filter(|&index| index < values.len())
+                .map(|index| (index, Object::Tuple(values.clone()))),
+            Some(Object::Record { shape, fields }) => {
+                match &self.module.shapes[*shape as usize].fields {
+                    Fields::Named(names) => {
+                        names.iter().position(|field| field == name).map(|index| {
+                            let record = Object::Record {
+                                shape: *shape,
+                                fields: fields.clone(),
+                            };
+                            (index, record)
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let Some((index, mut object)) = found else {
+            return Err(format!("{} has no field `{name}`", self.type_name(base)));
+        };
+
+        let (Object::Tuple(values) | Object::Record { fields: values, .. }) = &mut object else {
+            unreachable!("only tuples and records have fields")
+        };
+        match value {
+            None => Ok(values[index]),
+            Some(value) => {
+                values[index] = value;
+                Ok(self.alloc(object))
+            }
+        }
+    }
+
+    pub(super) fn element(&self, value: Value, n: u32) -> RunResult<Value> {
+        let values = match value {
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Object::Array(values)
+                | Object::Tuple(values)
+                | Object::Record { fields: values, .. } => Some(values),
+                _ => None,
+            },
+            _ => None,
+        };
+        values
+            .and_then(|values| values.get(n as usize).copied())
+            .ok_or_else(|| format!("{} has no element {n}", self.type_name(value)))
+    }
+
+    pub(super) fn len(&self, value: Value) -> RunResult<usize> {
+        let len = match value {
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Object::Array(values) | Object::Tuple(values) => Some(values.len()),
+                Object::Str(string) => Some(string.chars().count()),
+                _ => None,
+            },
+            _ => None,
+        };
+        len.ok_or_else(|| format!("{} has no length", self.type_name(value)))
+    }
+
+    fn array(&self, array: Value, index: Value) -> RunResult<&[Value]> {
+        if let (Value::Obj(obj), Value::Int(_)) = (array, index)
+            && let Object::Array(values) = self.heap.get(obj)
+        {
+            return Ok(values);
+        }
+        Err(format!(
+            "cannot index {} with {}",
+            self.type_name(array),
+            self.type_name(index)
+        ))
+    }
+
+    fn mismatched(&self, op: Op, lhs: Value, rhs: Value) -> String {
+        format!(
+            "cannot apply `{}` to {} and {}",
+            symbol(op),
+            self.type_name(lhs),
+            self.type_name(rhs)
+        )
+    }
+}
+
+/// Checks that `index` is in bounds for an array of `len` elements.
+fn array_index(len: usize, index: Value) -> RunResult<usize> {
+    let Value::Int(index) = index else {
+        unreachable!("checked by the caller")
+    };
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < len)
+        .ok_or_else(|| format!("index {index} is out of bounds for an array of length {len}"))
+}
+
+/// How an operator is written in source, for error messages.
+fn symbol(op: Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub | Op::Neg => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::Exp => "**",
+        Op::BitAnd => "&",
+        Op::BitOr => "|",
+        Op::BitXor => "^",
+        Op::Eq => "==",
+        Op::Neq => "!=",
+        Op::Lt => "<",
+        Op::Gt => ">",
+        Op::Leq => "<=",
+        Op::Geq => ">=",
+        Op::Not => "!",
+        _ => unreachable!("not an operator"),
+    }
+}
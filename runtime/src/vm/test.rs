@@ -0,0 +1,674 @@
+use std::io;
+
+use super::{RuntimeError, TraceFrame, Vm};
+use crate::{
+    GcConfig, GcStats, Value,
+    bytecode::{Capture, Constant, DecodeError, Fields, Function, Module, Op, Shape, VERSION},
+    heap::{Closure, Object, Upvalue},
+};
+
+fn function(name: &str, arity: u8, code: Vec<Op>) -> Function {
+    Function {
+        name: name.into(),
+        arity,
+        captures: Vec::new(),
+        code,
+        lines: Vec::new(),
+    }
+}
+
+/// A module whose entry point is its first function.
+fn module(constants: Vec<Constant>, functions: Vec<Function>) -> Module {
+    Module {
+        constants,
+        functions,
+        ..Module::default()
+    }
+}
+
+/// Runs `module`, returning the displayed result (or the runtime error)
+/// along with everything it printed. It's run again collecting garbage on
+/// every allocation, which has to make no difference.
+fn run(module: Module) -> (Result<String, RuntimeError>, String) {
+    let run = |module, config| {
+        let mut out = Vec::new();
+        let result = Vm::new(module, &mut out).and_then(|vm| {
+            let mut vm = vm.with_gc(config);
+            let value = vm.run()?;
+            Ok(vm.display(value))
+        });
+        (result, String::from_utf8(out).unwrap())
+    };
+
+    let stressed = GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    };
+    let result = run(module.clone(), GcConfig::default());
+    assert_eq!(run(module, stressed), result);
+    result
+}
+
+fn eval(module: Module) -> String {
+    match run(module) {
+        (Ok(value), _) => value,
+        (Err(err), _) => panic!("{err}"),
+    }
+}
+
+fn error(module: Module) -> RuntimeError {
+    match run(module) {
+        (Ok(value), _) => panic!("expected an error, got {value}"),
+        (Err(err), _) => err,
+    }
+}
+
+/// `fib` as a global, and an entry point that calls it with 15.
+fn fib() -> Module {
+    use Op::*;
+
+    Module {
+        globals: vec!["fib".into()],
+        ..module(
+            vec![Constant::Int(1), Constant::Int(2), Constant::Int(15)],
+            vec![
+                function(
+                    "<main>",
+                    0,
+                    vec![
+                        Closure(1),
+                        SetGlobal(0),
+                        GetGlobal(0),
+                        Const(2),
+                        Call(1),
+                        Return,
+                    ],
+                ),
+                function(
+                    "fib",
+                    1,
+                    vec![
+                        GetLocal(0),
+                        Const(1),
+                        Lt,
+                        JumpIfFalse(6),
+                        GetLocal(0),
+                        Return,
+                        GetGlobal(0),
+                        GetLocal(0),
+                        Const(0),
+                        Sub,
+                        Call(1),
+                        GetGlobal(0),
+                        GetLocal(0),
+                        Const(1),
+                        Sub,
+                        Call(1),
+                        Add,
+                        Return,
+                    ],
+                ),
+            ],
+        )
+    }
+}
+
+#[test]
+fn arithmetic_and_comparisons() {
+    use Op::*;
+
+    let constants = vec![
+        Constant::Int(7),
+        Constant::Int(2),
+        Constant::Float(1.5),
+        Constant::Str("con".into()),
+        Constant::Str("cat".into()),
+        Constant::Char('a'),
+    ];
+    let code = vec![
+        // 7 ** 2 - 7 / 2
+        Const(0),
+        Const(1),
+        Exp,
+        Const(0),
+        Const(1),
+        Div,
+        Sub,
+        // 1.5 * 1.5
+        Const(2),
+        Const(2),
+        Mul,
+        Const(3),
+        Const(4),
+        Add,
+        // "con" < "cat"
+        Const(3),
+        Const(4),
+        Lt,
+        // 'a' == 'a'
+        Const(5),
+        Const(5),
+        Eq,
+        // !(6 ^ 2)
+        Const(0),
+        Not,
+        Tuple(6),
+        Return,
+    ];
+    assert_eq!(
+        eval(module(constants, vec![function("<main>", 0, code)])),
+        r#"(46, 2.25, "concat", false, true, -8)"#
+    );
+}
+
+#[test]
+fn calls_and_natives() {
+    use Op::*;
+
+    assert_eq!(eval(fib()), "610");
+
+    let constants = vec![Constant::Str("héllo".into())];
+    let code = vec![
+        Native(0),
+        Const(0),
+        Call(1),
+        Pop,
+        Native(1),
+        Const(0),
+        Call(1),
+        Return,
+    ];
+    let module = Module {
+        natives: vec!["println".into(), "len".into()],
+        ..module(constants, vec![function("<main>", 0, code)])
+    };
+    assert_eq!(run(module), (Ok("5".into()), "héllo\n".into()));
+}
+
+#[test]
+fn closures_share_captured_variables() {
+    use Op::*;
+
+    let counter = Function {
+        captures: vec![Capture::Upvalue(0)],
+        ..function(
+            "increment",
+            0,
+            vec![
+                GetUpvalue(0),
+                Const(1),
+                Add,
+                SetUpvalue(0),
+                GetUpvalue(0),
+                Return,
+            ],
+        )
+    };
+    let get = Function {
+        captures: vec![Capture::Upvalue(0)],
+        ..function("get", 0, vec![GetUpvalue(0), Return])
+    };
+    // Makes the counter and getter from inside a closure, so they capture
+    // the variable through an upvalue of their own.
+    let make_inner = Function {
+        captures: vec![Capture::Local(0)],
+        ..function(
+            "make_inner",
+            0,
+            vec![Closure(3), Closure(4), Tuple(2), Return],
+        )
+    };
+    let make = function(
+        "make",
+        0,
+        vec![
+            Const(0),
+            Closure(2),
+            Call(0),
+            // Bumping the variable while it's still on the stack is seen by
+            // the closures too.
+            GetLocal(0),
+            Const(1),
+            Add,
+            SetLocal(0),
+            Return,
+        ],
+    );
+    let main = function(
+        "<main>",
+        0,
+        vec![
+            Closure(1),
+            Call(0),
+            GetLocal(0),
+            Element(0),
+            Call(0),
+            Pop,
+            GetLocal(0),
+            Element(0),
+            Call(0),
+            Pop,
+            GetLocal(0),
+            Element(1),
+            Call(0),
+            Return,
+        ],
+    );
+
+    let constants = vec![Constant::Int(0), Constant::Int(1)];
+    assert_eq!(
+        eval(module(
+            constants,
+            vec![main, make, make_inner, counter, get]
+        )),
+        "3"
+    );
+}
+
+#[test]
+fn aggregates_are_values() {
+    use Op::*;
+
+    let constants = vec![
+        Constant::Int(1),
+        Constant::Int(2),
+        Constant::Str("x".into()),
+        Constant::Int(0),
+    ];
+    let code = vec![
+        // let p = Point { x: 1, y: 2 }
+        Const(0),
+        Const(1),
+        Record(0),
+        // (p, p with { x: 2 }, Option::Some([1, 2] with [0] = 2), p.x)
+        GetLocal(0),
+        GetLocal(0),
+        Const(1),
+        SetField(2),
+        Const(0),
+        Const(1),
+        Array(2),
+        Const(3),
+        Const(1),
+        SetIndex,
+        Record(1),
+        Dup,
+        IsShape(1),
+        GetLocal(0),
+        GetField(2),
+        Tuple(5),
+        EndScope(1),
+        Return,
+    ];
+    let module = Module {
+        shapes: vec![
+            Shape {
+                name: "Point".into(),
+                fields: Fields::Named(vec!["x".into(), "y".into()]),
+            },
+            Shape {
+                name: "Option::Some".into(),
+                fields: Fields::Tuple(1),
+            },
+        ],
+        ..module(constants, vec![function("<main>", 0, code)])
+    };
+    assert_eq!(
+        eval(module),
+        "(Point { x: 1, y: 2 }, Point { x: 2, y: 2 }, Option::Some([2, 2]), true, 1)"
+    );
+}
+
+#[test]
+fn report_runtime_errors() {
+    use Op::*;
+
+    let constants = vec![Constant::Int(1), Constant::Int(0)];
+    let divide = Function {
+        lines: vec![2, 2, 3, 3],
+        ..function("divide", 0, vec![Const(0), Const(1), Div, Return])
+    };
+    let err = error(module(
+        constants.clone(),
+        vec![
+            function("<main>", 0, vec![Closure(1), Call(0), Return]),
+            divide,
+        ],
+    ));
+    assert_eq!(err.message, "division by zero");
+    assert_eq!(
+        err.trace,
+        [
+            TraceFrame {
+                function: "divide".into(),
+                line: Some(3),
+            },
+            TraceFrame {
+                function: "<main>".into(),
+                line: None,
+            },
+        ]
+    );
+
+    let err = error(module(
+        constants.clone(),
+        vec![function("<main>", 0, vec![Const(0), Call(0), Return])],
+    ));
+    assert_eq!(err.message, "Int is not a function");
+
+    let err = error(module(
+        constants.clone(),
+        vec![
+            function(
+                "<main>",
+                0,
+                vec![Closure(1), Const(0), Const(0), Call(2), Return],
+            ),
+            function("f", 1, vec![GetLocal(0), Return]),
+        ],
+    ));
+    assert_eq!(
+        err.message,
+        "`<fn f>` takes 1 argument, but 2 were supplied"
+    );
+
+    let err = error(module(
+        constants.clone(),
+        vec![
+            function("<main>", 0, vec![Closure(1), Const(0), Call(1), Return]),
+            function("f", 2, vec![GetLocal(0), Return]),
+        ],
+    ));
+    assert_eq!(
+        err.message,
+        "`<fn f>` takes 2 arguments, but 1 was supplied"
+    );
+
+    // Calling through a global rather than allocating a closure per call
+    // keeps this quick when every allocation collects garbage.
+    let err = error(Module {
+        globals: vec!["forever".into()],
+        ..module(
+            constants.clone(),
+            vec![
+                function(
+                    "<main>",
+                    0,
+                    vec![Closure(1), SetGlobal(0), GetGlobal(0), Call(0), Return],
+                ),
+                function("forever", 0, vec![GetGlobal(0), Call(0), Return]),
+            ],
+        )
+    });
+    assert_eq!(
+        err.message,
+        "stack overflow: calls nested more than 10000 deep"
+    );
+    assert_eq!(
+        err.to_string(),
+        "stack overflow: calls nested more than 10000 deep
+    in `forever`
+    ... repeated 9998 more times
+    in `<main>`"
+    );
+
+    let err = error(module(
+        constants.clone(),
+        vec![function(
+            "<main>",
+            0,
+            vec![Const(0), Array(1), Const(0), Index, Return],
+        )],
+    ));
+    assert_eq!(
+        err.message,
+        "index 1 is out of bounds for an array of length 1"
+    );
+}
+
+#[test]
+fn reject_invalid_modules() {
+    use Op::*;
+
+    let err = error(Module {
+        natives: vec!["launch".into()],
+        ..module(Vec::new(), vec![function("<main>", 0, vec![Unit, Return])])
+    });
+    assert_eq!(err.message, "unknown native function `launch`");
+
+    let err = error(module(
+        Vec::new(),
+        vec![function("<main>", 0, vec![Jump(5), Return])],
+    ));
+    assert_eq!(
+        err.message,
+        "invalid module: instruction 5 doesn't exist, there are only 2"
+    );
+
+    let err = error(module(Vec::new(), vec![function("<main>", 0, vec![Unit])]));
+    assert_eq!(
+        err.message,
+        "invalid module: function ended without returning"
+    );
+
+    let err = error(module(
+        Vec::new(),
+        vec![function("<main>", 0, vec![Pop, Pop, Return])],
+    ));
+    assert_eq!(
+        err.message,
+        "invalid module: instruction 0 of `<main>` pops 1 value, but there are only 0"
+    );
+
+    let err = error(module(
+        Vec::new(),
+        vec![function("<main>", 0, vec![GetUpvalue(5), Return])],
+    ));
+    assert_eq!(
+        err.message,
+        "invalid module: upvalue 5 of `<main>` doesn't exist, there are only 0"
+    );
+
+    let err = error(module(
+        Vec::new(),
+        vec![
+            function("<main>", 0, vec![Unit, Closure(1), EndScope(1), Return]),
+            Function {
+                captures: vec![Capture::Local(3)],
+                ..function("f", 0, vec![GetUpvalue(0), Return])
+            },
+        ],
+    ));
+    assert_eq!(
+        err.message,
+        "invalid module: local 3 doesn't exist at instruction 1 of `<main>`, there are only 1"
+    );
+
+    let err = error(module(
+        Vec::new(),
+        vec![function(
+            "<main>",
+            0,
+            vec![True, JumpIfFalse(3), Unit, Return],
+        )],
+    ));
+    assert_eq!(
+        err.message,
+        "invalid module: instruction 3 of `<main>` is reached with 1 and with 0 values on the stack"
+    );
+}
+
+#[test]
+fn encode_and_decode_modules() {
+    let mut fib = fib();
+    fib.source = "fib.ptn".into();
+    fib.functions[1].lines = [vec![1; 6], vec![2; 12]].concat();
+    let bytes = fib.encode();
+    assert_eq!(Module::decode(&bytes), Ok(fib));
+
+    assert!(Module::decode(&bytes[..bytes.len() - 1]).is_err());
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Module::decode(&trailing).unwrap_err().to_string(),
+        format!(
+            "invalid module: unexpected data after the end of the module at byte {}",
+            bytes.len()
+        )
+    );
+}
+
+#[test]
+fn refuse_other_format_versions() {
+    let mut bytes = fib().encode();
+    assert_eq!(Module::decode(b"#!/bin/sh"), Err(DecodeError::NotAModule));
+
+    bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    let err = Module::decode(&bytes).unwrap_err();
+    assert_eq!(err, DecodeError::Version(VERSION + 1));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "the module was compiled for format version {}, but this runtime only supports \
+             version {VERSION}; recompile it with a matching compiler",
+            VERSION + 1
+        )
+    );
+}
+
+/// A module that does nothing, for tests that drive the VM directly.
+fn empty() -> Module {
+    Module {
+        globals: vec!["kept".into()],
+        ..module(
+            Vec::new(),
+            vec![function("<main>", 0, vec![Op::Unit, Op::Return])],
+        )
+    }
+}
+
+#[test]
+fn collect_unreachable_objects() {
+    let mut vm = Vm::new(empty(), io::sink()).unwrap();
+    let string = vm.alloc(Object::Str("kept".into()));
+    let Value::Obj(upvalue) = vm.alloc(Object::Upvalue(Upvalue::Closed(string))) else {
+        unreachable!()
+    };
+    let closure = vm.alloc(Object::Closure(Closure {
+        function: 0,
+        upvalues: vec![upvalue],
+    }));
+    vm.globals[0] = vm.alloc(Object::Array(vec![closure, string]));
+    for i in 0..100 {
+        vm.alloc(Object::Tuple(vec![Value::Int(i)]));
+    }
+    assert_eq!(vm.gc_stats().objects, 104);
+
+    vm.collect_garbage();
+    assert_eq!(vm.gc_stats().objects, 4);
+    assert_eq!(vm.gc_stats().collections, 1);
+    assert_eq!(vm.display(vm.globals[0]), r#"[<main>, "kept"]"#);
+}
+
+#[test]
+fn collect_when_the_heap_outgrows_its_threshold() {
+    let config = GcConfig {
+        initial_threshold: 4096,
+        growth_factor: 2.0,
+        stress: false,
+    };
+    let mut vm = Vm::new(empty(), io::sink()).unwrap().with_gc(config);
+    for i in 0..10_000 {
+        vm.alloc(Object::Tuple(vec![Value::Int(i); 4]));
+    }
+    let stats = vm.gc_stats();
+    assert!(stats.collections > 10, "{stats:?}");
+    assert!(stats.bytes < 2 * 4096, "{stats:?}");
+
+    let stressed = GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    };
+    let mut vm = Vm::new(empty(), io::sink()).unwrap().with_gc(stressed);
+    for _ in 0..10 {
+        vm.alloc(Object::Str("garbage".into()));
+    }
+    let GcStats {
+        collections,
+        objects,
+        ..
+    } = vm.gc_stats();
+    assert_eq!((collections, objects), (10, 1));
+}
+
+/// A `greet` global that joins a greeting to its argument with the host's
+/// `join` native, set up by an init function separate from the entry point.
+fn greet() -> Module {
+    use Op::*;
+
+    Module {
+        natives: vec!["join".into()],
+        globals: vec!["greet".into()],
+        init: 1,
+        ..module(
+            vec![Constant::Str("hello, ".into())],
+            vec![
+                function("<main>", 0, vec![Unit, Return]),
+                function("<init>", 0, vec![Closure(2), SetGlobal(0), Unit, Return]),
+                function(
+                    "greet",
+                    1,
+                    vec![Native(0), Const(0), GetLocal(0), Call(2), Return],
+                ),
+            ],
+        )
+    }
+}
+
+#[test]
+fn call_into_the_module_from_the_host() {
+    let stressed = GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    };
+    for config in [GcConfig::default(), stressed] {
+        let mut vm = Vm::builder()
+            .output(io::sink())
+            .gc(config)
+            .native("join", |a: String, b: String| a + &b)
+            .load(greet())
+            .unwrap();
+        let greeting: String = vm.call("greet", ("world",)).unwrap();
+        assert_eq!(greeting, "hello, world");
+    }
+
+    let mut vm = Vm::builder()
+        .native("join", |a: String, b: String| a + &b)
+        .load(greet())
+        .unwrap();
+    assert_eq!(
+        vm.call::<()>("missing", ()).unwrap_err().message,
+        "no global named `missing`"
+    );
+    assert_eq!(
+        vm.call::<i64>("greet", ("world",)).unwrap_err().message,
+        "`greet` returned the wrong type: expected Int, found Str"
+    );
+    let err = vm.call::<String>("greet", (1,)).unwrap_err();
+    assert_eq!(err.message, "expected Str, found Int");
+    assert_eq!(err.trace[0].function, "greet");
+
+    let mut vm = Vm::builder()
+        .native("join", |_: String, b: String| {
+            Err::<String, _>(format!("can't greet {b}"))
+        })
+        .load(greet())
+        .unwrap();
+    let err = vm.call::<String>("greet", ("nobody",)).unwrap_err();
+    assert_eq!(err.message, "can't greet nobody");
+
+    assert_eq!(
+        Vm::new(greet(), io::sink()).err().unwrap().message,
+        "unknown native function `join`"
+    );
+}